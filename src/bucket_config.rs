@@ -0,0 +1,1186 @@
+use aws_sdk_s3::primitives::{DateTime, DateTimeFormat};
+use aws_sdk_s3::types as s3;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Bucket settings managed by `hsc bucket`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Setting {
+    Versioning,
+    Lifecycle,
+    Cors,
+    Policy,
+    PublicAccessBlock,
+    OwnershipControls,
+    Encryption,
+    Tags,
+}
+
+/// All settings, in the order `bucket apply` applies them: public access
+/// and ownership come before the policy they may allow
+pub const SETTINGS: [Setting; 8] = [
+    Setting::Versioning,
+    Setting::OwnershipControls,
+    Setting::PublicAccessBlock,
+    Setting::Encryption,
+    Setting::Policy,
+    Setting::Lifecycle,
+    Setting::Cors,
+    Setting::Tags,
+];
+
+impl Setting {
+    pub fn name(self) -> &'static str {
+        match self {
+            Setting::Versioning => "versioning",
+            Setting::Lifecycle => "lifecycle",
+            Setting::Cors => "cors",
+            Setting::Policy => "policy",
+            Setting::PublicAccessBlock => "public-access-block",
+            Setting::OwnershipControls => "ownership-controls",
+            Setting::Encryption => "encryption",
+            Setting::Tags => "tags",
+        }
+    }
+}
+
+/// Parse a setting name such as `lifecycle` or `public-access-block`
+pub fn parse_setting(value: &str) -> Result<Setting, String> {
+    SETTINGS
+        .into_iter()
+        .find(|setting| setting.name() == value.to_ascii_lowercase())
+        .ok_or_else(|| {
+            format!(
+                "Invalid setting: {}. Use versioning, lifecycle, cors, policy, public-access-block, ownership-controls, encryption or tags",
+                value
+            )
+        })
+}
+
+/// Document format of configuration files
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    Yaml,
+}
+
+/// Parse `--format json|yaml`
+pub fn parse_format(value: &str) -> Result<Format, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "json" => Ok(Format::Json),
+        "yaml" | "yml" => Ok(Format::Yaml),
+        _ => Err(format!("Invalid format: {}. Use json or yaml", value)),
+    }
+}
+
+/// Read a JSON or YAML document from a file, or from stdin for "-"
+///
+/// `.yaml` and `.yml` files are YAML and `.json` files JSON; other input is
+/// taken as JSON when it starts with '{' or '['.
+pub fn read_document<T: DeserializeOwned>(path: &str) -> Result<T, String> {
+    let text = if path == "-" {
+        std::io::read_to_string(std::io::stdin())
+            .map_err(|e| format!("Cannot read stdin: {}", e))?
+    } else {
+        std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?
+    };
+
+    let lower = path.to_ascii_lowercase();
+    let json = if lower.ends_with(".yaml") || lower.ends_with(".yml") {
+        false
+    } else {
+        lower.ends_with(".json") || text.trim_start().starts_with(['{', '['])
+    };
+
+    if json {
+        serde_json::from_str(&text).map_err(|e| format!("Invalid JSON in {}: {}", path, e))
+    } else {
+        serde_norway::from_str(&text).map_err(|e| format!("Invalid YAML in {}: {}", path, e))
+    }
+}
+
+/// Serialize a document as pretty-printed JSON or as YAML
+pub fn write_document<T: Serialize>(value: &T, format: Format) -> Result<String, String> {
+    match format {
+        Format::Json => serde_json::to_string_pretty(value).map_err(|e| e.to_string()),
+        Format::Yaml => serde_norway::to_string(value).map_err(|e| e.to_string()),
+    }
+}
+
+/// Value of an S3 enum field, refusing values S3 does not define
+fn known<T: for<'a> From<&'a str>>(value: &str, values: &[&str], field: &str) -> Result<T, String> {
+    if values.contains(&value) {
+        Ok(T::from(value))
+    } else {
+        Err(format!(
+            "Invalid {}: {}. Use {}",
+            field,
+            value,
+            values.join(", ")
+        ))
+    }
+}
+
+/// Lifecycle dates: ISO 8601 timestamps, or dates for midnight UTC
+fn parse_date(value: &str) -> Result<DateTime, String> {
+    let value = if value.len() == 10 {
+        format!("{}T00:00:00Z", value)
+    } else {
+        value.to_string()
+    };
+    DateTime::from_str(&value, DateTimeFormat::DateTime)
+        .map_err(|_| format!("Invalid date '{}': use YYYY-MM-DD", value))
+}
+
+fn format_date(date: &DateTime) -> String {
+    date.fmt(DateTimeFormat::DateTime).unwrap_or_default()
+}
+
+/// `Tag` of lifecycle rule filters
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+pub struct Tag {
+    pub key: String,
+    pub value: String,
+}
+
+impl Tag {
+    fn to_sdk(&self) -> Result<s3::Tag, String> {
+        s3::Tag::builder()
+            .key(&self.key)
+            .value(&self.value)
+            .build()
+            .map_err(|e| e.to_string())
+    }
+
+    fn from_sdk(tag: &s3::Tag) -> Self {
+        Tag {
+            key: tag.key().to_string(),
+            value: tag.value().to_string(),
+        }
+    }
+}
+
+/// Versioning state, as `{"Status": "Enabled"}`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+pub struct Versioning {
+    /// Enabled or Suspended; unset for buckets that never had versioning
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(default, rename = "MFADelete", skip_serializing_if = "Option::is_none")]
+    pub mfa_delete: Option<String>,
+}
+
+impl Versioning {
+    pub fn to_sdk(&self) -> Result<s3::VersioningConfiguration, String> {
+        let status = self
+            .status
+            .as_deref()
+            .map(|s| known(s, s3::BucketVersioningStatus::values(), "versioning status"))
+            .transpose()?;
+        let mfa_delete = self
+            .mfa_delete
+            .as_deref()
+            .map(|s| known(s, s3::MfaDelete::values(), "MFADelete"))
+            .transpose()?;
+        Ok(s3::VersioningConfiguration::builder()
+            .set_status(status)
+            .set_mfa_delete(mfa_delete)
+            .build())
+    }
+
+    pub fn from_sdk(
+        status: Option<&s3::BucketVersioningStatus>,
+        mfa_delete: Option<&s3::MfaDeleteStatus>,
+    ) -> Self {
+        Versioning {
+            status: status.map(|s| s.as_str().to_string()),
+            mfa_delete: mfa_delete.map(|s| s.as_str().to_string()),
+        }
+    }
+}
+
+/// Lifecycle rules, as `{"Rules": [...]}`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+pub struct Lifecycle {
+    pub rules: Vec<LifecycleRule>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+pub struct LifecycleRule {
+    #[serde(default, rename = "ID", skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Enabled or Disabled
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<LifecycleFilter>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expiration: Option<Expiration>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transitions: Vec<Transition>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub noncurrent_version_expiration: Option<NoncurrentVersionExpiration>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub noncurrent_version_transitions: Vec<NoncurrentVersionTransition>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub abort_incomplete_multipart_upload: Option<AbortIncompleteMultipartUpload>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+pub struct LifecycleFilter {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<Tag>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub object_size_greater_than: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub object_size_less_than: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub and: Option<LifecycleAnd>,
+}
+
+/// Filter conditions that must all hold
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+pub struct LifecycleAnd {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<Tag>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub object_size_greater_than: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub object_size_less_than: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+pub struct Expiration {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub days: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expired_object_delete_marker: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+pub struct Transition {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub days: Option<i32>,
+    pub storage_class: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+pub struct NoncurrentVersionExpiration {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub noncurrent_days: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub newer_noncurrent_versions: Option<i32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+pub struct NoncurrentVersionTransition {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub noncurrent_days: Option<i32>,
+    pub storage_class: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub newer_noncurrent_versions: Option<i32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+pub struct AbortIncompleteMultipartUpload {
+    pub days_after_initiation: i32,
+}
+
+impl Lifecycle {
+    pub fn to_sdk(&self) -> Result<s3::BucketLifecycleConfiguration, String> {
+        let rules = self
+            .rules
+            .iter()
+            .map(LifecycleRule::to_sdk)
+            .collect::<Result<Vec<_>, _>>()?;
+        s3::BucketLifecycleConfiguration::builder()
+            .set_rules(Some(rules))
+            .build()
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn from_sdk(rules: &[s3::LifecycleRule]) -> Self {
+        Lifecycle {
+            rules: rules.iter().map(LifecycleRule::from_sdk).collect(),
+        }
+    }
+}
+
+impl LifecycleRule {
+    fn to_sdk(&self) -> Result<s3::LifecycleRule, String> {
+        let transition_class =
+            |class: &str| known(class, s3::TransitionStorageClass::values(), "storage class");
+
+        let filter = self
+            .filter
+            .as_ref()
+            .map(|filter| -> Result<_, String> {
+                let and = filter
+                    .and
+                    .as_ref()
+                    .map(|and| -> Result<_, String> {
+                        let tags = and.tags.iter().map(Tag::to_sdk).collect::<Result<_, _>>()?;
+                        Ok(s3::LifecycleRuleAndOperator::builder()
+                            .set_prefix(and.prefix.clone())
+                            .set_tags((!and.tags.is_empty()).then_some(tags))
+                            .set_object_size_greater_than(and.object_size_greater_than)
+                            .set_object_size_less_than(and.object_size_less_than)
+                            .build())
+                    })
+                    .transpose()?;
+                Ok(s3::LifecycleRuleFilter::builder()
+                    .set_prefix(filter.prefix.clone())
+                    .set_tag(filter.tag.as_ref().map(Tag::to_sdk).transpose()?)
+                    .set_object_size_greater_than(filter.object_size_greater_than)
+                    .set_object_size_less_than(filter.object_size_less_than)
+                    .set_and(and)
+                    .build())
+            })
+            .transpose()?;
+
+        let expiration = self
+            .expiration
+            .as_ref()
+            .map(|e| -> Result<_, String> {
+                Ok(s3::LifecycleExpiration::builder()
+                    .set_date(e.date.as_deref().map(parse_date).transpose()?)
+                    .set_days(e.days)
+                    .set_expired_object_delete_marker(e.expired_object_delete_marker)
+                    .build())
+            })
+            .transpose()?;
+
+        let transitions = self
+            .transitions
+            .iter()
+            .map(|t| -> Result<_, String> {
+                Ok(s3::Transition::builder()
+                    .set_date(t.date.as_deref().map(parse_date).transpose()?)
+                    .set_days(t.days)
+                    .storage_class(transition_class(&t.storage_class)?)
+                    .build())
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let noncurrent_transitions = self
+            .noncurrent_version_transitions
+            .iter()
+            .map(|t| -> Result<_, String> {
+                Ok(s3::NoncurrentVersionTransition::builder()
+                    .set_noncurrent_days(t.noncurrent_days)
+                    .storage_class(transition_class(&t.storage_class)?)
+                    .set_newer_noncurrent_versions(t.newer_noncurrent_versions)
+                    .build())
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        s3::LifecycleRule::builder()
+            .set_id(self.id.clone())
+            .status(known::<s3::ExpirationStatus>(
+                &self.status,
+                s3::ExpirationStatus::values(),
+                "rule status",
+            )?)
+            .set_filter(filter)
+            .set_expiration(expiration)
+            .set_transitions((!transitions.is_empty()).then_some(transitions))
+            .set_noncurrent_version_expiration(self.noncurrent_version_expiration.as_ref().map(
+                |e| {
+                    s3::NoncurrentVersionExpiration::builder()
+                        .set_noncurrent_days(e.noncurrent_days)
+                        .set_newer_noncurrent_versions(e.newer_noncurrent_versions)
+                        .build()
+                },
+            ))
+            .set_noncurrent_version_transitions(
+                (!noncurrent_transitions.is_empty()).then_some(noncurrent_transitions),
+            )
+            .set_abort_incomplete_multipart_upload(
+                self.abort_incomplete_multipart_upload.as_ref().map(|a| {
+                    s3::AbortIncompleteMultipartUpload::builder()
+                        .days_after_initiation(a.days_after_initiation)
+                        .build()
+                }),
+            )
+            .build()
+            .map_err(|e| e.to_string())
+    }
+
+    fn from_sdk(rule: &s3::LifecycleRule) -> Self {
+        // Rules written with the deprecated top-level Prefix have no Filter;
+        // the prefix is carried over so that putting the rule back keeps it
+        #[allow(deprecated)]
+        let legacy_filter = rule.prefix().map(|prefix| LifecycleFilter {
+            prefix: Some(prefix.to_string()),
+            ..Default::default()
+        });
+
+        LifecycleRule {
+            id: rule.id().map(String::from),
+            status: rule.status().as_str().to_string(),
+            filter: rule
+                .filter()
+                .map(|filter| LifecycleFilter {
+                    prefix: filter.prefix().map(String::from),
+                    tag: filter.tag().map(Tag::from_sdk),
+                    object_size_greater_than: filter.object_size_greater_than(),
+                    object_size_less_than: filter.object_size_less_than(),
+                    and: filter.and().map(|and| LifecycleAnd {
+                        prefix: and.prefix().map(String::from),
+                        tags: and.tags().iter().map(Tag::from_sdk).collect(),
+                        object_size_greater_than: and.object_size_greater_than(),
+                        object_size_less_than: and.object_size_less_than(),
+                    }),
+                })
+                .or(legacy_filter),
+            expiration: rule.expiration().map(|e| Expiration {
+                date: e.date().map(format_date),
+                days: e.days(),
+                expired_object_delete_marker: e.expired_object_delete_marker(),
+            }),
+            transitions: rule
+                .transitions()
+                .iter()
+                .map(|t| Transition {
+                    date: t.date().map(format_date),
+                    days: t.days(),
+                    storage_class: t
+                        .storage_class()
+                        .map(|c| c.as_str().to_string())
+                        .unwrap_or_default(),
+                })
+                .collect(),
+            noncurrent_version_expiration: rule.noncurrent_version_expiration().map(|e| {
+                NoncurrentVersionExpiration {
+                    noncurrent_days: e.noncurrent_days(),
+                    newer_noncurrent_versions: e.newer_noncurrent_versions(),
+                }
+            }),
+            noncurrent_version_transitions: rule
+                .noncurrent_version_transitions()
+                .iter()
+                .map(|t| NoncurrentVersionTransition {
+                    noncurrent_days: t.noncurrent_days(),
+                    storage_class: t
+                        .storage_class()
+                        .map(|c| c.as_str().to_string())
+                        .unwrap_or_default(),
+                    newer_noncurrent_versions: t.newer_noncurrent_versions(),
+                })
+                .collect(),
+            abort_incomplete_multipart_upload: rule.abort_incomplete_multipart_upload().and_then(
+                |a| {
+                    a.days_after_initiation()
+                        .map(|days| AbortIncompleteMultipartUpload {
+                            days_after_initiation: days,
+                        })
+                },
+            ),
+        }
+    }
+}
+
+/// CORS rules, as `{"CORSRules": [...]}`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Cors {
+    #[serde(rename = "CORSRules")]
+    pub cors_rules: Vec<CorsRule>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+pub struct CorsRule {
+    #[serde(default, rename = "ID", skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_headers: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_origins: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub expose_headers: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age_seconds: Option<i32>,
+}
+
+impl Cors {
+    pub fn to_sdk(&self) -> Result<s3::CorsConfiguration, String> {
+        let rules = self
+            .cors_rules
+            .iter()
+            .map(|rule| {
+                s3::CorsRule::builder()
+                    .set_id(rule.id.clone())
+                    .set_allowed_headers(
+                        (!rule.allowed_headers.is_empty()).then(|| rule.allowed_headers.clone()),
+                    )
+                    .set_allowed_methods(Some(rule.allowed_methods.clone()))
+                    .set_allowed_origins(Some(rule.allowed_origins.clone()))
+                    .set_expose_headers(
+                        (!rule.expose_headers.is_empty()).then(|| rule.expose_headers.clone()),
+                    )
+                    .set_max_age_seconds(rule.max_age_seconds)
+                    .build()
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        s3::CorsConfiguration::builder()
+            .set_cors_rules(Some(rules))
+            .build()
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn from_sdk(rules: &[s3::CorsRule]) -> Self {
+        Cors {
+            cors_rules: rules
+                .iter()
+                .map(|rule| CorsRule {
+                    id: rule.id().map(String::from),
+                    allowed_headers: rule.allowed_headers().to_vec(),
+                    allowed_methods: rule.allowed_methods().to_vec(),
+                    allowed_origins: rule.allowed_origins().to_vec(),
+                    expose_headers: rule.expose_headers().to_vec(),
+                    max_age_seconds: rule.max_age_seconds(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Public access block; unset flags are off
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+pub struct PublicAccessBlock {
+    #[serde(default)]
+    pub block_public_acls: bool,
+    #[serde(default)]
+    pub ignore_public_acls: bool,
+    #[serde(default)]
+    pub block_public_policy: bool,
+    #[serde(default)]
+    pub restrict_public_buckets: bool,
+}
+
+impl PublicAccessBlock {
+    pub fn to_sdk(&self) -> s3::PublicAccessBlockConfiguration {
+        s3::PublicAccessBlockConfiguration::builder()
+            .block_public_acls(self.block_public_acls)
+            .ignore_public_acls(self.ignore_public_acls)
+            .block_public_policy(self.block_public_policy)
+            .restrict_public_buckets(self.restrict_public_buckets)
+            .build()
+    }
+
+    pub fn from_sdk(config: &s3::PublicAccessBlockConfiguration) -> Self {
+        PublicAccessBlock {
+            block_public_acls: config.block_public_acls().unwrap_or(false),
+            ignore_public_acls: config.ignore_public_acls().unwrap_or(false),
+            block_public_policy: config.block_public_policy().unwrap_or(false),
+            restrict_public_buckets: config.restrict_public_buckets().unwrap_or(false),
+        }
+    }
+}
+
+/// Object ownership, as `{"Rules": [{"ObjectOwnership": "BucketOwnerEnforced"}]}`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+pub struct OwnershipControls {
+    pub rules: Vec<OwnershipRule>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+pub struct OwnershipRule {
+    pub object_ownership: String,
+}
+
+impl OwnershipControls {
+    pub fn to_sdk(&self) -> Result<s3::OwnershipControls, String> {
+        let rules = self
+            .rules
+            .iter()
+            .map(|rule| -> Result<_, String> {
+                s3::OwnershipControlsRule::builder()
+                    .object_ownership(known::<s3::ObjectOwnership>(
+                        &rule.object_ownership,
+                        s3::ObjectOwnership::values(),
+                        "object ownership",
+                    )?)
+                    .build()
+                    .map_err(|e| e.to_string())
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        s3::OwnershipControls::builder()
+            .set_rules(Some(rules))
+            .build()
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn from_sdk(controls: &s3::OwnershipControls) -> Self {
+        OwnershipControls {
+            rules: controls
+                .rules()
+                .iter()
+                .map(|rule| OwnershipRule {
+                    object_ownership: rule.object_ownership().as_str().to_string(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Default encryption, as `{"Rules": [{"ApplyServerSideEncryptionByDefault": {...}}]}`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+pub struct Encryption {
+    pub rules: Vec<EncryptionRule>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+pub struct EncryptionRule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub apply_server_side_encryption_by_default: Option<EncryptionDefault>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bucket_key_enabled: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EncryptionDefault {
+    /// AES256, aws:kms or aws:kms:dsse
+    #[serde(rename = "SSEAlgorithm")]
+    pub sse_algorithm: String,
+    #[serde(
+        default,
+        rename = "KMSMasterKeyID",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub kms_master_key_id: Option<String>,
+}
+
+impl Encryption {
+    pub fn to_sdk(&self) -> Result<s3::ServerSideEncryptionConfiguration, String> {
+        let rules = self
+            .rules
+            .iter()
+            .map(|rule| -> Result<_, String> {
+                let default = rule
+                    .apply_server_side_encryption_by_default
+                    .as_ref()
+                    .map(|default| -> Result<_, String> {
+                        s3::ServerSideEncryptionByDefault::builder()
+                            .sse_algorithm(known::<s3::ServerSideEncryption>(
+                                &default.sse_algorithm,
+                                s3::ServerSideEncryption::values(),
+                                "SSEAlgorithm",
+                            )?)
+                            .set_kms_master_key_id(default.kms_master_key_id.clone())
+                            .build()
+                            .map_err(|e| e.to_string())
+                    })
+                    .transpose()?;
+                Ok(s3::ServerSideEncryptionRule::builder()
+                    .set_apply_server_side_encryption_by_default(default)
+                    .set_bucket_key_enabled(rule.bucket_key_enabled)
+                    .build())
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        s3::ServerSideEncryptionConfiguration::builder()
+            .set_rules(Some(rules))
+            .build()
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn from_sdk(config: &s3::ServerSideEncryptionConfiguration) -> Self {
+        Encryption {
+            rules: config
+                .rules()
+                .iter()
+                .map(|rule| EncryptionRule {
+                    apply_server_side_encryption_by_default: rule
+                        .apply_server_side_encryption_by_default()
+                        .map(|default| EncryptionDefault {
+                            sse_algorithm: default.sse_algorithm().as_str().to_string(),
+                            kms_master_key_id: default.kms_master_key_id().map(String::from),
+                        }),
+                    bucket_key_enabled: rule.bucket_key_enabled(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Bucket tags, as `{"TagSet": [{"Key": "team", "Value": "data"}]}`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+pub struct Tags {
+    pub tag_set: Vec<Tag>,
+}
+
+impl Tags {
+    pub fn to_sdk(&self) -> Result<s3::Tagging, String> {
+        let tags = self
+            .tag_set
+            .iter()
+            .map(Tag::to_sdk)
+            .collect::<Result<Vec<_>, _>>()?;
+        s3::Tagging::builder()
+            .set_tag_set(Some(tags))
+            .build()
+            .map_err(|e| e.to_string())
+    }
+
+    /// Tags sorted by key, as S3 does not keep their order
+    pub fn from_sdk(tags: &[s3::Tag]) -> Self {
+        let mut tag_set: Vec<Tag> = tags.iter().map(Tag::from_sdk).collect();
+        tag_set.sort_by(|a, b| a.key.cmp(&b.key));
+        Tags { tag_set }
+    }
+}
+
+/// Desired configuration of a set of buckets, as read by `bucket apply`
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+pub struct BucketsFile {
+    pub buckets: BTreeMap<String, BucketSpec>,
+}
+
+/// Desired settings of one bucket, each in the form `bucket get` prints it
+///
+/// Settings left out are not managed: they are neither compared nor changed.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+pub struct BucketSpec {
+    pub versioning: Option<Versioning>,
+    pub lifecycle: Option<Lifecycle>,
+    pub policy: Option<Value>,
+    pub cors: Option<Cors>,
+    pub public_access_block: Option<PublicAccessBlock>,
+    pub ownership_controls: Option<OwnershipControls>,
+    pub encryption: Option<Encryption>,
+    pub tags: Option<Tags>,
+}
+
+impl BucketSpec {
+    /// The managed settings in the order they are applied, validated and
+    /// normalized to the documents S3 would return for them: dates spelled
+    /// out in full and tags sorted
+    pub fn documents(&self) -> Result<Vec<(Setting, Value)>, String> {
+        let mut documents = Vec::new();
+        for setting in SETTINGS {
+            let document = match setting {
+                Setting::Versioning => self
+                    .versioning
+                    .as_ref()
+                    .map(|versioning| versioning.to_sdk().map(|_| to_value(versioning)))
+                    .transpose()?,
+                Setting::Lifecycle => self
+                    .lifecycle
+                    .as_ref()
+                    .map(|lifecycle| {
+                        lifecycle
+                            .to_sdk()
+                            .map(|sdk| to_value(&Lifecycle::from_sdk(sdk.rules())))
+                    })
+                    .transpose()?,
+                Setting::Policy => match &self.policy {
+                    Some(policy) if !policy.is_object() => {
+                        return Err("Policy must be a JSON object".to_string())
+                    }
+                    policy => policy.clone(),
+                },
+                Setting::Cors => self
+                    .cors
+                    .as_ref()
+                    .map(|cors| {
+                        cors.to_sdk()
+                            .map(|sdk| to_value(&Cors::from_sdk(sdk.cors_rules())))
+                    })
+                    .transpose()?,
+                Setting::PublicAccessBlock => self
+                    .public_access_block
+                    .as_ref()
+                    .map(|block| to_value(&PublicAccessBlock::from_sdk(&block.to_sdk()))),
+                Setting::OwnershipControls => self
+                    .ownership_controls
+                    .as_ref()
+                    .map(|controls| {
+                        controls
+                            .to_sdk()
+                            .map(|sdk| to_value(&OwnershipControls::from_sdk(&sdk)))
+                    })
+                    .transpose()?,
+                Setting::Encryption => self
+                    .encryption
+                    .as_ref()
+                    .map(|encryption| {
+                        encryption
+                            .to_sdk()
+                            .map(|sdk| to_value(&Encryption::from_sdk(&sdk)))
+                    })
+                    .transpose()?,
+                Setting::Tags => self
+                    .tags
+                    .as_ref()
+                    .map(|tags| {
+                        tags.to_sdk()
+                            .map(|sdk| to_value(&Tags::from_sdk(sdk.tag_set())))
+                    })
+                    .transpose()?,
+            };
+            if let Some(document) = document {
+                documents.push((setting, document));
+            }
+        }
+        Ok(documents)
+    }
+}
+
+/// Our documents always serialize: every map key is a string
+fn to_value<T: Serialize>(document: &T) -> Value {
+    serde_json::to_value(document).expect("configuration documents serialize")
+}
+
+/// Whether a bucket's live setting matches the desired document
+///
+/// Policies and tags must be identical, after bringing policies into the
+/// form S3 returns them in (see `normalize_policy`). Other settings match
+/// when every field the desired document sets has the same value, so values
+/// S3 fills in (generated rule IDs, `BucketKeyEnabled: false`) do not count
+/// as drift; lists must still match element by element.
+pub fn matches(setting: Setting, live: &Value, desired: &Value) -> bool {
+    match setting {
+        Setting::Policy => normalize_policy(live) == normalize_policy(desired),
+        Setting::Tags => live == desired,
+        _ => covers(live, desired),
+    }
+}
+
+/// A bucket policy in the form S3 stores it
+///
+/// S3 accepts several spellings of the same policy and returns one: a single
+/// statement becomes a list, one-element lists of actions, resources,
+/// principals and condition values become plain values, and a bare account
+/// ID principal becomes `arn:aws:iam::<id>:root`.
+fn normalize_policy(policy: &Value) -> Value {
+    let mut policy = policy.clone();
+    let Some(statements) = policy.get_mut("Statement") else {
+        return policy;
+    };
+    if statements.is_object() {
+        *statements = Value::Array(vec![statements.take()]);
+    }
+
+    for statement in statements.as_array_mut().into_iter().flatten() {
+        let Some(statement) = statement.as_object_mut() else {
+            continue;
+        };
+        for (field, value) in statement.iter_mut() {
+            match field.as_str() {
+                "Action" | "NotAction" | "Resource" | "NotResource" => unwrap_single(value),
+                "Principal" | "NotPrincipal" => {
+                    for (kind, principals) in value.as_object_mut().into_iter().flatten() {
+                        if kind == "AWS" {
+                            for_each_value(principals, |principal| {
+                                if let Some(id) = principal.as_str().filter(|id| is_account_id(id))
+                                {
+                                    *principal = Value::String(format!("arn:aws:iam::{}:root", id));
+                                }
+                            });
+                        }
+                        unwrap_single(principals);
+                    }
+                }
+                "Condition" => {
+                    for (_, keys) in value.as_object_mut().into_iter().flatten() {
+                        for (_, values) in keys.as_object_mut().into_iter().flatten() {
+                            unwrap_single(values);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    policy
+}
+
+/// Replace a one-element list by its element
+fn unwrap_single(value: &mut Value) {
+    if let Some([single]) = value.as_array_mut().map(Vec::as_mut_slice) {
+        *value = single.take();
+    }
+}
+
+/// Apply `f` to a value, or to each element of a list
+fn for_each_value(value: &mut Value, mut f: impl FnMut(&mut Value)) {
+    match value {
+        Value::Array(values) => values.iter_mut().for_each(f),
+        value => f(value),
+    }
+}
+
+fn is_account_id(value: &str) -> bool {
+    value.len() == 12 && value.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn covers(live: &Value, desired: &Value) -> bool {
+    match (live, desired) {
+        (Value::Object(live), Value::Object(desired)) => desired
+            .iter()
+            .all(|(key, value)| live.get(key).is_some_and(|live| covers(live, value))),
+        (Value::Array(live), Value::Array(desired)) => {
+            live.len() == desired.len() && live.iter().zip(desired).all(|(l, d)| covers(l, d))
+        }
+        _ => live == desired,
+    }
+}
+
+/// Line diff of two documents: unchanged lines are prefixed with two
+/// spaces, removed lines with "- " and added lines with "+ "
+pub fn diff_lines(old: &str, new: &str) -> Vec<String> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // common[i][j]: length of the longest common subsequence of old[i..] and new[j..]
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(format!("  {}", old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(format!("- {}", old[i]));
+            i += 1;
+        } else {
+            lines.push(format!("+ {}", new[j]));
+            j += 1;
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_names() {
+        assert_eq!(
+            parse_setting("public-access-block").unwrap(),
+            Setting::PublicAccessBlock
+        );
+        assert_eq!(parse_setting("CORS").unwrap(), Setting::Cors);
+        assert!(parse_setting("acl").is_err());
+        assert_eq!(parse_format("yml").unwrap(), Format::Yaml);
+        assert!(parse_format("xml").is_err());
+    }
+
+    #[test]
+    fn test_lifecycle_round_trip() {
+        let yaml = r#"
+Rules:
+  - ID: expire-logs
+    Status: Enabled
+    Filter:
+      And:
+        Prefix: logs/
+        Tags:
+          - Key: retention
+            Value: short
+    Expiration:
+      Days: 30
+    Transitions:
+      - Days: 7
+        StorageClass: STANDARD_IA
+  - ID: old-archive
+    Status: Disabled
+    Filter:
+      Prefix: archive/
+    Expiration:
+      Date: "2030-01-01"
+    AbortIncompleteMultipartUpload:
+      DaysAfterInitiation: 3
+"#;
+        let lifecycle: Lifecycle = serde_norway::from_str(yaml).unwrap();
+        let sdk = lifecycle.to_sdk().unwrap();
+        let back = Lifecycle::from_sdk(sdk.rules());
+
+        assert_eq!(back.rules[0], lifecycle.rules[0]);
+        assert_eq!(
+            back.rules[1].expiration.as_ref().unwrap().date.as_deref(),
+            Some("2030-01-01T00:00:00Z")
+        );
+
+        let json = write_document(&back, Format::Json).unwrap();
+        let again: Lifecycle = serde_json::from_str(&json).unwrap();
+        assert_eq!(again, back);
+    }
+
+    #[test]
+    fn test_legacy_prefix_round_trip() {
+        #[allow(deprecated)]
+        let legacy = s3::LifecycleRule::builder()
+            .id("expire-logs")
+            .status(s3::ExpirationStatus::Enabled)
+            .prefix("logs/")
+            .expiration(s3::LifecycleExpiration::builder().days(30).build())
+            .build()
+            .unwrap();
+
+        let lifecycle = Lifecycle::from_sdk(&[legacy]);
+        let filter = lifecycle.rules[0].filter.as_ref().unwrap();
+        assert_eq!(filter.prefix.as_deref(), Some("logs/"));
+
+        // Put back, the rule stays scoped to the prefix
+        let sdk = lifecycle.to_sdk().unwrap();
+        assert_eq!(sdk.rules()[0].filter().unwrap().prefix(), Some("logs/"));
+        assert_eq!(Lifecycle::from_sdk(sdk.rules()), lifecycle);
+    }
+
+    #[test]
+    fn test_invalid_documents() {
+        // Typos in field names are reported rather than ignored
+        assert!(serde_norway::from_str::<Versioning>("Stauts: Enabled").is_err());
+
+        let versioning = Versioning {
+            status: Some("On".to_string()),
+            mfa_delete: None,
+        };
+        assert!(versioning.to_sdk().is_err());
+
+        let encryption: Encryption = serde_json::from_str(
+            r#"{"Rules": [{"ApplyServerSideEncryptionByDefault": {"SSEAlgorithm": "aws:kms", "KMSMasterKeyID": "alias/data"}, "BucketKeyEnabled": true}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            Encryption::from_sdk(&encryption.to_sdk().unwrap()),
+            encryption
+        );
+
+        let ownership = OwnershipControls {
+            rules: vec![OwnershipRule {
+                object_ownership: "Nobody".to_string(),
+            }],
+        };
+        assert!(ownership.to_sdk().is_err());
+    }
+
+    #[test]
+    fn test_buckets_file() {
+        let yaml = r#"
+Buckets:
+  app-logs:
+    Versioning:
+      Status: Enabled
+    Lifecycle:
+      Rules:
+        - Status: Enabled
+          Expiration:
+            Date: "2030-01-01"
+    Tags:
+      TagSet:
+        - Key: team
+          Value: ops
+        - Key: env
+          Value: prod
+"#;
+        let file: BucketsFile = serde_norway::from_str(yaml).unwrap();
+        let documents = file.buckets["app-logs"].documents().unwrap();
+        let settings: Vec<Setting> = documents.iter().map(|(setting, _)| *setting).collect();
+        assert_eq!(
+            settings,
+            [Setting::Versioning, Setting::Lifecycle, Setting::Tags]
+        );
+        assert_eq!(
+            documents[1].1["Rules"][0]["Expiration"]["Date"],
+            "2030-01-01T00:00:00Z"
+        );
+        assert_eq!(documents[2].1["TagSet"][0]["Key"], "env");
+
+        assert!(serde_norway::from_str::<BucketsFile>("Buckets: {b: {Lifecyle: {}}}").is_err());
+        let spec: BucketSpec = serde_norway::from_str("Policy: allow-all").unwrap();
+        assert!(spec.documents().is_err());
+    }
+
+    #[test]
+    fn test_matches() {
+        let desired =
+            serde_json::json!({"Rules": [{"Status": "Enabled", "Expiration": {"Days": 7}}]});
+        let live = serde_json::json!({"Rules": [{"ID": "generated", "Status": "Enabled", "Expiration": {"Days": 7}}]});
+        assert!(matches(Setting::Lifecycle, &live, &desired));
+        assert!(!matches(Setting::Lifecycle, &desired, &live));
+
+        let two_rules = serde_json::json!({"Rules": [live["Rules"][0], live["Rules"][0]]});
+        assert!(!matches(Setting::Lifecycle, &two_rules, &desired));
+
+        // Anything extra in a policy is drift
+        let policy = serde_json::json!({"Statement": [{"Effect": "Allow"}]});
+        let wider = serde_json::json!({"Statement": [{"Effect": "Allow", "Principal": "*"}]});
+        assert!(!matches(Setting::Policy, &wider, &policy));
+        assert!(matches(Setting::Policy, &policy, &policy));
+
+        // Spellings S3 normalizes are not drift
+        let desired = serde_json::json!({"Statement": {
+            "Effect": "Allow",
+            "Principal": {"AWS": ["123456789012"]},
+            "Action": ["s3:GetObject"],
+            "Resource": "arn:aws:s3:::b/*",
+            "Condition": {"StringEquals": {"aws:PrincipalTag/team": ["data"]}}
+        }});
+        let live = serde_json::json!({"Statement": [{
+            "Effect": "Allow",
+            "Principal": {"AWS": "arn:aws:iam::123456789012:root"},
+            "Action": "s3:GetObject",
+            "Resource": "arn:aws:s3:::b/*",
+            "Condition": {"StringEquals": {"aws:PrincipalTag/team": "data"}}
+        }]});
+        assert!(matches(Setting::Policy, &live, &desired));
+        let other = serde_json::json!({"Statement": {
+            "Effect": "Allow",
+            "Principal": {"AWS": "210987654321"},
+            "Action": "s3:GetObject",
+            "Resource": "arn:aws:s3:::b/*",
+            "Condition": {"StringEquals": {"aws:PrincipalTag/team": "data"}}
+        }});
+        assert!(!matches(Setting::Policy, &live, &other));
+
+        assert_eq!(
+            diff_lines("a\nb\nc\n", "a\nc\nd\n"),
+            ["  a", "- b", "  c", "+ d"]
+        );
+    }
+}
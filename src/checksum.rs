@@ -0,0 +1,221 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use crc_fast::{CrcAlgorithm, Digest as CrcDigest};
+use md5::{Digest, Md5};
+use sha1::Sha1;
+use sha2::Sha256;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Checksum algorithms that can be compared against values stored by S3
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// MD5 in ETag form (hex encoded)
+    Md5,
+    Crc32,
+    Crc32C,
+    Crc64Nvme,
+    Sha1,
+    Sha256,
+}
+
+impl Algorithm {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Algorithm::Md5 => "ETag",
+            Algorithm::Crc32 => "CRC32",
+            Algorithm::Crc32C => "CRC32C",
+            Algorithm::Crc64Nvme => "CRC64NVME",
+            Algorithm::Sha1 => "SHA1",
+            Algorithm::Sha256 => "SHA256",
+        }
+    }
+}
+
+/// Incremental hasher for any supported algorithm
+pub enum Hasher {
+    Md5(Md5),
+    Crc32(crc32fast::Hasher),
+    Crc(CrcDigest, usize),
+    Sha1(Sha1),
+    Sha256(Sha256),
+}
+
+impl Hasher {
+    pub fn new(algorithm: Algorithm) -> Self {
+        match algorithm {
+            Algorithm::Md5 => Hasher::Md5(Md5::new()),
+            Algorithm::Crc32 => Hasher::Crc32(crc32fast::Hasher::new()),
+            Algorithm::Crc32C => Hasher::Crc(CrcDigest::new(CrcAlgorithm::Crc32Iscsi), 4),
+            Algorithm::Crc64Nvme => Hasher::Crc(CrcDigest::new(CrcAlgorithm::Crc64Nvme), 8),
+            Algorithm::Sha1 => Hasher::Sha1(Sha1::new()),
+            Algorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Md5(h) => h.update(data),
+            Hasher::Crc32(h) => h.update(data),
+            Hasher::Crc(h, _) => h.update(data),
+            Hasher::Sha1(h) => h.update(data),
+            Hasher::Sha256(h) => h.update(data),
+        }
+    }
+
+    /// Finish hashing and return the raw (big-endian for CRCs) digest bytes
+    pub fn finalize(self) -> Vec<u8> {
+        match self {
+            Hasher::Md5(h) => h.finalize().to_vec(),
+            Hasher::Crc32(h) => h.finalize().to_be_bytes().to_vec(),
+            Hasher::Crc(h, width) => {
+                let value = h.finalize().to_be_bytes();
+                value[8 - width..].to_vec()
+            }
+            Hasher::Sha1(h) => h.finalize().to_vec(),
+            Hasher::Sha256(h) => h.finalize().to_vec(),
+        }
+    }
+}
+
+/// Encode a digest the way S3 reports it (hex for ETags, base64 otherwise)
+pub fn encode(algorithm: Algorithm, digest: &[u8]) -> String {
+    match algorithm {
+        Algorithm::Md5 => digest.iter().map(|b| format!("{:02x}", b)).collect(),
+        _ => BASE64.encode(digest),
+    }
+}
+
+/// Calculate a file checksum in S3 format
+///
+/// With `part_size` set, the result is the multipart form: the checksum of
+/// the concatenated per-part digests followed by `-<part count>`. Without it,
+/// the checksum covers the whole file. This is blocking I/O; call it from
+/// `spawn_blocking` in async code.
+pub fn file_checksum(
+    path: &Path,
+    algorithm: Algorithm,
+    part_size: Option<u64>,
+) -> std::io::Result<String> {
+    let mut file = File::open(path)?;
+    checksum_reader(&mut file, algorithm, part_size)
+}
+
+/// Calculate a checksum in S3 format over everything readable from `reader`
+pub fn checksum_reader<R: Read>(
+    reader: &mut R,
+    algorithm: Algorithm,
+    part_size: Option<u64>,
+) -> std::io::Result<String> {
+    let mut buffer = vec![0u8; 65536];
+
+    let part_size = match part_size {
+        Some(size) if size > 0 => size,
+        _ => {
+            let mut hasher = Hasher::new(algorithm);
+            loop {
+                let n = reader.read(&mut buffer)?;
+                if n == 0 {
+                    break;
+                }
+                hasher.update(&buffer[..n]);
+            }
+            return Ok(encode(algorithm, &hasher.finalize()));
+        }
+    };
+
+    let mut combined = Hasher::new(algorithm);
+    let mut part_hasher = Hasher::new(algorithm);
+    let mut part_bytes = 0u64;
+    let mut part_count = 0u64;
+
+    loop {
+        let to_read = (part_size - part_bytes).min(buffer.len() as u64) as usize;
+        let n = reader.read(&mut buffer[..to_read])?;
+        if n == 0 {
+            break;
+        }
+        part_hasher.update(&buffer[..n]);
+        part_bytes += n as u64;
+
+        if part_bytes == part_size {
+            let done = std::mem::replace(&mut part_hasher, Hasher::new(algorithm));
+            combined.update(&done.finalize());
+            part_bytes = 0;
+            part_count += 1;
+        }
+    }
+
+    if part_bytes > 0 || part_count == 0 {
+        combined.update(&part_hasher.finalize());
+        part_count += 1;
+    }
+
+    Ok(format!(
+        "{}-{}",
+        encode(algorithm, &combined.finalize()),
+        part_count
+    ))
+}
+
+/// Split an S3 checksum or ETag into its value and multipart part count
+pub fn split_part_count(value: &str) -> (&str, Option<u64>) {
+    let value = value.trim_matches('"');
+    match value.rsplit_once('-') {
+        Some((checksum, count)) => match count.parse::<u64>() {
+            Ok(count) => (checksum, Some(count)),
+            Err(_) => (value, None),
+        },
+        None => (value, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_full_object_checksums() {
+        let data = b"hello world";
+        let sum = |algo| checksum_reader(&mut Cursor::new(data), algo, None).unwrap();
+
+        assert_eq!(sum(Algorithm::Md5), "5eb63bbbe01eeed093cb22bb8f5acdc3");
+        assert_eq!(sum(Algorithm::Crc32), "DUoRhQ==");
+        assert_eq!(sum(Algorithm::Crc32C), "yZRlqg==");
+        assert_eq!(sum(Algorithm::Sha1), "Kq5sNclPz7QV2+lfQIuc6R7oRu0=");
+        assert_eq!(
+            sum(Algorithm::Sha256),
+            "uU0nuZNNPgilLlLX2n2r+sSE7+N6U4DukIj3rOLvzek="
+        );
+    }
+
+    #[test]
+    fn test_multipart_etag() {
+        let data = b"aaaabbbbcc";
+        let etag = checksum_reader(&mut Cursor::new(data), Algorithm::Md5, Some(4)).unwrap();
+
+        let mut expected = Md5::new();
+        expected.update(Md5::digest(b"aaaa"));
+        expected.update(Md5::digest(b"bbbb"));
+        expected.update(Md5::digest(b"cc"));
+        let expected = format!("{:x}-3", expected.finalize());
+
+        assert_eq!(etag, expected);
+    }
+
+    #[test]
+    fn test_multipart_exact_boundary() {
+        let data = b"aaaabbbb";
+        let etag = checksum_reader(&mut Cursor::new(data), Algorithm::Md5, Some(4)).unwrap();
+        assert!(etag.ends_with("-2"));
+    }
+
+    #[test]
+    fn test_split_part_count() {
+        assert_eq!(split_part_count("\"abc-3\""), ("abc", Some(3)));
+        assert_eq!(split_part_count("abc"), ("abc", None));
+        assert_eq!(split_part_count("a-b"), ("a-b", None));
+    }
+}
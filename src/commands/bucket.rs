@@ -0,0 +1,582 @@
+use crate::bucket_config::{
+    self, BucketsFile, Cors, Encryption, Format, Lifecycle, OwnershipControls, PublicAccessBlock,
+    Setting, Tags, Versioning,
+};
+use crate::commands::mb::{self, BucketOptions};
+use crate::path_utils::{parse_s3_uri, validate_bucket_name, PathType};
+use aws_sdk_s3::error::{ProvideErrorMetadata, SdkError};
+use aws_sdk_s3::Client;
+use serde_json::Value;
+
+/// Error codes the bucket configuration APIs return for unset settings
+const NOT_CONFIGURED: &[&str] = &[
+    "NoSuchLifecycleConfiguration",
+    "NoSuchCORSConfiguration",
+    "NoSuchBucketPolicy",
+    "NoSuchPublicAccessBlockConfiguration",
+    "OwnershipControlsNotFoundError",
+    "ServerSideEncryptionConfigurationNotFoundError",
+];
+
+/// `None` for the error a bucket configuration API returns when the
+/// setting is not configured, the error itself otherwise
+fn unless_not_configured<T, E, R>(
+    result: Result<T, SdkError<E, R>>,
+) -> Result<Option<T>, Box<dyn std::error::Error>>
+where
+    E: ProvideErrorMetadata + std::error::Error + Send + Sync + 'static,
+    R: std::fmt::Debug + Send + Sync + 'static,
+{
+    match result {
+        Ok(output) => Ok(Some(output)),
+        Err(e) if e.code().is_some_and(|code| NOT_CONFIGURED.contains(&code)) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Bucket name of an `s3://bucket` URI
+pub fn bucket_name(bucket_uri: &str) -> Result<String, Box<dyn std::error::Error>> {
+    match parse_s3_uri(bucket_uri)? {
+        PathType::S3 { bucket, key } if key.is_empty() => Ok(bucket),
+        PathType::S3 { key, .. } => Err(format!(
+            "bucket command expects bucket URI only (s3://bucket-name), got key: {}",
+            key
+        )
+        .into()),
+        PathType::Local(_) => Err("bucket command requires S3 URI (s3://bucket-name)".into()),
+    }
+}
+
+/// Print a bucket setting as JSON or YAML
+///
+/// Settings that are not configured print nothing on stdout, so the output
+/// can be saved and put back as is.
+pub async fn get(
+    client: &Client,
+    bucket_uri: &str,
+    setting: Setting,
+    format: Format,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let bucket = bucket_name(bucket_uri)?;
+
+    let document = get_document(client, &bucket, setting).await?;
+    match document {
+        Some(document) => {
+            let text = bucket_config::write_document(&document, format)?;
+            println!("{}", text.trim_end());
+        }
+        None => eprintln!("No {} configuration: s3://{}", setting.name(), bucket),
+    }
+    Ok(())
+}
+
+/// Set a bucket setting from a JSON or YAML file ("-" for stdin)
+pub async fn put(
+    client: &Client,
+    bucket_uri: &str,
+    setting: Setting,
+    file: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let bucket = bucket_name(bucket_uri)?;
+
+    match setting {
+        Setting::Versioning => {
+            put_versioning(client, &bucket, &bucket_config::read_document(file)?).await?
+        }
+        Setting::Lifecycle => {
+            put_lifecycle(client, &bucket, &bucket_config::read_document(file)?).await?
+        }
+        Setting::Cors => put_cors(client, &bucket, &bucket_config::read_document(file)?).await?,
+        Setting::Policy => {
+            put_policy(client, &bucket, &bucket_config::read_document(file)?).await?
+        }
+        Setting::PublicAccessBlock => {
+            put_public_access_block(client, &bucket, &bucket_config::read_document(file)?).await?
+        }
+        Setting::OwnershipControls => {
+            put_ownership_controls(client, &bucket, &bucket_config::read_document(file)?).await?
+        }
+        Setting::Encryption => {
+            put_encryption(client, &bucket, &bucket_config::read_document(file)?).await?
+        }
+        Setting::Tags => put_tags(client, &bucket, &bucket_config::read_document(file)?).await?,
+    }
+
+    println!("Updated {}: s3://{}", setting.name(), bucket);
+    Ok(())
+}
+
+/// Remove a bucket setting
+///
+/// Versioning cannot be removed once enabled, only suspended.
+pub async fn delete(
+    client: &Client,
+    bucket_uri: &str,
+    setting: Setting,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let bucket = bucket_name(bucket_uri)?;
+
+    match setting {
+        Setting::Versioning => {
+            return Err(
+                "Versioning cannot be removed; put {\"Status\": \"Suspended\"} to suspend it"
+                    .into(),
+            )
+        }
+        Setting::Lifecycle => {
+            client
+                .delete_bucket_lifecycle()
+                .bucket(&bucket)
+                .send()
+                .await?;
+        }
+        Setting::Cors => {
+            client.delete_bucket_cors().bucket(&bucket).send().await?;
+        }
+        Setting::Policy => {
+            client.delete_bucket_policy().bucket(&bucket).send().await?;
+        }
+        Setting::PublicAccessBlock => {
+            client
+                .delete_public_access_block()
+                .bucket(&bucket)
+                .send()
+                .await?;
+        }
+        Setting::OwnershipControls => {
+            client
+                .delete_bucket_ownership_controls()
+                .bucket(&bucket)
+                .send()
+                .await?;
+        }
+        Setting::Encryption => {
+            client
+                .delete_bucket_encryption()
+                .bucket(&bucket)
+                .send()
+                .await?;
+        }
+        Setting::Tags => {
+            client
+                .delete_bucket_tagging()
+                .bucket(&bucket)
+                .send()
+                .await?;
+        }
+    }
+
+    println!("Deleted {}: s3://{}", setting.name(), bucket);
+    Ok(())
+}
+
+/// A setting whose live state differs from the desired document
+struct Change {
+    setting: Setting,
+    /// Live document, `None` if the setting is not configured
+    live: Option<Value>,
+    desired: Value,
+}
+
+/// Changes that bring one bucket to its desired configuration
+struct BucketPlan {
+    bucket: String,
+    exists: bool,
+    changes: Vec<Change>,
+}
+
+/// Compare buckets against a desired configuration file and print the
+/// differences
+///
+/// With `yes` the buckets are created and changed to match; with `check`
+/// the process exits with status 1 if anything differs.
+pub async fn apply(
+    client: &Client,
+    file: &str,
+    yes: bool,
+    check: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let spec: BucketsFile = bucket_config::read_document(file)?;
+    if spec.buckets.is_empty() {
+        return Err(format!("No buckets in {}", file).into());
+    }
+
+    let mut plans = Vec::new();
+    for (bucket, bucket_spec) in &spec.buckets {
+        validate_bucket_name(bucket)?;
+        let documents = bucket_spec
+            .documents()
+            .map_err(|e| format!("{}: {}", bucket, e))?;
+
+        let exists = match client.head_bucket().bucket(bucket).send().await {
+            Ok(_) => true,
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_not_found()) => false,
+            Err(e) => return Err(format!("Bucket '{}' is not accessible: {}", bucket, e).into()),
+        };
+
+        let mut changes = Vec::new();
+        for (setting, desired) in documents {
+            let live = match exists {
+                true => get_document(client, bucket, setting).await?,
+                false => None,
+            };
+            let unchanged = live
+                .as_ref()
+                .is_some_and(|live| bucket_config::matches(setting, live, &desired));
+            if !unchanged {
+                changes.push(Change {
+                    setting,
+                    live,
+                    desired,
+                });
+            }
+        }
+
+        if !exists || !changes.is_empty() {
+            plans.push(BucketPlan {
+                bucket: bucket.clone(),
+                exists,
+                changes,
+            });
+        }
+    }
+
+    if plans.is_empty() {
+        println!("No changes: {} buckets match {}", spec.buckets.len(), file);
+        return Ok(());
+    }
+
+    for plan in &plans {
+        print_plan(plan)?;
+    }
+    let changes: usize = plans
+        .iter()
+        .map(|plan| plan.changes.len() + usize::from(!plan.exists))
+        .sum();
+    println!(
+        "Plan: {} changes to {} of {} buckets",
+        changes,
+        plans.len(),
+        spec.buckets.len()
+    );
+
+    if check {
+        eprintln!("Configuration drift detected");
+        std::process::exit(1);
+    }
+    if !yes {
+        println!("Run with --yes to apply these changes");
+        return Ok(());
+    }
+
+    println!();
+    for plan in &plans {
+        if !plan.exists {
+            let uri = format!("s3://{}", plan.bucket);
+            mb::make_bucket(client, &uri, &BucketOptions::default()).await?;
+        }
+        for change in &plan.changes {
+            put_document(client, &plan.bucket, change.setting, &change.desired).await?;
+            println!("Updated {}: s3://{}", change.setting.name(), plan.bucket);
+        }
+    }
+    Ok(())
+}
+
+/// Print the changes of one bucket, with documents as YAML:
+/// `+` for settings that are added and `~` with a line diff for changes
+fn print_plan(plan: &BucketPlan) -> Result<(), Box<dyn std::error::Error>> {
+    match plan.exists {
+        true => println!("s3://{}", plan.bucket),
+        false => println!("s3://{} (new bucket)", plan.bucket),
+    }
+
+    for change in &plan.changes {
+        let desired = bucket_config::write_document(&change.desired, Format::Yaml)?;
+        match &change.live {
+            None => {
+                println!("  + {}", change.setting.name());
+                for line in desired.lines() {
+                    println!("      {}", line);
+                }
+            }
+            Some(live) => {
+                let live = bucket_config::write_document(live, Format::Yaml)?;
+                println!("  ~ {}", change.setting.name());
+                for line in bucket_config::diff_lines(&live, &desired) {
+                    println!("    {}", line);
+                }
+            }
+        }
+    }
+    println!();
+    Ok(())
+}
+
+/// A bucket setting as a JSON document, `None` if it is not configured
+async fn get_document(
+    client: &Client,
+    bucket: &str,
+    setting: Setting,
+) -> Result<Option<Value>, Box<dyn std::error::Error>> {
+    let document = match setting {
+        Setting::Versioning => Some(serde_json::to_value(get_versioning(client, bucket).await?)?),
+        Setting::Lifecycle => get_lifecycle(client, bucket)
+            .await?
+            .map(serde_json::to_value)
+            .transpose()?,
+        Setting::Cors => get_cors(client, bucket)
+            .await?
+            .map(serde_json::to_value)
+            .transpose()?,
+        Setting::Policy => get_policy(client, bucket).await?,
+        Setting::PublicAccessBlock => get_public_access_block(client, bucket)
+            .await?
+            .map(serde_json::to_value)
+            .transpose()?,
+        Setting::OwnershipControls => get_ownership_controls(client, bucket)
+            .await?
+            .map(serde_json::to_value)
+            .transpose()?,
+        Setting::Encryption => get_encryption(client, bucket)
+            .await?
+            .map(serde_json::to_value)
+            .transpose()?,
+        Setting::Tags => Some(serde_json::to_value(get_tags(client, bucket).await?)?),
+    };
+    Ok(document)
+}
+
+/// Set a bucket setting from a document checked by `BucketSpec::documents`
+async fn put_document(
+    client: &Client,
+    bucket: &str,
+    setting: Setting,
+    document: &Value,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let document = document.clone();
+    match setting {
+        Setting::Versioning => {
+            put_versioning(client, bucket, &serde_json::from_value(document)?).await
+        }
+        Setting::Lifecycle => {
+            put_lifecycle(client, bucket, &serde_json::from_value(document)?).await
+        }
+        Setting::Cors => put_cors(client, bucket, &serde_json::from_value(document)?).await,
+        Setting::Policy => put_policy(client, bucket, &document).await,
+        Setting::PublicAccessBlock => {
+            put_public_access_block(client, bucket, &serde_json::from_value(document)?).await
+        }
+        Setting::OwnershipControls => {
+            put_ownership_controls(client, bucket, &serde_json::from_value(document)?).await
+        }
+        Setting::Encryption => {
+            put_encryption(client, bucket, &serde_json::from_value(document)?).await
+        }
+        Setting::Tags => put_tags(client, bucket, &serde_json::from_value(document)?).await,
+    }
+}
+
+pub async fn get_versioning(
+    client: &Client,
+    bucket: &str,
+) -> Result<Versioning, Box<dyn std::error::Error>> {
+    let response = client.get_bucket_versioning().bucket(bucket).send().await?;
+    Ok(Versioning::from_sdk(
+        response.status(),
+        response.mfa_delete(),
+    ))
+}
+
+pub async fn put_versioning(
+    client: &Client,
+    bucket: &str,
+    versioning: &Versioning,
+) -> Result<(), Box<dyn std::error::Error>> {
+    client
+        .put_bucket_versioning()
+        .bucket(bucket)
+        .versioning_configuration(versioning.to_sdk()?)
+        .send()
+        .await?;
+    Ok(())
+}
+
+pub async fn get_lifecycle(
+    client: &Client,
+    bucket: &str,
+) -> Result<Option<Lifecycle>, Box<dyn std::error::Error>> {
+    let response = client
+        .get_bucket_lifecycle_configuration()
+        .bucket(bucket)
+        .send()
+        .await;
+    Ok(unless_not_configured(response)?.map(|r| Lifecycle::from_sdk(r.rules())))
+}
+
+pub async fn put_lifecycle(
+    client: &Client,
+    bucket: &str,
+    lifecycle: &Lifecycle,
+) -> Result<(), Box<dyn std::error::Error>> {
+    client
+        .put_bucket_lifecycle_configuration()
+        .bucket(bucket)
+        .lifecycle_configuration(lifecycle.to_sdk()?)
+        .send()
+        .await?;
+    Ok(())
+}
+
+pub async fn get_cors(
+    client: &Client,
+    bucket: &str,
+) -> Result<Option<Cors>, Box<dyn std::error::Error>> {
+    let response = client.get_bucket_cors().bucket(bucket).send().await;
+    Ok(unless_not_configured(response)?.map(|r| Cors::from_sdk(r.cors_rules())))
+}
+
+pub async fn put_cors(
+    client: &Client,
+    bucket: &str,
+    cors: &Cors,
+) -> Result<(), Box<dyn std::error::Error>> {
+    client
+        .put_bucket_cors()
+        .bucket(bucket)
+        .cors_configuration(cors.to_sdk()?)
+        .send()
+        .await?;
+    Ok(())
+}
+
+/// Bucket policy as a JSON document
+pub async fn get_policy(
+    client: &Client,
+    bucket: &str,
+) -> Result<Option<serde_json::Value>, Box<dyn std::error::Error>> {
+    let response = client.get_bucket_policy().bucket(bucket).send().await;
+    match unless_not_configured(response)?.and_then(|r| r.policy) {
+        Some(policy) => Ok(Some(serde_json::from_str(&policy)?)),
+        None => Ok(None),
+    }
+}
+
+pub async fn put_policy(
+    client: &Client,
+    bucket: &str,
+    policy: &serde_json::Value,
+) -> Result<(), Box<dyn std::error::Error>> {
+    client
+        .put_bucket_policy()
+        .bucket(bucket)
+        .policy(policy.to_string())
+        .send()
+        .await?;
+    Ok(())
+}
+
+pub async fn get_public_access_block(
+    client: &Client,
+    bucket: &str,
+) -> Result<Option<PublicAccessBlock>, Box<dyn std::error::Error>> {
+    let response = client.get_public_access_block().bucket(bucket).send().await;
+    Ok(unless_not_configured(response)?
+        .and_then(|r| r.public_access_block_configuration)
+        .map(|config| PublicAccessBlock::from_sdk(&config)))
+}
+
+pub async fn put_public_access_block(
+    client: &Client,
+    bucket: &str,
+    block: &PublicAccessBlock,
+) -> Result<(), Box<dyn std::error::Error>> {
+    client
+        .put_public_access_block()
+        .bucket(bucket)
+        .public_access_block_configuration(block.to_sdk())
+        .send()
+        .await?;
+    Ok(())
+}
+
+pub async fn get_ownership_controls(
+    client: &Client,
+    bucket: &str,
+) -> Result<Option<OwnershipControls>, Box<dyn std::error::Error>> {
+    let response = client
+        .get_bucket_ownership_controls()
+        .bucket(bucket)
+        .send()
+        .await;
+    Ok(unless_not_configured(response)?
+        .and_then(|r| r.ownership_controls)
+        .map(|controls| OwnershipControls::from_sdk(&controls)))
+}
+
+pub async fn put_ownership_controls(
+    client: &Client,
+    bucket: &str,
+    controls: &OwnershipControls,
+) -> Result<(), Box<dyn std::error::Error>> {
+    client
+        .put_bucket_ownership_controls()
+        .bucket(bucket)
+        .ownership_controls(controls.to_sdk()?)
+        .send()
+        .await?;
+    Ok(())
+}
+
+pub async fn get_encryption(
+    client: &Client,
+    bucket: &str,
+) -> Result<Option<Encryption>, Box<dyn std::error::Error>> {
+    let response = client.get_bucket_encryption().bucket(bucket).send().await;
+    Ok(unless_not_configured(response)?
+        .and_then(|r| r.server_side_encryption_configuration)
+        .map(|config| Encryption::from_sdk(&config)))
+}
+
+pub async fn put_encryption(
+    client: &Client,
+    bucket: &str,
+    encryption: &Encryption,
+) -> Result<(), Box<dyn std::error::Error>> {
+    client
+        .put_bucket_encryption()
+        .bucket(bucket)
+        .server_side_encryption_configuration(encryption.to_sdk()?)
+        .send()
+        .await?;
+    Ok(())
+}
+
+/// Bucket tags; a bucket without tags has an empty tag set
+pub async fn get_tags(client: &Client, bucket: &str) -> Result<Tags, Box<dyn std::error::Error>> {
+    let response = client.get_bucket_tagging().bucket(bucket).send().await;
+    match response {
+        Ok(response) => Ok(Tags::from_sdk(response.tag_set())),
+        Err(e) if e.code() == Some("NoSuchTagSet") => Ok(Tags::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Replace the bucket tags; an empty tag set removes them
+pub async fn put_tags(
+    client: &Client,
+    bucket: &str,
+    tags: &Tags,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if tags.tag_set.is_empty() {
+        client.delete_bucket_tagging().bucket(bucket).send().await?;
+    } else {
+        client
+            .put_bucket_tagging()
+            .bucket(bucket)
+            .tagging(tags.to_sdk()?)
+            .send()
+            .await?;
+    }
+    Ok(())
+}
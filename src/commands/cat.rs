@@ -1,16 +1,31 @@
+use crate::commands::cp;
+use crate::compression::{Codec, Decompressor};
+use crate::encryption::{Decryptor, MasterKey};
+use crate::manifest::ManifestEntry;
 use crate::path_utils::{parse_path, PathType};
+use crate::sse::{CustomerKey, SseCustomerHeaders};
+use crate::storage_class;
+use crate::wildcard::{self, WildcardPattern};
 use aws_sdk_s3::Client;
 use std::path::Path;
 use tokio::fs::File;
 use tokio::io::{self, AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 
 /// Concatenate and print file or object content to STDOUT
+///
+/// Objects with a gzip, zstd or bzip2 Content-Encoding are decompressed; with
+/// `decompress`, so are objects and files named `.gz`, `.zst` or `.bz2`.
+#[allow(clippy::too_many_arguments)]
 pub async fn cat(
     client: &Client,
     path: &str,
     range: Option<String>,
     offset: Option<u64>,
     size: Option<u64>,
+    customer_key: Option<&CustomerKey>,
+    master_key: Option<&MasterKey>,
+    decompress: bool,
+    literal_keys: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     // Validate options
     if range.is_some() && (offset.is_some() || size.is_some()) {
@@ -24,22 +39,130 @@ pub async fn cat(
             if key.is_empty() {
                 return Err("Cannot cat an S3 bucket, please specify an object key".into());
             }
-            cat_s3_object(client, &bucket, &key, range, offset, size).await
+
+            if !wildcard::has_wildcard(&key, literal_keys) {
+                return cat_s3_object(
+                    client,
+                    &bucket,
+                    &key,
+                    None,
+                    range,
+                    offset,
+                    size,
+                    customer_key,
+                    master_key,
+                    decompress,
+                )
+                .await;
+            }
+
+            // Concatenate every matching object in key order
+            let pattern = WildcardPattern::new(&key)?;
+            let objects = wildcard::list_matching(client, &bucket, &pattern).await?;
+            if objects.is_empty() {
+                return Err(format!("No objects match s3://{}/{}", bucket, key).into());
+            }
+            for obj in &objects {
+                if let Some(key) = obj.key() {
+                    cat_s3_object(
+                        client,
+                        &bucket,
+                        key,
+                        None,
+                        range.clone(),
+                        offset,
+                        size,
+                        customer_key,
+                        master_key,
+                        decompress,
+                    )
+                    .await?;
+                }
+            }
+            Ok(())
+        }
+        PathType::Local(local_path) => {
+            cat_local_file(&local_path, range, offset, size, decompress).await
+        }
+    }
+}
+
+/// Concatenate exactly the entries of a `--files-from` manifest, in order
+///
+/// Entries are resolved against `path` (a local directory or S3 prefix).
+/// Output stops at the first entry that cannot be read.
+#[allow(clippy::too_many_arguments)]
+pub async fn cat_manifest(
+    client: &Client,
+    path: &str,
+    entries: &[ManifestEntry],
+    range: Option<String>,
+    offset: Option<u64>,
+    size: Option<u64>,
+    customer_key: Option<&CustomerKey>,
+    master_key: Option<&MasterKey>,
+    decompress: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if range.is_some() && (offset.is_some() || size.is_some()) {
+        return Err("Cannot specify both --range and --offset/--size options".into());
+    }
+
+    let base = parse_path(path)?;
+
+    for entry in entries {
+        match entry.resolve(&base).0 {
+            PathType::S3 { bucket, key } => {
+                let version_id = entry.version_id.as_deref();
+                cat_s3_object(
+                    client,
+                    &bucket,
+                    &key,
+                    version_id,
+                    range.clone(),
+                    offset,
+                    size,
+                    customer_key,
+                    master_key,
+                    decompress,
+                )
+                .await?
+            }
+            PathType::Local(local_path) => {
+                cat_local_file(&local_path, range.clone(), offset, size, decompress).await?
+            }
         }
-        PathType::Local(local_path) => cat_local_file(&local_path, range, offset, size).await,
     }
+
+    Ok(())
 }
 
 /// Read and output S3 object content
+///
+/// Client-side encrypted and compressed objects are decoded; ranges then
+/// apply to the decoded content, so the whole object is read and the range
+/// cut from it.
+#[allow(clippy::too_many_arguments)]
 async fn cat_s3_object(
     client: &Client,
     bucket: &str,
     key: &str,
+    version_id: Option<&str>,
     range: Option<String>,
     offset: Option<u64>,
     size: Option<u64>,
+    customer_key: Option<&CustomerKey>,
+    master_key: Option<&MasterKey>,
+    decompress: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let mut request = client.get_object().bucket(bucket).key(key);
+    let whole = client
+        .get_object()
+        .bucket(bucket)
+        .key(key)
+        .set_version_id(version_id.map(String::from))
+        .sse_customer(customer_key);
+    let mut request = whole.clone();
+    let ranged = range.is_some() || offset.is_some();
+    let decoded_range = (range.clone(), offset, size);
 
     // Handle range options
     if let Some(range_str) = range {
@@ -60,27 +183,120 @@ async fn cat_s3_object(
         request = request.range(range_str);
     }
 
-    let response = request.send().await?;
+    let response = request
+        .send()
+        .await
+        .map_err(|e| storage_class::explain_archived(bucket, key, e))?;
+    let envelope = cp::open_envelope(bucket, key, response.metadata(), master_key)?;
+    let codec = response
+        .content_encoding()
+        .and_then(Codec::from_content_encoding)
+        .or_else(|| Codec::from_name(key).filter(|_| decompress));
     let mut body = response.body;
 
     // Stream output to STDOUT
     let mut stdout = io::stdout();
 
+    if envelope.is_none() && codec.is_none() {
+        while let Some(bytes) = body.try_next().await? {
+            stdout.write_all(&bytes).await?;
+        }
+
+        stdout.flush().await?;
+        return Ok(());
+    }
+
+    if ranged {
+        body = whole
+            .send()
+            .await
+            .map_err(|e| storage_class::explain_archived(bucket, key, e))?
+            .body;
+    }
+
+    let (range, offset, size) = decoded_range;
+    let (start, len) = parse_range_options(range, offset, size)?;
+    let start = start.unwrap_or(0);
+    let end = len.map_or(u64::MAX, |len| start + len);
+
+    let mut decoder = Decoder {
+        decryptor: envelope.map(Decryptor::new),
+        decompressor: codec.map(Decompressor::new).transpose()?,
+    };
+    let mut position = 0u64;
+
     while let Some(bytes) = body.try_next().await? {
-        stdout.write_all(&bytes).await?;
+        let data = decoder.update(&bytes)?;
+        write_slice(&mut stdout, &data, &mut position, start, end).await?;
     }
+    let data = decoder.finish()?;
+    write_slice(&mut stdout, &data, &mut position, start, end).await?;
 
     stdout.flush().await?;
 
     Ok(())
 }
 
+/// Decryption and decompression of content on its way to STDOUT
+struct Decoder {
+    decryptor: Option<Decryptor>,
+    decompressor: Option<Decompressor>,
+}
+
+impl Decoder {
+    fn update(&mut self, data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let data = match &mut self.decryptor {
+            Some(decryptor) => decryptor.update(data)?,
+            None => data.to_vec(),
+        };
+        match &mut self.decompressor {
+            Some(decompressor) => Ok(decompressor.update(&data)?),
+            None => Ok(data),
+        }
+    }
+
+    fn finish(self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let data = match self.decryptor {
+            Some(decryptor) => decryptor.finish()?,
+            None => Vec::new(),
+        };
+        match self.decompressor {
+            Some(mut decompressor) => {
+                let mut output = decompressor.update(&data)?;
+                output.extend(decompressor.finish()?);
+                Ok(output)
+            }
+            None => Ok(data),
+        }
+    }
+}
+
+/// Write the part of `data` that falls within `start..end` of the output,
+/// where `position` is the offset of `data` (advanced past it)
+async fn write_slice(
+    stdout: &mut io::Stdout,
+    data: &[u8],
+    position: &mut u64,
+    start: u64,
+    end: u64,
+) -> io::Result<()> {
+    let from = start.saturating_sub(*position).min(data.len() as u64) as usize;
+    let to = end.saturating_sub(*position).min(data.len() as u64) as usize;
+    *position += data.len() as u64;
+
+    if from < to {
+        stdout.write_all(&data[from..to]).await?;
+    }
+    Ok(())
+}
+
 /// Read and output local file content
 async fn cat_local_file(
     path: &str,
     range: Option<String>,
     offset: Option<u64>,
     size: Option<u64>,
+    decompress: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let path_obj = Path::new(path);
 
@@ -98,6 +314,32 @@ async fn cat_local_file(
     // Parse range options
     let (start_pos, read_size) = parse_range_options(range, offset, size)?;
 
+    // Compressed files are decompressed whole; the range applies to the output
+    if let Some(codec) = Codec::from_name(path).filter(|_| decompress) {
+        let start = start_pos.unwrap_or(0);
+        let end = read_size.map_or(u64::MAX, |len| start + len);
+        let mut decoder = Decoder {
+            decryptor: None,
+            decompressor: Some(Decompressor::new(codec)?),
+        };
+        let mut buffer = vec![0u8; 8192];
+        let mut position = 0u64;
+
+        loop {
+            let n = file.read(&mut buffer).await?;
+            if n == 0 {
+                break;
+            }
+            let data = decoder.update(&buffer[..n])?;
+            write_slice(&mut stdout, &data, &mut position, start, end).await?;
+        }
+        let data = decoder.finish()?;
+        write_slice(&mut stdout, &data, &mut position, start, end).await?;
+
+        stdout.flush().await?;
+        return Ok(());
+    }
+
     if let Some(start) = start_pos {
         file.seek(io::SeekFrom::Start(start)).await?;
     }
@@ -145,11 +387,7 @@ fn parse_range_options(
 ) -> Result<(Option<u64>, Option<u64>), Box<dyn std::error::Error>> {
     if let Some(range_str) = range {
         // Parse range string like "0-100" or "bytes=0-100"
-        let range_part = if range_str.starts_with("bytes=") {
-            &range_str[6..]
-        } else {
-            &range_str[..]
-        };
+        let range_part = range_str.strip_prefix("bytes=").unwrap_or(&range_str);
 
         let parts: Vec<&str> = range_part.split('-').collect();
         if parts.len() != 2 {
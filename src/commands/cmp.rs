@@ -1,4 +1,5 @@
 use crate::path_utils::{parse_path, PathType};
+use crate::sse::{CustomerKey, SseCustomerHeaders};
 use aws_sdk_s3::Client;
 use std::path::Path;
 use tokio::fs::File;
@@ -15,6 +16,7 @@ pub async fn cmp(
     range: Option<String>,
     offset: Option<u64>,
     size: Option<u64>,
+    customer_key: Option<&CustomerKey>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     if range.is_some() && (offset.is_some() || size.is_some()) {
         return Err("Cannot specify both --range and --offset/--size".into());
@@ -22,8 +24,8 @@ pub async fn cmp(
 
     let (start, limit) = resolve_range(range, offset, size)?;
 
-    let mut reader1 = open_reader(client, path1, start, limit).await?;
-    let mut reader2 = open_reader(client, path2, start, limit).await?;
+    let mut reader1 = open_reader(client, path1, start, limit, customer_key).await?;
+    let mut reader2 = open_reader(client, path2, start, limit, customer_key).await?;
 
     let (size1, size2) = (reader1.total_size, reader2.total_size);
 
@@ -97,11 +99,7 @@ fn resolve_range(
     size: Option<u64>,
 ) -> Result<(Option<u64>, Option<u64>), Box<dyn std::error::Error>> {
     if let Some(range_str) = range {
-        let part = if range_str.starts_with("bytes=") {
-            &range_str[6..]
-        } else {
-            &range_str[..]
-        };
+        let part = range_str.strip_prefix("bytes=").unwrap_or(&range_str);
         let parts: Vec<&str> = part.split('-').collect();
         if parts.len() != 2 {
             return Err(format!("Invalid range '{}', expected 'start-end'", range_str).into());
@@ -149,6 +147,7 @@ async fn open_reader(
     path: &str,
     start: Option<u64>,
     limit: Option<u64>,
+    customer_key: Option<&CustomerKey>,
 ) -> Result<Reader, Box<dyn std::error::Error>> {
     match parse_path(path)? {
         PathType::Local(local_path) => {
@@ -178,6 +177,7 @@ async fn open_reader(
                 .head_object()
                 .bucket(&bucket)
                 .key(&key)
+                .sse_customer(customer_key)
                 .send()
                 .await
                 .map_err(|e| format!("Cannot stat s3://{}/{}: {}", bucket, key, e))?;
@@ -185,7 +185,11 @@ async fn open_reader(
 
             // Build Range header
             let range_hdr = build_range_header(start, limit);
-            let mut req = client.get_object().bucket(&bucket).key(&key);
+            let mut req = client
+                .get_object()
+                .bucket(&bucket)
+                .key(&key)
+                .sse_customer(customer_key);
             if let Some(r) = range_hdr {
                 req = req.range(r);
             }
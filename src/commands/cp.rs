@@ -1,29 +1,97 @@
+use crate::compression::{Codec, Compressor};
+use crate::encryption::{self, Decryptor, Envelope, MasterKey};
+use crate::file_metadata::{self, PosixAttributes};
 use crate::filters::FileFilter;
-use crate::path_utils::{join_s3_key, parse_path, PathType};
+use crate::headers::ObjectHeaders;
+use crate::manifest::ManifestEntry;
+use crate::path_utils::{
+    is_dir_marker, join_s3_key, parse_path, relative_key, source_destinations, PathType,
+};
+use crate::sse::{CopySourceSseCustomerHeaders, SseCustomerHeaders, SseOptions};
+use crate::storage_class;
+use crate::temp_file::TempFile;
+use crate::walk::{self, EntryKind, LocalEntry};
+use crate::wildcard::{self, WildcardPattern};
+use aws_sdk_s3::operation::create_multipart_upload::builders::CreateMultipartUploadFluentBuilder;
+use aws_sdk_s3::operation::put_object::builders::PutObjectFluentBuilder;
 use aws_sdk_s3::primitives::ByteStream;
-use aws_sdk_s3::types::{ChecksumAlgorithm, ChecksumMode, CompletedMultipartUpload, CompletedPart};
+use aws_sdk_s3::types::{
+    ChecksumAlgorithm, ChecksumMode, CompletedMultipartUpload, CompletedPart, MetadataDirective,
+    Object, TaggingDirective,
+};
 use aws_sdk_s3::Client;
-use std::path::Path;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
 use tokio::fs;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use walkdir::WalkDir;
+
+/// Options shared by uploads and downloads
+#[derive(Clone)]
+pub struct TransferOptions {
+    pub multipart_threshold: u64,
+    pub multipart_chunksize: u64,
+    /// Store POSIX attributes as user metadata on upload and restore them on download
+    pub preserve: bool,
+    /// Upload symlink targets instead of storing symlinks as marker objects
+    pub follow_symlinks: bool,
+    /// Upload empty directories as `prefix/` marker objects and recreate them on download
+    pub dir_markers: bool,
+    /// Delete each local source once it has been transferred (used by `mv`)
+    pub remove_source: bool,
+    /// Headers and user metadata for uploaded and copied objects
+    pub headers: ObjectHeaders,
+    /// Transfer GLACIER and DEEP_ARCHIVE objects found by listings instead of skipping them
+    pub force_glacier_transfer: bool,
+    /// Server-side encryption of new objects and SSE-C keys of existing ones
+    pub sse: SseOptions,
+    /// Master key for client-side encryption of uploads and decryption of downloads
+    pub encryption: Option<MasterKey>,
+    /// Compress uploads with this codec and set their Content-Encoding
+    pub compress: Option<Codec>,
+    /// Add the codec's suffix (`.gz`, `.zst`) to the keys of compressed uploads
+    pub compress_suffix: bool,
+    /// Take `*` and `?` in S3 keys literally instead of as wildcards (`--no-wildcards`)
+    pub literal_keys: bool,
+}
+
+impl TransferOptions {
+    /// Key an upload to `key` is stored under
+    pub fn upload_key(&self, key: &str) -> String {
+        match self.compress {
+            Some(codec) if self.compress_suffix && !key.ends_with(codec.extension()) => {
+                format!("{}{}", key, codec.extension())
+            }
+            _ => key.to_string(),
+        }
+    }
+
+    /// Content-Encoding of uploads: the codec's when compressing
+    fn content_encoding(&self) -> Option<String> {
+        match self.compress {
+            Some(codec) => Some(codec.content_encoding().to_string()),
+            None => self.headers.content_encoding.clone(),
+        }
+    }
+}
+
+/// Bytes read from a file at a time when compressing it
+const COMPRESS_READ_SIZE: usize = 1024 * 1024;
 
 /// Copy files between local and S3
+///
+/// With several sources, `dest` is a directory or prefix that receives each
+/// source under its own name (see `source_destinations`).
+#[allow(clippy::too_many_arguments)]
 pub async fn copy(
     client: &Client,
-    source: &str,
+    sources: &[String],
     dest: &str,
     recursive: bool,
-    include: Vec<String>,
-    exclude: Vec<String>,
+    filter: &FileFilter,
     checksum_mode: Option<String>,
     checksum_algorithm: Option<String>,
-    multipart_threshold: u64,
-    multipart_chunksize: u64,
+    opts: &TransferOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let source_type = parse_path(source)?;
-    let dest_type = parse_path(dest)?;
-
     // Parse checksum options (only for single object operations)
     let checksum_opts = if !recursive {
         parse_checksum_options(checksum_mode, checksum_algorithm)?
@@ -34,17 +102,189 @@ pub async fn copy(
         (None, None)
     };
 
-    if recursive {
-        let filter = FileFilter::new(include, exclude)?;
-        copy_recursive(
+    let pairs = source_destinations(sources, dest, opts.literal_keys)?;
+    let mut failed = 0;
+
+    for (source, dest) in &pairs {
+        let result = copy_one(
             client,
-            source_type,
-            dest_type,
-            &filter,
-            multipart_threshold,
-            multipart_chunksize,
+            source,
+            dest,
+            recursive,
+            filter,
+            checksum_opts.clone(),
+            opts,
         )
-        .await
+        .await;
+
+        if let Err(e) = result {
+            if pairs.len() == 1 {
+                return Err(e);
+            }
+            eprintln!("Error: {}: {}", source, e);
+            failed += 1;
+        }
+    }
+
+    check_failures(failed, pairs.len())
+}
+
+/// Fail a multi-source command if any source failed (after trying them all)
+pub fn check_failures(failed: usize, total: usize) -> Result<(), Box<dyn std::error::Error>> {
+    if failed > 0 {
+        return Err(format!("{} of {} sources failed", failed, total).into());
+    }
+    Ok(())
+}
+
+/// Copy exactly the entries of a `--files-from` manifest
+///
+/// Entries are resolved against the single source directory or prefix and
+/// written to `dest` under their path relative to it, without listing the
+/// source. Every entry is attempted before the command fails.
+pub async fn copy_manifest(
+    client: &Client,
+    sources: &[String],
+    dest: &str,
+    entries: &[ManifestEntry],
+    checksum_mode: Option<String>,
+    checksum_algorithm: Option<String>,
+    opts: &TransferOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let [source] = sources else {
+        return Err("--files-from takes a single source directory or prefix".into());
+    };
+    let (checksum_mode, checksum_algorithm) =
+        parse_checksum_options(checksum_mode, checksum_algorithm)?;
+    let source_type = parse_path(source)?;
+    let dest_type = parse_path(dest)?;
+    let mut failed = 0;
+
+    for entry in entries {
+        let (source, relative) = entry.resolve(&source_type);
+        let target = match &dest_type {
+            PathType::Local(dir) => {
+                PathType::Local(Path::new(dir).join(relative).to_string_lossy().to_string())
+            }
+            PathType::S3 { bucket, key } => PathType::S3 {
+                bucket: bucket.clone(),
+                key: join_s3_key(key, relative),
+            },
+        };
+
+        let result = copy_entry(
+            client,
+            source,
+            target,
+            entry.version_id.as_deref(),
+            checksum_mode.clone(),
+            checksum_algorithm.clone(),
+            opts,
+        )
+        .await;
+
+        if let Err(e) = result {
+            eprintln!("Error: {}: {}", entry.key, e);
+            failed += 1;
+        }
+    }
+
+    check_failures(failed, entries.len())
+}
+
+/// Copy one manifest entry, a specific object version if one is given
+async fn copy_entry(
+    client: &Client,
+    source: PathType,
+    dest: PathType,
+    version_id: Option<&str>,
+    checksum_mode: Option<ChecksumMode>,
+    checksum_algorithm: Option<ChecksumAlgorithm>,
+    opts: &TransferOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match (&source, &dest) {
+        (PathType::S3 { bucket, key }, PathType::Local(dst)) => {
+            if is_dir_marker(key) {
+                return download_dir_marker(bucket, key, Path::new(dst), opts).await;
+            }
+            let root = Path::new(dst).parent().unwrap_or(Path::new(""));
+            download_file(
+                client,
+                bucket,
+                key,
+                version_id,
+                dst,
+                root,
+                checksum_mode,
+                opts,
+            )
+            .await?;
+            Ok(())
+        }
+        (
+            PathType::S3 {
+                bucket: src_bucket,
+                key: src_key,
+            },
+            PathType::S3 {
+                bucket: dst_bucket,
+                key: dst_key,
+            },
+        ) => {
+            copy_s3_to_s3(
+                client, src_bucket, src_key, version_id, dst_bucket, dst_key, opts,
+            )
+            .await
+        }
+        (PathType::Local(_), PathType::Local(dst)) => {
+            if let Some(parent) = Path::new(dst).parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            copy_single(
+                client,
+                source,
+                dest,
+                checksum_mode,
+                checksum_algorithm,
+                opts,
+            )
+            .await
+        }
+        (PathType::Local(_), PathType::S3 { .. }) => {
+            copy_single(
+                client,
+                source,
+                dest,
+                checksum_mode,
+                checksum_algorithm,
+                opts,
+            )
+            .await
+        }
+    }
+}
+
+/// Copy one source to its destination
+async fn copy_one(
+    client: &Client,
+    source: &str,
+    dest: &str,
+    recursive: bool,
+    filter: &FileFilter,
+    checksum_opts: (Option<ChecksumMode>, Option<ChecksumAlgorithm>),
+    opts: &TransferOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let source_type = parse_path(source)?;
+    let dest_type = parse_path(dest)?;
+
+    if let PathType::S3 { bucket, key } = &source_type {
+        if wildcard::has_wildcard(key, opts.literal_keys) {
+            return copy_wildcard(client, bucket, key, &dest_type, filter, opts).await;
+        }
+    }
+
+    if recursive {
+        copy_recursive(client, source_type, dest_type, filter, opts).await
     } else {
         copy_single(
             client,
@@ -52,13 +292,91 @@ pub async fn copy(
             dest_type,
             checksum_opts.0,
             checksum_opts.1,
-            multipart_threshold,
-            multipart_chunksize,
+            opts,
         )
         .await
     }
 }
 
+/// Copy every object matching a wildcard pattern into a destination directory
+///
+/// Keys keep their path below the directory holding the first wildcard, so
+/// `s3://b/logs/2024-*/app.gz` to `./out` writes `./out/2024-01/app.gz`.
+async fn copy_wildcard(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+    dest: &PathType,
+    filter: &FileFilter,
+    opts: &TransferOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let pattern = WildcardPattern::new(key)?;
+    let objects = wildcard::list_matching(client, bucket, &pattern).await?;
+    if objects.is_empty() {
+        return Err(format!("No objects match s3://{}/{}", bucket, key).into());
+    }
+
+    for obj in &objects {
+        let Some(key) = obj.key() else { continue };
+        let relative = pattern.relative(key);
+        if !filter.matches_object(relative, obj) || skip_archived(bucket, obj, opts) {
+            continue;
+        }
+
+        match dest {
+            PathType::Local(local_dir) => {
+                let local_path = Path::new(local_dir).join(relative);
+                if is_dir_marker(key) {
+                    download_dir_marker(bucket, key, &local_path, opts).await?;
+                    continue;
+                }
+                download_file(
+                    client,
+                    bucket,
+                    key,
+                    None,
+                    local_path.to_str().unwrap(),
+                    Path::new(local_dir),
+                    None,
+                    opts,
+                )
+                .await?;
+            }
+            PathType::S3 {
+                bucket: dst_bucket,
+                key: dst_prefix,
+            } => {
+                let dst_key = join_s3_key(dst_prefix, relative);
+                copy_s3_to_s3(client, bucket, key, None, dst_bucket, &dst_key, opts).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether to skip an archived object found by a listing
+///
+/// GLACIER and DEEP_ARCHIVE objects are only readable once restored, which a
+/// listing does not show, so they are skipped with a warning unless
+/// `--force-glacier-transfer` is given.
+pub fn skip_archived(bucket: &str, obj: &Object, opts: &TransferOptions) -> bool {
+    let Some(class) = obj.storage_class().map(|class| class.as_str()) else {
+        return false;
+    };
+    if opts.force_glacier_transfer || !storage_class::is_archived(class) {
+        return false;
+    }
+
+    eprintln!(
+        "Warning: Skipping s3://{}/{}: object is in {} storage (restore it with `hsc restore`, then use --force-glacier-transfer)",
+        bucket,
+        obj.key().unwrap_or_default(),
+        class
+    );
+    true
+}
+
 /// Parse checksum options
 fn parse_checksum_options(
     mode: Option<String>,
@@ -100,8 +418,7 @@ async fn copy_single(
     dest: PathType,
     checksum_mode: Option<ChecksumMode>,
     checksum_algorithm: Option<ChecksumAlgorithm>,
-    multipart_threshold: u64,
-    multipart_chunksize: u64,
+    opts: &TransferOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
     match (&source, &dest) {
         (PathType::Local(src), PathType::S3 { bucket, key }) => {
@@ -113,14 +430,16 @@ async fn copy_single(
                 key,
                 checksum_mode,
                 checksum_algorithm,
-                multipart_threshold,
-                multipart_chunksize,
+                opts,
             )
-            .await
+            .await?;
+            remove_local_source(Path::new(src), opts).await
         }
         (PathType::S3 { bucket, key }, PathType::Local(dst)) => {
             // S3 to local
-            download_file(client, bucket, key, dst, checksum_mode).await
+            let root = Path::new(dst).parent().unwrap_or(Path::new(""));
+            download_file(client, bucket, key, None, dst, root, checksum_mode, opts).await?;
+            Ok(())
         }
         (
             PathType::S3 {
@@ -133,14 +452,82 @@ async fn copy_single(
             },
         ) => {
             // S3 to S3
-            copy_s3_to_s3(client, src_bucket, src_key, dst_bucket, dst_key).await
+            copy_s3_to_s3(client, src_bucket, src_key, None, dst_bucket, dst_key, opts).await
         }
         (PathType::Local(src), PathType::Local(dst)) => {
-            // Local to local
-            fs::copy(src, dst).await?;
-            println!("Copied: {} -> {}", src, dst);
-            Ok(())
+            // Local to local, handled like the files of a directory copy
+            let entry = LocalEntry::from_path(Path::new(src), opts.follow_symlinks)?;
+            let target = local_file_target(&entry, dst);
+            if is_same_file(&entry, &target) {
+                return Err(format!("{} and {} are the same file", src, target.display()).into());
+            }
+            if opts.remove_source && move_local_file(&entry, &target).await? {
+                return Ok(());
+            }
+            copy_local_file(&entry, &target, opts).await?;
+            remove_local_source(Path::new(src), opts).await
+        }
+    }
+}
+
+/// Whether `target` is the file `entry` would be copied from: the same path,
+/// or another hard link to it. Copying it onto itself would destroy it.
+fn is_same_file(entry: &LocalEntry, target: &Path) -> bool {
+    let metadata = |path: &Path| match entry.kind {
+        EntryKind::File => std::fs::metadata(path),
+        EntryKind::Symlink(_) => std::fs::symlink_metadata(path),
+    };
+    let (Ok(source), Ok(existing)) = (metadata(&entry.path), metadata(target)) else {
+        return false;
+    };
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        source.dev() == existing.dev() && source.ino() == existing.ino()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (source, existing);
+        std::fs::canonicalize(&entry.path).ok() == std::fs::canonicalize(target).ok()
+    }
+}
+
+/// Move a single local file by renaming it; returns false when it has to be
+/// copied and deleted instead
+///
+/// Renaming keeps the file as it is, which matches a copy unless a followed
+/// symlink has to become the file it points to. Across file systems the
+/// rename fails and the caller falls back to copying.
+async fn move_local_file(
+    entry: &LocalEntry,
+    target: &Path,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    if entry.kind == EntryKind::File && fs::symlink_metadata(&entry.path).await?.is_symlink() {
+        return Ok(false);
+    }
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+
+    match fs::rename(&entry.path, target).await {
+        Ok(()) => {
+            println!("Moved: {} -> {}", entry.path.display(), target.display());
+            Ok(true)
         }
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Where a single local file is copied to: `dst` itself, or the file's name
+/// inside `dst` when that is a directory or ends with a separator
+fn local_file_target(entry: &LocalEntry, dst: &str) -> PathBuf {
+    let dst_path = Path::new(dst);
+    if dst.ends_with(std::path::MAIN_SEPARATOR) || dst.ends_with('/') || dst_path.is_dir() {
+        dst_path.join(&entry.relative)
+    } else {
+        dst_path.to_path_buf()
     }
 }
 
@@ -152,14 +539,51 @@ pub async fn upload_file(
     key: &str,
     checksum_mode: Option<ChecksumMode>,
     checksum_algorithm: Option<ChecksumAlgorithm>,
-    multipart_threshold: u64,
-    multipart_chunksize: u64,
+    opts: &TransferOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    if !opts.follow_symlinks && fs::symlink_metadata(local_path).await?.is_symlink() {
+        return upload_symlink(client, local_path, bucket, key, &opts.sse).await;
+    }
+
+    let key = &opts.upload_key(key);
+    let checksum_algorithm =
+        checksum_mode.map(|_| checksum_algorithm.unwrap_or(ChecksumAlgorithm::Crc32));
+
     // Check file size
     let metadata = fs::metadata(local_path).await?;
     let file_size = metadata.len();
 
-    if file_size >= multipart_threshold {
+    let mut user_metadata = if opts.preserve {
+        Some(file_metadata::to_s3_metadata(&metadata))
+    } else {
+        None
+    };
+
+    if let Some(codec) = opts.compress {
+        return upload_file_compressed(
+            client,
+            local_path,
+            bucket,
+            key,
+            user_metadata,
+            codec,
+            checksum_algorithm,
+            opts,
+        )
+        .await;
+    }
+
+    // Client-side encryption uses a fresh data key per object, stored wrapped
+    // in the object's metadata
+    let envelope = opts.encryption.as_ref().map(|master| {
+        let envelope = Envelope::generate();
+        user_metadata
+            .get_or_insert_with(HashMap::new)
+            .extend(envelope.to_metadata(master, file_size));
+        envelope
+    });
+
+    if file_size >= opts.multipart_threshold {
         // Use multipart upload
         upload_file_multipart(
             client,
@@ -167,48 +591,319 @@ pub async fn upload_file(
             bucket,
             key,
             file_size,
-            multipart_chunksize,
+            user_metadata,
+            envelope.as_ref(),
+            opts,
         )
         .await
     } else {
         // Use regular put_object
-        let body = ByteStream::from_path(Path::new(local_path)).await?;
+        let body = match &envelope {
+            Some(envelope) => {
+                ByteStream::from(envelope.encrypt(0, &fs::read(local_path).await?, true))
+            }
+            None => ByteStream::from_path(Path::new(local_path)).await?,
+        };
+
+        put_object_request(client, local_path, bucket, key, user_metadata, opts)
+            .body(body)
+            .set_checksum_algorithm(checksum_algorithm)
+            .send()
+            .await?;
+
+        println!("Uploaded: {} -> s3://{}/{}", local_path, bucket, key);
+        Ok(())
+    }
+}
+
+/// PutObject request for an upload, with the headers, storage class and
+/// encryption of `opts`
+fn put_object_request(
+    client: &Client,
+    local_path: &str,
+    bucket: &str,
+    key: &str,
+    user_metadata: Option<HashMap<String, String>>,
+    opts: &TransferOptions,
+) -> PutObjectFluentBuilder {
+    let headers = &opts.headers;
+    let sse = &opts.sse;
+
+    client
+        .put_object()
+        .bucket(bucket)
+        .key(key)
+        .set_content_type(headers.content_type_for(local_path))
+        .set_cache_control(headers.cache_control.clone())
+        .set_content_disposition(headers.content_disposition.clone())
+        .set_content_encoding(opts.content_encoding())
+        .set_expires(headers.expires())
+        .set_metadata(headers.metadata_with(user_metadata))
+        .set_storage_class(headers.storage_class.clone())
+        .set_tagging(headers.tagging.clone())
+        .set_server_side_encryption(sse.sse.clone())
+        .set_ssekms_key_id(sse.kms_key_id.clone())
+        .sse_customer(sse.customer_key.as_ref())
+}
+
+/// CreateMultipartUpload request for an upload, set up like `put_object_request`
+fn create_multipart_request(
+    client: &Client,
+    local_path: &str,
+    bucket: &str,
+    key: &str,
+    user_metadata: Option<HashMap<String, String>>,
+    opts: &TransferOptions,
+) -> CreateMultipartUploadFluentBuilder {
+    let headers = &opts.headers;
+    let sse = &opts.sse;
+
+    client
+        .create_multipart_upload()
+        .bucket(bucket)
+        .key(key)
+        .set_content_type(headers.content_type_for(local_path))
+        .set_cache_control(headers.cache_control.clone())
+        .set_content_disposition(headers.content_disposition.clone())
+        .set_content_encoding(opts.content_encoding())
+        .set_expires(headers.expires())
+        .set_metadata(headers.metadata_with(user_metadata))
+        .set_storage_class(headers.storage_class.clone())
+        .set_tagging(headers.tagging.clone())
+        .set_server_side_encryption(sse.sse.clone())
+        .set_ssekms_key_id(sse.kms_key_id.clone())
+        .sse_customer(sse.customer_key.as_ref())
+}
+
+/// Upload one part of a multipart upload
+async fn upload_part(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+    upload_id: &str,
+    part_number: i32,
+    body: ByteStream,
+    sse: &SseOptions,
+) -> Result<CompletedPart, Box<dyn std::error::Error>> {
+    let response = client
+        .upload_part()
+        .bucket(bucket)
+        .key(key)
+        .upload_id(upload_id)
+        .part_number(part_number)
+        .body(body)
+        .sse_customer(sse.customer_key.as_ref())
+        .send()
+        .await?;
+
+    let etag = response.e_tag().ok_or("Failed to get ETag for part")?;
+
+    Ok(CompletedPart::builder()
+        .part_number(part_number)
+        .e_tag(etag)
+        .build())
+}
+
+/// Complete a multipart upload from its parts
+async fn complete_multipart_upload(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+    upload_id: &str,
+    parts: Vec<CompletedPart>,
+    sse: &SseOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let completed_upload = CompletedMultipartUpload::builder()
+        .set_parts(Some(parts))
+        .build();
+
+    client
+        .complete_multipart_upload()
+        .bucket(bucket)
+        .key(key)
+        .upload_id(upload_id)
+        .multipart_upload(completed_upload)
+        .sse_customer(sse.customer_key.as_ref())
+        .send()
+        .await?;
+
+    Ok(())
+}
+
+/// Upload a file compressed with `codec`, compressing while it is read
+///
+/// The compressed size is not known in advance: output below the multipart
+/// threshold is sent with one PutObject, otherwise a multipart upload is
+/// started once that much output has been produced.
+#[allow(clippy::too_many_arguments)]
+async fn upload_file_compressed(
+    client: &Client,
+    local_path: &str,
+    bucket: &str,
+    key: &str,
+    user_metadata: Option<HashMap<String, String>>,
+    codec: Codec,
+    checksum_algorithm: Option<ChecksumAlgorithm>,
+    opts: &TransferOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let chunk_size = opts.multipart_chunksize as usize;
+    let mut file = fs::File::open(local_path).await?;
+    let mut compressor = Compressor::new(codec)?;
+    let mut buffer = vec![0u8; COMPRESS_READ_SIZE];
+    let mut pending = Vec::new();
+    let mut upload_id: Option<String> = None;
+    let mut parts = Vec::new();
+    let mut read_bytes = 0u64;
+    let mut uploaded_bytes = 0u64;
 
-        let mut request = client.put_object().bucket(bucket).key(key).body(body);
+    loop {
+        let n = file.read(&mut buffer).await?;
+        if n == 0 {
+            break; // EOF
+        }
+        read_bytes += n as u64;
+        pending.extend(compressor.update(&buffer[..n])?);
+
+        if upload_id.is_none() && pending.len() as u64 >= opts.multipart_threshold {
+            let response = create_multipart_request(
+                client,
+                local_path,
+                bucket,
+                key,
+                user_metadata.clone(),
+                opts,
+            )
+            .send()
+            .await?;
+            let id = response.upload_id().ok_or("Failed to get upload ID")?;
+            upload_id = Some(id.to_string());
+        }
 
-        if checksum_mode.is_some() {
-            request =
-                request.checksum_algorithm(checksum_algorithm.unwrap_or(ChecksumAlgorithm::Crc32));
+        if let Some(upload_id) = &upload_id {
+            while pending.len() >= chunk_size {
+                let part: Vec<u8> = pending.drain(..chunk_size).collect();
+                let part_number = parts.len() as i32 + 1;
+                let body = ByteStream::from(part);
+                parts.push(
+                    upload_part(client, bucket, key, upload_id, part_number, body, &opts.sse)
+                        .await?,
+                );
+                uploaded_bytes += chunk_size as u64;
+            }
         }
+    }
 
-        request.send().await?;
+    pending.extend(compressor.finish()?);
+    let compressed_size = uploaded_bytes + pending.len() as u64;
+
+    match upload_id {
+        Some(upload_id) => {
+            if !pending.is_empty() {
+                let part_number = parts.len() as i32 + 1;
+                let body = ByteStream::from(pending);
+                parts.push(
+                    upload_part(
+                        client,
+                        bucket,
+                        key,
+                        &upload_id,
+                        part_number,
+                        body,
+                        &opts.sse,
+                    )
+                    .await?,
+                );
+            }
+            complete_multipart_upload(client, bucket, key, &upload_id, parts, &opts.sse).await?;
+        }
+        None => {
+            put_object_request(client, local_path, bucket, key, user_metadata, opts)
+                .body(ByteStream::from(pending))
+                .set_checksum_algorithm(checksum_algorithm)
+                .send()
+                .await?;
+        }
+    }
+
+    println!(
+        "Uploaded: {} -> s3://{}/{} ({}, {} -> {} bytes)",
+        local_path,
+        bucket,
+        key,
+        codec.content_encoding(),
+        read_bytes,
+        compressed_size
+    );
+    Ok(())
+}
+
+/// Upload a symlink as a marker object
+///
+/// The object body is the link target and the `mode` metadata carries the
+/// symlink file type (the s3fs convention), so downloads can recreate the link.
+async fn upload_symlink(
+    client: &Client,
+    local_path: &str,
+    bucket: &str,
+    key: &str,
+    sse: &SseOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let target = fs::read_link(local_path).await?;
+    let target = target
+        .to_str()
+        .ok_or_else(|| format!("Symlink target of {} is not valid UTF-8", local_path))?;
+    let metadata = file_metadata::symlink_marker_metadata(Path::new(local_path))?;
+
+    client
+        .put_object()
+        .bucket(bucket)
+        .key(key)
+        .body(ByteStream::from(target.as_bytes().to_vec()))
+        .set_metadata(Some(metadata))
+        .set_server_side_encryption(sse.sse.clone())
+        .set_ssekms_key_id(sse.kms_key_id.clone())
+        .sse_customer(sse.customer_key.as_ref())
+        .send()
+        .await?;
 
-        println!("Uploaded: {} -> s3://{}/{}", local_path, bucket, key);
-        Ok(())
-    }
+    println!(
+        "Uploaded symlink: {} -> s3://{}/{} (target: {})",
+        local_path, bucket, key, target
+    );
+    Ok(())
 }
 
 /// Upload a file to S3 using multipart upload
+///
+/// With client-side encryption each part is encrypted on its own, so parts
+/// are rounded up to whole encryption chunks.
+#[allow(clippy::too_many_arguments)]
 async fn upload_file_multipart(
     client: &Client,
     local_path: &str,
     bucket: &str,
     key: &str,
     file_size: u64,
-    chunk_size: u64,
+    user_metadata: Option<HashMap<String, String>>,
+    envelope: Option<&Envelope>,
+    opts: &TransferOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let chunk_size = match envelope {
+        Some(_) => opts
+            .multipart_chunksize
+            .next_multiple_of(encryption::CHUNK_SIZE as u64),
+        None => opts.multipart_chunksize,
+    };
     println!(
         "Using multipart upload for {} ({} bytes, {} bytes per part)",
         local_path, file_size, chunk_size
     );
 
     // Step 1: Create multipart upload
-    let multipart_upload = client
-        .create_multipart_upload()
-        .bucket(bucket)
-        .key(key)
-        .send()
-        .await?;
+    let multipart_upload =
+        create_multipart_request(client, local_path, bucket, key, user_metadata, opts)
+            .send()
+            .await?;
 
     let upload_id = multipart_upload
         .upload_id()
@@ -241,28 +936,16 @@ async fn upload_file_multipart(
         buffer.truncate(bytes_read);
 
         // Upload this part
-        let body = ByteStream::from(buffer);
-        let upload_part_response = client
-            .upload_part()
-            .bucket(bucket)
-            .key(key)
-            .upload_id(upload_id)
-            .part_number(part_number)
-            .body(body)
-            .send()
-            .await?;
-
-        let etag = upload_part_response
-            .e_tag()
-            .ok_or("Failed to get ETag for part")?
-            .to_string();
-
-        parts.push(
-            CompletedPart::builder()
-                .part_number(part_number)
-                .e_tag(etag)
-                .build(),
-        );
+        let body = match envelope {
+            Some(envelope) => {
+                let first_chunk = uploaded_bytes / encryption::CHUNK_SIZE as u64;
+                let last = uploaded_bytes + bytes_read as u64 == file_size;
+                ByteStream::from(envelope.encrypt(first_chunk, &buffer, last))
+            }
+            None => ByteStream::from(buffer),
+        };
+        parts
+            .push(upload_part(client, bucket, key, upload_id, part_number, body, &opts.sse).await?);
 
         uploaded_bytes += bytes_read as u64;
         println!(
@@ -281,18 +964,7 @@ async fn upload_file_multipart(
     }
 
     // Step 3: Complete multipart upload
-    let completed_upload = CompletedMultipartUpload::builder()
-        .set_parts(Some(parts))
-        .build();
-
-    client
-        .complete_multipart_upload()
-        .bucket(bucket)
-        .key(key)
-        .upload_id(upload_id)
-        .multipart_upload(completed_upload)
-        .send()
-        .await?;
+    complete_multipart_upload(client, bucket, key, upload_id, parts, &opts.sse).await?;
 
     println!(
         "Multipart upload completed: {} -> s3://{}/{}",
@@ -302,54 +974,319 @@ async fn upload_file_multipart(
 }
 
 /// Download a file from S3
+///
+/// The object is written to a temp file in the destination directory, synced
+/// to disk and then renamed over `local_path`, so an interrupted or failed
+/// download never leaves a truncated file behind. With `checksum_mode` set the
+/// SDK validates the stored checksum while streaming, before the rename.
+///
+/// Symlink marker objects are recreated as symlinks only when not following
+/// symlinks, and only if their target stays inside `root`, the directory
+/// being downloaded into; a marker pointing outside it is skipped with a
+/// warning. Returns whether anything was written.
+#[allow(clippy::too_many_arguments)]
 pub async fn download_file(
     client: &Client,
     bucket: &str,
     key: &str,
+    version_id: Option<&str>,
     local_path: &str,
+    root: &Path,
     checksum_mode: Option<ChecksumMode>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut request = client.get_object().bucket(bucket).key(key);
+    opts: &TransferOptions,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let customer_key = opts.sse.customer_key.as_ref();
+    let mut request = client
+        .get_object()
+        .bucket(bucket)
+        .key(key)
+        .set_version_id(version_id.map(String::from))
+        .sse_customer(customer_key);
 
     if let Some(mode) = checksum_mode {
         request = request.checksum_mode(mode);
     }
 
-    let response = request.send().await?;
+    let response = request
+        .send()
+        .await
+        .map_err(|e| storage_class::explain_archived(bucket, key, e))?;
+    let expected_size = response.content_length();
+    let attributes = response
+        .metadata()
+        .map(PosixAttributes::from_s3_metadata)
+        .unwrap_or_default();
+    let envelope = open_envelope(bucket, key, response.metadata(), opts.encryption.as_ref())?;
 
     // Create parent directories if needed
-    if let Some(parent) = Path::new(local_path).parent() {
+    let target = Path::new(local_path);
+    if let Some(parent) = target.parent() {
         fs::create_dir_all(parent).await?;
     }
 
-    let mut file = fs::File::create(local_path).await?;
+    if attributes.is_symlink() && !opts.follow_symlinks {
+        let link_target = response.body.collect().await?.into_bytes();
+        let link_target = String::from_utf8(link_target.to_vec())
+            .map_err(|_| format!("Symlink target in s3://{}/{} is not UTF-8", bucket, key))?;
+        if !file_metadata::symlink_stays_within(root, target, &link_target) {
+            eprintln!(
+                "Warning: skipping symlink s3://{}/{}: target {} is outside {}",
+                bucket,
+                key,
+                link_target,
+                root.display()
+            );
+            return Ok(false);
+        }
+        create_symlink(&link_target, target).await?;
+        println!(
+            "Downloaded symlink: s3://{}/{} -> {} (target: {})",
+            bucket, key, local_path, link_target
+        );
+        return Ok(true);
+    }
+
+    let temp = TempFile::new(target);
+    let mut file = fs::File::create(temp.path()).await?;
     let mut body = response.body;
+    let mut decryptor = envelope.map(Decryptor::new);
+    let mut received = 0u64;
 
     while let Some(chunk) = body.try_next().await? {
-        file.write_all(&chunk).await?;
+        match decryptor.as_mut() {
+            Some(decryptor) => file.write_all(&decryptor.update(&chunk)?).await?,
+            None => file.write_all(&chunk).await?,
+        }
+        received += chunk.len() as u64;
     }
 
+    if let Some(decryptor) = decryptor {
+        file.write_all(&decryptor.finish()?).await?;
+    }
+
+    file.flush().await?;
+    file.sync_all().await?;
+    drop(file);
+
+    if let Some(expected) = expected_size {
+        if received != expected as u64 {
+            return Err(format!(
+                "Incomplete download of s3://{}/{}: received {} of {} bytes",
+                bucket, key, received, expected
+            )
+            .into());
+        }
+    }
+
+    if opts.preserve {
+        attributes.apply(temp.path())?;
+    }
+
+    temp.persist(target).await?;
+
     println!("Downloaded: s3://{}/{} -> {}", bucket, key, local_path);
+    Ok(true)
+}
+
+/// Envelope of a client-side encrypted object, or `None` for plain objects
+///
+/// Encrypted objects cannot be read without the master key.
+pub fn open_envelope(
+    bucket: &str,
+    key: &str,
+    metadata: Option<&HashMap<String, String>>,
+    master: Option<&MasterKey>,
+) -> Result<Option<Envelope>, String> {
+    let Some(metadata) = metadata.filter(|m| encryption::is_encrypted(m)) else {
+        return Ok(None);
+    };
+    let master = master.ok_or_else(|| {
+        format!(
+            "s3://{}/{} is client-side encrypted; pass --encryption-key-file to read it",
+            bucket, key
+        )
+    })?;
+    Envelope::from_metadata(master, metadata).map_err(|e| format!("s3://{}/{}: {}", bucket, key, e))
+}
+
+/// Whether a local file and an S3 object hold the same amount of data
+///
+/// With client-side encryption the object may hold the encrypted file. Its
+/// size alone cannot tell an encrypted copy from a plain object that happens
+/// to be as large, so when it matches either size the plaintext size is read
+/// from the object's metadata.
+pub async fn same_size(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+    local_size: u64,
+    s3_size: i64,
+    opts: &TransferOptions,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let s3_size = s3_size.max(0) as u64;
+    if opts.encryption.is_none() {
+        return Ok(local_size == s3_size);
+    }
+    if local_size != s3_size && encryption::encrypted_size(local_size) != s3_size {
+        return Ok(false);
+    }
+
+    let customer_key = opts.sse.customer_key.as_ref();
+    let head = client
+        .head_object()
+        .bucket(bucket)
+        .key(key)
+        .sse_customer(customer_key)
+        .send()
+        .await?;
+    let metadata = head.metadata().cloned().unwrap_or_default();
+    Ok(encryption::data_size(&metadata, s3_size) == Some(local_size))
+}
+
+/// Create a symlink pointing at `link_target`, replacing `target` atomically
+#[cfg(unix)]
+async fn create_symlink(link_target: &str, target: &Path) -> std::io::Result<()> {
+    let temp = TempFile::new(target);
+    fs::symlink(link_target, temp.path()).await?;
+    temp.persist(target).await
+}
+
+/// Symlinks cannot be recreated portably; write the link target instead
+#[cfg(not(unix))]
+async fn create_symlink(link_target: &str, target: &Path) -> std::io::Result<()> {
+    eprintln!(
+        "Warning: cannot create symlink {}, writing its target as file content",
+        target.display()
+    );
+    fs::write(target, link_target).await
+}
+
+/// Copy a walked local entry to `target`, the way a download writes it
+///
+/// Files are copied to a temp file next to `target` and renamed into place;
+/// symlinks not being followed are recreated as symlinks. With `preserve` the
+/// same attributes an upload would store are applied to the copy.
+pub async fn copy_local_file(
+    entry: &LocalEntry,
+    target: &Path,
+    opts: &TransferOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+
+    if let EntryKind::Symlink(link_target) = &entry.kind {
+        create_symlink(link_target, target).await?;
+        println!(
+            "Copied symlink: {} -> {} (target: {})",
+            entry.path.display(),
+            target.display(),
+            link_target
+        );
+        return Ok(());
+    }
+
+    let temp = TempFile::new(target);
+    fs::copy(&entry.path, temp.path()).await?;
+    fs::File::open(temp.path()).await?.sync_all().await?;
+
+    if opts.preserve {
+        let metadata = entry.metadata()?;
+        PosixAttributes::from_s3_metadata(&file_metadata::to_s3_metadata(&metadata))
+            .apply(temp.path())?;
+    }
+
+    temp.persist(target).await?;
+
+    println!("Copied: {} -> {}", entry.path.display(), target.display());
     Ok(())
 }
 
-/// Copy object from S3 to S3
+/// Copy object from S3 to S3, optionally a specific version of the source
+///
+/// Headers given in `opts` are merged over the source object's headers and
+/// metadata; an explicit REPLACE directive uses only the given ones (keeping
+/// a client-side encryption envelope). Tags are copied from the source unless
+/// `--tags` gives new ones.
 pub async fn copy_s3_to_s3(
     client: &Client,
     src_bucket: &str,
     src_key: &str,
+    src_version_id: Option<&str>,
     dst_bucket: &str,
     dst_key: &str,
+    opts: &TransferOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let copy_source = format!("{}/{}", src_bucket, src_key);
+    let headers = &opts.headers;
+    let sse = &opts.sse;
+    let source_key = sse.copy_source_key.as_ref();
 
-    client
+    let copy_source = match src_version_id {
+        Some(version_id) => format!("{}/{}?versionId={}", src_bucket, src_key, version_id),
+        None => format!("{}/{}", src_bucket, src_key),
+    };
+
+    let mut request = client
         .copy_object()
         .copy_source(&copy_source)
         .bucket(dst_bucket)
         .key(dst_key)
+        .set_storage_class(headers.storage_class.clone())
+        .set_server_side_encryption(sse.sse.clone())
+        .set_ssekms_key_id(sse.kms_key_id.clone())
+        .sse_customer(sse.customer_key.as_ref())
+        .copy_source_sse_customer(source_key);
+
+    let directive = headers.copy_directive();
+    if directive == MetadataDirective::Replace {
+        let source = client
+            .head_object()
+            .bucket(src_bucket)
+            .key(src_key)
+            .set_version_id(src_version_id.map(String::from))
+            .sse_customer(source_key)
+            .send()
+            .await
+            .map_err(|e| storage_class::explain_archived(src_bucket, src_key, e))?;
+
+        let replacement = if headers.metadata_directive.is_none() {
+            // Headers given on their own are merged over the source's
+            request = request.set_content_language(source.content_language().map(String::from));
+            headers.over(&ObjectHeaders::from_source(&source))
+        } else {
+            // An explicit REPLACE starts afresh, but must not drop the
+            // wrapped data key of a client-side encrypted source
+            let envelope = source
+                .metadata()
+                .map(encryption::envelope_metadata)
+                .filter(|metadata| !metadata.is_empty());
+            ObjectHeaders {
+                metadata: headers.metadata_with(envelope).unwrap_or_default(),
+                ..headers.clone()
+            }
+        };
+
+        request = request
+            .set_content_type(replacement.content_type_for(dst_key))
+            .set_cache_control(replacement.cache_control.clone())
+            .set_content_disposition(replacement.content_disposition.clone())
+            .set_content_encoding(replacement.content_encoding.clone())
+            .set_expires(replacement.expires())
+            .set_metadata(Some(replacement.metadata));
+    }
+
+    // Copies keep the source tags unless --tags replaces them
+    if let Some(tagging) = &headers.tagging {
+        request = request
+            .tagging(tagging)
+            .tagging_directive(TaggingDirective::Replace);
+    }
+
+    request
+        .metadata_directive(directive)
         .send()
-        .await?;
+        .await
+        .map_err(|e| storage_class::explain_archived(src_bucket, src_key, e))?;
 
     println!(
         "Copied: s3://{}/{} -> s3://{}/{}",
@@ -364,26 +1301,16 @@ async fn copy_recursive(
     source: PathType,
     dest: PathType,
     filter: &FileFilter,
-    multipart_threshold: u64,
-    multipart_chunksize: u64,
+    opts: &TransferOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
     match (&source, &dest) {
         (PathType::Local(src), PathType::S3 { bucket, key }) => {
             // Local directory to S3
-            upload_directory(
-                client,
-                src,
-                bucket,
-                key,
-                filter,
-                multipart_threshold,
-                multipart_chunksize,
-            )
-            .await
+            upload_directory(client, src, bucket, key, filter, opts).await
         }
         (PathType::S3 { bucket, key }, PathType::Local(dst)) => {
             // S3 prefix to local directory
-            download_directory(client, bucket, key, dst, filter).await
+            download_directory(client, bucket, key, dst, filter, opts).await
         }
         (
             PathType::S3 {
@@ -396,12 +1323,88 @@ async fn copy_recursive(
             },
         ) => {
             // S3 to S3 recursive
-            copy_s3_directory(client, src_bucket, src_key, dst_bucket, dst_key, filter).await
+            copy_s3_directory(
+                client, src_bucket, src_key, dst_bucket, dst_key, filter, opts,
+            )
+            .await
+        }
+        (PathType::Local(src), PathType::Local(dst)) => {
+            // Local directory to local directory
+            copy_local_directory(src, dst, filter, opts).await
+        }
+    }
+}
+
+/// Copy a local directory to another local directory
+async fn copy_local_directory(
+    src_dir: &str,
+    dst_dir: &str,
+    filter: &FileFilter,
+    opts: &TransferOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    check_local_destination(src_dir, dst_dir).await?;
+    let mut moved_from = BTreeSet::new();
+
+    for entry in walk::walk(Path::new(src_dir), opts.follow_symlinks, filter) {
+        // Apply filters
+        if !filter.matches_local(&entry) {
+            continue;
+        }
+
+        copy_local_file(&entry, &Path::new(dst_dir).join(&entry.relative), opts).await?;
+        if remove_walked_source(Path::new(src_dir), &entry.path, opts).await? {
+            moved_from.extend(entry.path.parent().map(Path::to_path_buf));
         }
-        (PathType::Local(_), PathType::Local(_)) => Err(
-            "Local to local recursive copy not implemented. Use standard 'cp -r' command.".into(),
-        ),
     }
+
+    if opts.dir_markers {
+        for relative in walk::empty_dirs(Path::new(src_dir), opts.follow_symlinks, filter) {
+            if !filter.matches(&relative) {
+                continue;
+            }
+
+            let local_path = Path::new(dst_dir).join(&relative);
+            fs::create_dir_all(&local_path).await?;
+            println!("Created directory: {}", local_path.display());
+            moved_from.insert(Path::new(src_dir).join(&relative));
+        }
+    }
+
+    if opts.remove_source {
+        prune_empty_dirs(Path::new(src_dir), &moved_from).await;
+    }
+
+    Ok(())
+}
+
+/// Refuse to copy a local directory into itself or one of its subdirectories
+///
+/// The walk would otherwise pick up the files it has just written.
+pub async fn check_local_destination(
+    src_dir: &str,
+    dst_dir: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let src = fs::canonicalize(src_dir)
+        .await
+        .map_err(|e| format!("Cannot access {}: {}", src_dir, e))?;
+    if !src.is_dir() {
+        return Err(format!("{} is not a directory", src_dir).into());
+    }
+
+    // Resolve the deepest existing ancestor, since dst_dir may not exist yet
+    let dst = std::path::absolute(dst_dir)?;
+    let mut existing = dst.as_path();
+    while !existing.exists() {
+        existing = existing.parent().unwrap_or(Path::new("/"));
+    }
+    let resolved = fs::canonicalize(existing)
+        .await?
+        .join(dst.strip_prefix(existing).unwrap_or(Path::new("")));
+
+    if resolved.starts_with(&src) {
+        return Err(format!("Cannot copy {} into itself ({})", src_dir, dst_dir).into());
+    }
+    Ok(())
 }
 
 /// Upload a directory to S3
@@ -411,41 +1414,150 @@ async fn upload_directory(
     bucket: &str,
     s3_prefix: &str,
     filter: &FileFilter,
-    multipart_threshold: u64,
-    multipart_chunksize: u64,
+    opts: &TransferOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let base_path = Path::new(local_dir);
+    let mut moved_from = BTreeSet::new();
 
-    for entry in WalkDir::new(local_dir).into_iter().filter_map(|e| e.ok()) {
-        let path = entry.path();
+    for entry in walk::walk(Path::new(local_dir), opts.follow_symlinks, filter) {
+        // Apply filters
+        if !filter.matches_local(&entry) {
+            continue;
+        }
+
+        let s3_key = join_s3_key(s3_prefix, &entry.relative);
 
-        if path.is_file() {
-            let relative_path = path
-                .strip_prefix(base_path)
-                .map_err(|e| format!("Path error: {}", e))?;
-            let relative_str = relative_path.to_string_lossy().to_string();
+        upload_file(client, entry.path_str(), bucket, &s3_key, None, None, opts).await?;
+        if remove_walked_source(Path::new(local_dir), &entry.path, opts).await? {
+            moved_from.extend(entry.path.parent().map(Path::to_path_buf));
+        }
+    }
 
-            // Apply filters
-            if !filter.matches(&relative_str) {
+    if opts.dir_markers {
+        for relative in walk::empty_dirs(Path::new(local_dir), opts.follow_symlinks, filter) {
+            if !filter.matches(&relative) {
                 continue;
             }
 
-            let s3_key = join_s3_key(s3_prefix, &relative_str.replace("\\", "/"));
+            let s3_key = join_s3_key(s3_prefix, &relative);
+            upload_dir_marker(client, bucket, &s3_key, &opts.sse).await?;
+            moved_from.insert(Path::new(local_dir).join(&relative));
+        }
+    }
 
-            upload_file(
-                client,
-                path.to_str().unwrap(),
-                bucket,
-                &s3_key,
-                None,
-                None,
-                multipart_threshold,
-                multipart_chunksize,
-            )
-            .await?;
+    if opts.remove_source {
+        prune_empty_dirs(Path::new(local_dir), &moved_from).await;
+    }
+
+    Ok(())
+}
+
+/// Delete a local source after it has been transferred, when moving
+async fn remove_local_source(
+    path: &Path,
+    opts: &TransferOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if opts.remove_source {
+        fs::remove_file(path).await?;
+        println!("Deleted: {}", path.display());
+    }
+    Ok(())
+}
+
+/// Delete a file found by walking `root` once it has been transferred, when
+/// moving; returns whether it was deleted
+///
+/// When following symlinks the walk also descends into symlinked
+/// directories. Their files belong to another tree and are left in place.
+async fn remove_walked_source(
+    root: &Path,
+    path: &Path,
+    opts: &TransferOptions,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    if !opts.remove_source {
+        return Ok(false);
+    }
+    if !path.parent().is_some_and(|dir| is_inside(root, dir)) {
+        eprintln!(
+            "Warning: not deleting {}: it is reached through a symlinked directory",
+            path.display()
+        );
+        return Ok(false);
+    }
+    remove_local_source(path, opts).await?;
+    Ok(true)
+}
+
+/// Whether `dir` (a path below `root`) really is inside `root`, that is
+/// reached without passing through a symlinked directory
+fn is_inside(root: &Path, dir: &Path) -> bool {
+    let (Ok(relative), Ok(real_root), Ok(real_dir)) = (
+        dir.strip_prefix(root),
+        std::fs::canonicalize(root),
+        std::fs::canonicalize(dir),
+    ) else {
+        return false;
+    };
+    real_dir == real_root.join(relative)
+}
+
+/// Remove directories left empty by a move, from the deepest up to `root`
+///
+/// Only `dirs` (directories that files were moved out of) and their parents
+/// are considered, so unrelated empty directories are left alone. Directories
+/// that still have entries are kept, as are directories reached through a
+/// symlink.
+async fn prune_empty_dirs(root: &Path, dirs: &BTreeSet<PathBuf>) {
+    // Reverse order visits subdirectories before their parents
+    for dir in dirs.iter().rev() {
+        let mut dir = dir.as_path();
+        while is_inside(root, dir) && fs::remove_dir(dir).await.is_ok() {
+            println!("Removed directory: {}", dir.display());
+            match dir.parent() {
+                Some(parent) if dir != root => dir = parent,
+                _ => break,
+            }
         }
     }
+}
+
+/// Create a zero-byte directory marker object (key ending in '/')
+pub async fn upload_dir_marker(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+    sse: &SseOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    client
+        .put_object()
+        .bucket(bucket)
+        .key(key)
+        .body(ByteStream::from_static(b""))
+        .set_server_side_encryption(sse.sse.clone())
+        .set_ssekms_key_id(sse.kms_key_id.clone())
+        .sse_customer(sse.customer_key.as_ref())
+        .send()
+        .await?;
+
+    println!("Created directory marker: s3://{}/{}", bucket, key);
+    Ok(())
+}
 
+/// Turn a directory marker into a local directory, or skip it without --dir-markers
+pub async fn download_dir_marker(
+    bucket: &str,
+    key: &str,
+    local_path: &Path,
+    opts: &TransferOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if opts.dir_markers {
+        fs::create_dir_all(local_path).await?;
+        println!(
+            "Created directory: s3://{}/{} -> {}",
+            bucket,
+            key,
+            local_path.display()
+        );
+    }
     Ok(())
 }
 
@@ -456,6 +1568,7 @@ async fn download_directory(
     prefix: &str,
     local_dir: &str,
     filter: &FileFilter,
+    opts: &TransferOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let mut continuation_token: Option<String> = None;
 
@@ -474,19 +1587,31 @@ async fn download_directory(
 
         for obj in response.contents() {
             if let Some(key) = obj.key() {
+                let relative_key = relative_key(key, prefix);
+
                 // Apply filters
-                if !filter.matches(key) {
+                if !filter.matches_object(relative_key, obj) || skip_archived(bucket, obj, opts) {
                     continue;
                 }
 
-                let relative_key = if !prefix.is_empty() && key.starts_with(prefix) {
-                    key[prefix.len()..].trim_start_matches('/')
-                } else {
-                    key
-                };
-
                 let local_path = Path::new(local_dir).join(relative_key);
-                download_file(client, bucket, key, local_path.to_str().unwrap(), None).await?;
+
+                if is_dir_marker(key) {
+                    download_dir_marker(bucket, key, &local_path, opts).await?;
+                    continue;
+                }
+
+                download_file(
+                    client,
+                    bucket,
+                    key,
+                    None,
+                    local_path.to_str().unwrap(),
+                    Path::new(local_dir),
+                    None,
+                    opts,
+                )
+                .await?;
             }
         }
 
@@ -508,6 +1633,7 @@ async fn copy_s3_directory(
     dst_bucket: &str,
     dst_prefix: &str,
     filter: &FileFilter,
+    opts: &TransferOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let mut continuation_token: Option<String> = None;
 
@@ -526,19 +1652,16 @@ async fn copy_s3_directory(
 
         for obj in response.contents() {
             if let Some(key) = obj.key() {
+                let relative_key = relative_key(key, src_prefix);
+
                 // Apply filters
-                if !filter.matches(key) {
+                if !filter.matches_object(relative_key, obj) || skip_archived(src_bucket, obj, opts)
+                {
                     continue;
                 }
 
-                let relative_key = if !src_prefix.is_empty() && key.starts_with(src_prefix) {
-                    key[src_prefix.len()..].trim_start_matches('/')
-                } else {
-                    key
-                };
-
                 let dst_key = join_s3_key(dst_prefix, relative_key);
-                copy_s3_to_s3(client, src_bucket, key, dst_bucket, &dst_key).await?;
+                copy_s3_to_s3(client, src_bucket, key, None, dst_bucket, &dst_key, opts).await?;
             }
         }
 
@@ -551,3 +1674,268 @@ async fn copy_s3_directory(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn move_options() -> TransferOptions {
+        TransferOptions {
+            multipart_threshold: 8 << 20,
+            multipart_chunksize: 8 << 20,
+            preserve: false,
+            follow_symlinks: true,
+            dir_markers: false,
+            remove_source: true,
+            headers: ObjectHeaders::default(),
+            force_glacier_transfer: false,
+            sse: SseOptions::default(),
+            encryption: None,
+            compress: None,
+            compress_suffix: false,
+            literal_keys: false,
+        }
+    }
+
+    /// A client that is never expected to reach a server
+    fn offline_client() -> Client {
+        let config = aws_sdk_s3::Config::builder()
+            .behavior_version_latest()
+            .region(aws_sdk_s3::config::Region::new("us-east-1"))
+            .endpoint_url("http://127.0.0.1:1")
+            .build();
+        Client::from_conf(config)
+    }
+
+    #[tokio::test]
+    async fn test_sync_size_decision() {
+        let client = offline_client();
+        let mut opts = move_options();
+        assert!(same_size(&client, "b", "k", 100, 100, &opts).await.unwrap());
+        // Without client-side encryption a plain object as large as the
+        // encrypted file would be is simply a different size
+        assert!(!same_size(&client, "b", "k", 100, 116, &opts).await.unwrap());
+
+        let key_file = std::env::temp_dir().join(format!("hsc-sync-key-{}", std::process::id()));
+        std::fs::write(&key_file, [7u8; 32]).unwrap();
+        opts.encryption = Some(MasterKey::load(&key_file).unwrap());
+        std::fs::remove_file(&key_file).unwrap();
+
+        // Neither the file's size nor its encrypted size: no need to look
+        assert!(!same_size(&client, "b", "k", 100, 150, &opts).await.unwrap());
+        // A candidate size is only trusted once the metadata is read
+        assert!(same_size(&client, "b", "k", 100, 116, &opts).await.is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_move_leaves_symlinked_directories() {
+        let base = std::env::temp_dir().join(format!("hsc-mv-link-{}", std::process::id()));
+        let root = base.join("root");
+        let outside = base.join("outside");
+        std::fs::create_dir_all(root.join("real")).unwrap();
+        std::fs::create_dir_all(outside.join("sub")).unwrap();
+        std::fs::write(root.join("real/a.txt"), b"a").unwrap();
+        std::fs::write(outside.join("sub/b.txt"), b"b").unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+
+        let opts = move_options();
+        let moved = root.join("real/a.txt");
+        assert!(remove_walked_source(&root, &moved, &opts).await.unwrap());
+        assert!(!moved.exists());
+
+        // Reached through root/link: the real file is outside the tree
+        let linked = root.join("link/sub/b.txt");
+        assert!(!remove_walked_source(&root, &linked, &opts).await.unwrap());
+        assert!(outside.join("sub/b.txt").exists());
+
+        std::fs::remove_file(outside.join("sub/b.txt")).unwrap();
+        let dirs = BTreeSet::from([root.join("real"), root.join("link/sub")]);
+        prune_empty_dirs(&root, &dirs).await;
+        assert!(!root.join("real").exists());
+        assert!(outside.join("sub").is_dir());
+        assert!(root.join("link").exists());
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_check_local_destination() {
+        let base = std::env::temp_dir().join(format!("hsc-cp-dest-{}", std::process::id()));
+        let src = base.join("src");
+        std::fs::create_dir_all(&src).unwrap();
+        let path = |p: &Path| p.to_string_lossy().to_string();
+
+        assert!(
+            check_local_destination(&path(&src), &path(&base.join("copy")))
+                .await
+                .is_ok()
+        );
+        // The destination may not exist yet, or be the source itself
+        let inside = check_local_destination(&path(&src), &path(&src.join("a/b"))).await;
+        assert!(inside.unwrap_err().to_string().contains("into itself"));
+        assert!(check_local_destination(&path(&src), &path(&src))
+            .await
+            .is_err());
+        // "src2" only shares a name prefix with "src"
+        assert!(
+            check_local_destination(&path(&src), &path(&base.join("src2")))
+                .await
+                .is_ok()
+        );
+        assert!(
+            check_local_destination(&path(&base.join("missing")), &path(&base))
+                .await
+                .is_err()
+        );
+
+        #[cfg(unix)]
+        {
+            // Reached through a symlink to the source
+            std::os::unix::fs::symlink(&src, base.join("alias")).unwrap();
+            let aliased = path(&base.join("alias/out"));
+            assert!(check_local_destination(&path(&src), &aliased)
+                .await
+                .is_err());
+        }
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_copy_single_local_file() {
+        let base = std::env::temp_dir().join(format!("hsc-cp-single-{}", std::process::id()));
+        std::fs::create_dir_all(base.join("dir")).unwrap();
+        std::fs::write(base.join("a.txt"), b"a").unwrap();
+        let path = |name: &str| base.join(name).to_string_lossy().to_string();
+        let copy = |src: String, dst: String, opts: TransferOptions| async move {
+            let client = offline_client();
+            copy_single(
+                &client,
+                PathType::Local(src),
+                PathType::Local(dst),
+                None,
+                None,
+                &opts,
+            )
+            .await
+        };
+        let opts = TransferOptions {
+            remove_source: false,
+            ..move_options()
+        };
+
+        // A directory destination receives the file under its own name
+        copy(path("a.txt"), path("dir"), opts.clone())
+            .await
+            .unwrap();
+        copy(path("a.txt"), path("new/"), opts.clone())
+            .await
+            .unwrap();
+        copy(path("a.txt"), path("b.txt"), opts.clone())
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(base.join("dir/a.txt")).unwrap(), b"a");
+        assert_eq!(std::fs::read(base.join("new/a.txt")).unwrap(), b"a");
+        assert_eq!(std::fs::read(base.join("b.txt")).unwrap(), b"a");
+
+        let err = copy(path("dir"), path("other"), opts.clone())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("--recursive"));
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink("a.txt", base.join("link")).unwrap();
+            let links = TransferOptions {
+                follow_symlinks: false,
+                ..opts.clone()
+            };
+            copy(path("link"), path("dir/link"), links).await.unwrap();
+            assert_eq!(
+                std::fs::read_link(base.join("dir/link")).unwrap(),
+                Path::new("a.txt")
+            );
+            copy(path("link"), path("followed"), opts).await.unwrap();
+            assert!(!base.join("followed").is_symlink());
+        }
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_move_single_local_file() {
+        let base = std::env::temp_dir().join(format!("hsc-mv-single-{}", std::process::id()));
+        std::fs::create_dir_all(base.join("dir")).unwrap();
+        std::fs::write(base.join("a.txt"), b"a").unwrap();
+        let path = |name: &str| base.join(name).to_string_lossy().to_string();
+        let opts = move_options();
+        let client = offline_client();
+        let mv = |src: String, dst: String| {
+            copy_single(
+                &client,
+                PathType::Local(src),
+                PathType::Local(dst),
+                None,
+                None,
+                &opts,
+            )
+        };
+
+        // Onto itself, spelled differently
+        let same = format!("{}/./a.txt", base.display());
+        assert!(mv(path("a.txt"), same).await.is_err());
+        assert_eq!(std::fs::read(base.join("a.txt")).unwrap(), b"a");
+
+        mv(path("a.txt"), path("dir")).await.unwrap();
+        assert!(!base.join("a.txt").exists());
+        assert_eq!(std::fs::read(base.join("dir/a.txt")).unwrap(), b"a");
+
+        #[cfg(unix)]
+        {
+            // Hard links share the data: neither may be truncated or deleted
+            std::fs::hard_link(base.join("dir/a.txt"), base.join("h2")).unwrap();
+            assert!(mv(path("dir/a.txt"), path("h2")).await.is_err());
+            assert_eq!(std::fs::read(base.join("dir/a.txt")).unwrap(), b"a");
+            assert_eq!(std::fs::read(base.join("h2")).unwrap(), b"a");
+        }
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_copy_local_directory() {
+        let base = std::env::temp_dir().join(format!("hsc-cp-local-{}", std::process::id()));
+        let src = base.join("src");
+        let dst = base.join("dst");
+        std::fs::create_dir_all(src.join("sub")).unwrap();
+        std::fs::create_dir_all(&dst).unwrap();
+        std::fs::write(src.join("a.txt"), b"new").unwrap();
+        std::fs::write(src.join("sub/b.txt"), b"b").unwrap();
+        std::fs::write(src.join("sub/debug.log"), b"log").unwrap();
+        std::fs::write(dst.join("a.txt"), b"old contents").unwrap();
+
+        let opts = TransferOptions {
+            remove_source: false,
+            ..move_options()
+        };
+        let filter = FileFilter::new(Vec::new(), vec!["*.log".to_string()]).unwrap();
+        let (src_dir, dst_dir) = (src.to_string_lossy(), dst.to_string_lossy());
+        copy_local_directory(&src_dir, &dst_dir, &filter, &opts)
+            .await
+            .unwrap();
+
+        assert_eq!(std::fs::read(dst.join("a.txt")).unwrap(), b"new");
+        assert_eq!(std::fs::read(dst.join("sub/b.txt")).unwrap(), b"b");
+        assert!(!dst.join("sub/debug.log").exists());
+        assert!(src.join("a.txt").exists());
+
+        let nested = src.join("nested").to_string_lossy().to_string();
+        assert!(copy_local_directory(&src_dir, &nested, &filter, &opts)
+            .await
+            .is_err());
+        assert!(!src.join("nested").exists());
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -1,19 +1,33 @@
-use crate::filters::FileFilter;
-use crate::path_utils::{parse_path, PathType};
+use crate::encryption;
+use crate::filters::{FileAttributes, FileFilter};
+use crate::path_utils::{is_dir_marker, parse_path, relative_key, PathType};
+use crate::walk::{self, EntryKind};
 use aws_sdk_s3::Client;
 use md5::{Digest, Md5};
 use std::collections::{HashMap, HashSet};
 use std::path::Path;
 use tokio::fs;
 use tokio::io::AsyncReadExt;
-use walkdir::WalkDir;
 
 #[derive(Debug, Clone)]
-#[allow(dead_code)]
-struct FileInfo {
-    path: String,
-    size: u64,
-    etag: Option<String>,
+pub struct FileInfo {
+    pub path: String,
+    pub size: u64,
+    pub etag: Option<String>,
+    /// Target of a local symlink that is compared as a marker object
+    pub symlink_target: Option<String>,
+}
+
+/// Options controlling how files are collected and compared
+#[derive(Debug, Clone, Copy)]
+pub struct DiffOptions {
+    /// Compare object contents using ETags/MD5 (slower)
+    pub compare_content: bool,
+    pub follow_symlinks: bool,
+    /// Treat empty local directories and `prefix/` marker objects as entries
+    pub dir_markers: bool,
+    /// Objects may be client-side encrypted copies of the files they are compared to
+    pub client_side_encryption: bool,
 }
 
 #[derive(Debug)]
@@ -29,21 +43,18 @@ pub async fn diff(
     client: &Client,
     source: &str,
     dest: &str,
-    compare_content: bool,
-    include: Vec<String>,
-    exclude: Vec<String>,
+    filter: &FileFilter,
+    opts: DiffOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let source_type = parse_path(source)?;
     let dest_type = parse_path(dest)?;
 
-    let filter = FileFilter::new(include, exclude)?;
-
     // Collect file information from both source and dest
-    let source_files = collect_files(client, &source_type, &filter, compare_content).await?;
-    let dest_files = collect_files(client, &dest_type, &filter, compare_content).await?;
+    let source_files = collect_files(client, &source_type, filter, opts).await?;
+    let dest_files = collect_files(client, &dest_type, filter, opts).await?;
 
     // Find differences
-    let differences = find_differences(&source_files, &dest_files, compare_content);
+    let differences = find_differences(&source_files, &dest_files, opts);
 
     // Display results
     display_differences(source, dest, &differences);
@@ -52,17 +63,15 @@ pub async fn diff(
 }
 
 /// Collect files from a path (local or S3)
-async fn collect_files(
+pub async fn collect_files(
     client: &Client,
     path_type: &PathType,
     filter: &FileFilter,
-    calculate_etag: bool,
+    opts: DiffOptions,
 ) -> Result<HashMap<String, FileInfo>, Box<dyn std::error::Error>> {
     match path_type {
-        PathType::S3 { bucket, key } => {
-            collect_s3_files(client, bucket, key, filter, calculate_etag).await
-        }
-        PathType::Local(path) => collect_local_files(path, filter, calculate_etag).await,
+        PathType::S3 { bucket, key } => collect_s3_files(client, bucket, key, filter, opts).await,
+        PathType::Local(path) => collect_local_files(path, filter, opts).await,
     }
 }
 
@@ -72,7 +81,7 @@ async fn collect_s3_files(
     bucket: &str,
     prefix: &str,
     filter: &FileFilter,
-    _calculate_etag: bool,
+    opts: DiffOptions,
 ) -> Result<HashMap<String, FileInfo>, Box<dyn std::error::Error>> {
     let mut files = HashMap::new();
     let mut continuation_token: Option<String> = None;
@@ -93,23 +102,30 @@ async fn collect_s3_files(
         for obj in response.contents() {
             if let Some(key) = obj.key() {
                 // Get relative path (remove prefix)
-                let relative_key = if !prefix.is_empty() && key.starts_with(prefix) {
-                    key[prefix.len()..].trim_start_matches('/')
-                } else {
-                    key
-                };
+                let relative_key = relative_key(key, prefix);
 
-                if relative_key.is_empty() {
+                if is_dir_marker(key) && !opts.dir_markers {
                     continue;
                 }
 
                 // Apply filters
-                if !filter.matches(relative_key) {
+                if !filter.matches_object(relative_key, obj) {
                     continue;
                 }
 
-                let size = obj.size().unwrap_or(0) as u64;
-                let etag = obj.e_tag().map(|s| s.trim_matches('"').to_string());
+                let mut size = obj.size().unwrap_or(0) as u64;
+                let mut etag = obj.e_tag().map(|s| s.trim_matches('"').to_string());
+
+                // Encrypted objects are compared by the plaintext size in
+                // their metadata; their ETag cannot match the file's
+                if opts.client_side_encryption {
+                    let head = client.head_object().bucket(bucket).key(key).send().await?;
+                    let metadata = head.metadata().cloned().unwrap_or_default();
+                    if encryption::is_encrypted(&metadata) {
+                        size = encryption::data_size(&metadata, size).unwrap_or(size);
+                        etag = None;
+                    }
+                }
 
                 files.insert(
                     relative_key.to_string(),
@@ -117,6 +133,7 @@ async fn collect_s3_files(
                         path: key.to_string(),
                         size,
                         etag,
+                        symlink_target: None,
                     },
                 );
             }
@@ -136,8 +153,9 @@ async fn collect_s3_files(
 async fn collect_local_files(
     path: &str,
     filter: &FileFilter,
-    calculate_etag: bool,
+    opts: DiffOptions,
 ) -> Result<HashMap<String, FileInfo>, Box<dyn std::error::Error>> {
+    let calculate_etag = opts.compare_content;
     let mut files = HashMap::new();
     let base_path = Path::new(path);
 
@@ -160,50 +178,69 @@ async fn collect_local_files(
             .unwrap_or("")
             .to_string();
 
-        if filter.matches(&file_name) {
+        if filter.matches_file(&file_name, &FileAttributes::from_metadata(&metadata)) {
             files.insert(
                 file_name.clone(),
                 FileInfo {
                     path: path.to_string(),
                     size: metadata.len(),
                     etag,
+                    symlink_target: None,
                 },
             );
         }
     } else {
         // Directory - walk recursively
-        for entry in WalkDir::new(base_path)
-            .into_iter()
-            .filter_map(|e| e.ok())
-            .filter(|e| e.file_type().is_file())
-        {
-            let full_path = entry.path();
-            let relative_path = full_path
-                .strip_prefix(base_path)
-                .unwrap_or(full_path)
-                .to_string_lossy()
-                .to_string();
-
-            if !filter.matches(&relative_path) {
+        for entry in walk::walk(base_path, opts.follow_symlinks, filter) {
+            if !filter.matches_local(&entry) {
                 continue;
             }
 
-            let metadata = fs::metadata(full_path).await?;
-            let etag = if calculate_etag {
-                calculate_file_etag(full_path).await.ok()
-            } else {
-                None
+            // Symlinks are compared as the marker objects they upload as
+            let (etag, symlink_target) = match &entry.kind {
+                EntryKind::File => {
+                    let etag = if calculate_etag {
+                        calculate_file_etag(&entry.path).await.ok()
+                    } else {
+                        None
+                    };
+                    (etag, None)
+                }
+                EntryKind::Symlink(target) => {
+                    let etag = calculate_etag.then(|| format!("{:x}", Md5::digest(target)));
+                    (etag, Some(target.clone()))
+                }
             };
 
             files.insert(
-                relative_path,
+                entry.relative.clone(),
                 FileInfo {
-                    path: full_path.to_string_lossy().to_string(),
-                    size: metadata.len(),
+                    path: entry.path_str().to_string(),
+                    size: entry.size()?,
                     etag,
+                    symlink_target,
                 },
             );
         }
+
+        // Empty directories are compared as the zero-byte markers they upload as
+        if opts.dir_markers {
+            for relative in walk::empty_dirs(base_path, opts.follow_symlinks, filter) {
+                if !filter.matches(&relative) {
+                    continue;
+                }
+
+                files.insert(
+                    relative.clone(),
+                    FileInfo {
+                        path: base_path.join(&relative).to_string_lossy().to_string(),
+                        size: 0,
+                        etag: calculate_etag.then(|| format!("{:x}", Md5::digest(b""))),
+                        symlink_target: None,
+                    },
+                );
+            }
+        }
     }
 
     Ok(files)
@@ -230,7 +267,7 @@ async fn calculate_file_etag(path: &Path) -> Result<String, Box<dyn std::error::
 fn find_differences(
     source_files: &HashMap<String, FileInfo>,
     dest_files: &HashMap<String, FileInfo>,
-    compare_content: bool,
+    opts: DiffOptions,
 ) -> Vec<(String, DiffType)> {
     let mut differences = Vec::new();
 
@@ -256,7 +293,7 @@ fn find_differences(
                 // Both exist - check if they differ
                 if src.size != dst.size {
                     differences.push((path, DiffType::SizeDiffers));
-                } else if compare_content {
+                } else if opts.compare_content {
                     // Compare ETags if available
                     if let (Some(src_etag), Some(dst_etag)) = (&src.etag, &dst.etag) {
                         if src_etag != dst_etag {
@@ -1,11 +1,20 @@
-use crate::path_utils::{parse_path, PathType};
+use crate::filters::FileFilter;
+use crate::path_utils::{is_dir_marker, parse_path, relative_key, PathType};
+use crate::tagging::TagFilter;
+use crate::wildcard::{self, WildcardPattern};
+use aws_sdk_s3::types::Object;
 use aws_sdk_s3::Client;
 
 /// List S3 buckets or objects
+///
+/// With `--tag`, only objects carrying the given tags are listed.
 pub async fn list(
     client: &Client,
     path: Option<String>,
     recursive: bool,
+    filter: &FileFilter,
+    tags: &TagFilter,
+    literal_keys: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     match path {
         None => {
@@ -15,8 +24,11 @@ pub async fn list(
         Some(path_str) => {
             let path_type = parse_path(&path_str)?;
             match path_type {
+                PathType::S3 { bucket, key } if wildcard::has_wildcard(&key, literal_keys) => {
+                    list_wildcard(client, &bucket, &key, filter, tags).await
+                }
                 PathType::S3 { bucket, key } => {
-                    list_objects(client, &bucket, &key, recursive).await
+                    list_objects(client, &bucket, &key, recursive, filter, tags).await
                 }
                 PathType::Local(_) => {
                     Err("ls command requires S3 URI (s3://bucket[/prefix])".into())
@@ -55,10 +67,11 @@ async fn list_objects(
     bucket: &str,
     prefix: &str,
     recursive: bool,
+    filter: &FileFilter,
+    tags: &TagFilter,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let mut continuation_token: Option<String> = None;
-    let mut total_count = 0;
-    let mut total_size = 0i64;
+    let mut totals = Totals::default();
 
     loop {
         let mut request = client.list_objects_v2().bucket(bucket);
@@ -82,6 +95,10 @@ async fn list_objects(
         if !recursive {
             for common_prefix in response.common_prefixes() {
                 if let Some(prefix_str) = common_prefix.prefix() {
+                    let relative = relative_key(prefix_str, prefix).trim_end_matches('/');
+                    if !filter.includes_dir(relative) {
+                        continue;
+                    }
                     println!("{:>20} {}", "PRE", prefix_str);
                 }
             }
@@ -90,15 +107,12 @@ async fn list_objects(
         // List objects
         for obj in response.contents() {
             if let Some(key) = obj.key() {
-                let size = obj.size().unwrap_or(0);
-                let last_modified = obj
-                    .last_modified()
-                    .map(|d| d.to_string())
-                    .unwrap_or_else(|| "N/A".to_string());
-
-                println!("{:30} {:>12} {}", last_modified, size, key);
-                total_count += 1;
-                total_size += size;
+                if !filter.matches_object(relative_key(key, prefix), obj)
+                    || !tags.matches_object(client, bucket, key).await?
+                {
+                    continue;
+                }
+                print_object(obj, key, &mut totals);
             }
         }
 
@@ -109,9 +123,75 @@ async fn list_objects(
         }
     }
 
-    println!(
-        "\nTotal objects: {}, Total size: {} bytes",
-        total_count, total_size
-    );
+    totals.print();
+    Ok(())
+}
+
+/// List objects whose keys match a wildcard pattern
+async fn list_wildcard(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+    filter: &FileFilter,
+    tags: &TagFilter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let pattern = WildcardPattern::new(key)?;
+    let mut totals = Totals::default();
+
+    for obj in wildcard::list_matching(client, bucket, &pattern).await? {
+        if let Some(key) = obj.key() {
+            if filter.matches_object(pattern.relative(key), &obj)
+                && tags.matches_object(client, bucket, key).await?
+            {
+                print_object(&obj, key, &mut totals);
+            }
+        }
+    }
+
+    totals.print();
     Ok(())
 }
+
+/// Object, directory marker and byte counts of a listing
+#[derive(Default)]
+struct Totals {
+    objects: usize,
+    dirs: usize,
+    size: i64,
+}
+
+impl Totals {
+    fn print(&self) {
+        if self.dirs > 0 {
+            println!(
+                "\nTotal objects: {}, Total directories: {}, Total size: {} bytes",
+                self.objects, self.dirs, self.size
+            );
+        } else {
+            println!(
+                "\nTotal objects: {}, Total size: {} bytes",
+                self.objects, self.size
+            );
+        }
+    }
+}
+
+/// Print one listing line and add the object to the totals
+fn print_object(obj: &Object, key: &str, totals: &mut Totals) {
+    let size = obj.size().unwrap_or(0);
+    let last_modified = obj
+        .last_modified()
+        .map(|d| d.to_string())
+        .unwrap_or_else(|| "N/A".to_string());
+
+    // Directory markers ("folders") are listed as directories
+    if is_dir_marker(key) {
+        println!("{:30} {:>12} {}", last_modified, "DIR", key);
+        totals.dirs += 1;
+        return;
+    }
+
+    println!("{:30} {:>12} {}", last_modified, size, key);
+    totals.objects += 1;
+    totals.size += size;
+}
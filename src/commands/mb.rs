@@ -1,10 +1,33 @@
-use crate::path_utils::{parse_s3_uri, PathType};
+use crate::path_utils::{parse_s3_uri, validate_bucket_name, PathType};
+use crate::tagging::TagSet;
+use aws_sdk_s3::error::ProvideErrorMetadata;
+use aws_sdk_s3::types::{
+    BucketCannedAcl, BucketLocationConstraint, BucketVersioningStatus, CreateBucketConfiguration,
+    VersioningConfiguration,
+};
 use aws_sdk_s3::Client;
 
+/// Settings of a new bucket
+#[derive(Debug, Default)]
+pub struct BucketOptions {
+    /// Enable S3 Object Lock (which also enables versioning)
+    pub object_lock: bool,
+    pub versioning: bool,
+    pub acl: Option<BucketCannedAcl>,
+    /// Succeed without changes if the bucket exists and is ours
+    pub ignore_existing: bool,
+    pub tags: Option<TagSet>,
+}
+
 /// Create an S3 bucket
+///
+/// The bucket is created in the client's region: outside us-east-1 the
+/// region is sent as the LocationConstraint. Versioning and tags are set
+/// after creation.
 pub async fn make_bucket(
     client: &Client,
     bucket_uri: &str,
+    opts: &BucketOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let path = parse_s3_uri(bucket_uri)?;
 
@@ -23,10 +46,61 @@ pub async fn make_bucket(
             return Err("mb command requires S3 URI (s3://bucket-name)".into());
         }
     };
+    validate_bucket_name(&bucket_name)?;
+
+    // us-east-1 is the default location and must not be named
+    let location = client
+        .config()
+        .region()
+        .map(|region| region.as_ref())
+        .filter(|region| *region != "us-east-1")
+        .map(|region| {
+            CreateBucketConfiguration::builder()
+                .location_constraint(BucketLocationConstraint::from(region))
+                .build()
+        });
 
     println!("Creating bucket: {}", bucket_name);
 
-    client.create_bucket().bucket(&bucket_name).send().await?;
+    let result = client
+        .create_bucket()
+        .bucket(&bucket_name)
+        .set_create_bucket_configuration(location)
+        .set_acl(opts.acl.clone())
+        .set_object_lock_enabled_for_bucket(opts.object_lock.then_some(true))
+        .send()
+        .await;
+
+    match result {
+        Ok(_) => {}
+        Err(e) if opts.ignore_existing && e.code() == Some("BucketAlreadyOwnedByYou") => {
+            println!("Bucket already exists: {} (left unchanged)", bucket_name);
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    if opts.versioning {
+        client
+            .put_bucket_versioning()
+            .bucket(&bucket_name)
+            .versioning_configuration(
+                VersioningConfiguration::builder()
+                    .status(BucketVersioningStatus::Enabled)
+                    .build(),
+            )
+            .send()
+            .await?;
+    }
+
+    if let Some(tags) = &opts.tags {
+        client
+            .put_bucket_tagging()
+            .bucket(&bucket_name)
+            .tagging(tags.to_tagging()?)
+            .send()
+            .await?;
+    }
 
     println!("Successfully created bucket: {}", bucket_name);
     Ok(())
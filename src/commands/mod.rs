@@ -1,3 +1,4 @@
+pub mod bucket;
 pub mod cat;
 pub mod cmp;
 pub mod cp;
@@ -5,7 +6,11 @@ pub mod diff;
 pub mod ls;
 pub mod mb;
 pub mod mv;
+pub mod presign;
 pub mod rb;
+pub mod restore;
 pub mod rm;
 pub mod stat;
 pub mod sync;
+pub mod tag;
+pub mod verify;
@@ -1,41 +1,443 @@
-use crate::commands::cp;
-use crate::commands::rm;
+use crate::commands::cp::{self, TransferOptions};
+use crate::filters::FileFilter;
+use crate::path_utils::{
+    is_dir_marker, join_s3_key, parse_path, relative_key, source_destinations, PathType,
+};
+use crate::sse::SseCustomerHeaders;
+use crate::wildcard::{self, WildcardPattern};
+use aws_sdk_s3::error::ProvideErrorMetadata;
 use aws_sdk_s3::Client;
+use futures_util::stream::{self, StreamExt};
+use std::path::{Path, PathBuf};
+
+/// Number of objects moved concurrently
+const MOVE_CONCURRENCY: usize = 8;
 
 /// Move files (copy + delete source)
+///
+/// Each source is copied and then deleted before the next one starts; with
+/// several sources, `dest` is a directory or prefix as for `cp`.
 pub async fn move_files(
+    client: &Client,
+    sources: &[String],
+    dest: &str,
+    recursive: bool,
+    filter: &FileFilter,
+    opts: &TransferOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let pairs = source_destinations(sources, dest, opts.literal_keys)?;
+    let mut failed = 0;
+
+    for (source, dest) in &pairs {
+        if let Err(e) = move_one(client, source, dest, recursive, filter, opts).await {
+            if pairs.len() == 1 {
+                return Err(e);
+            }
+            eprintln!("Error: {}: {}", source, e);
+            failed += 1;
+        }
+    }
+
+    cp::check_failures(failed, pairs.len())
+}
+
+/// Move one source to its destination
+///
+/// Local files are deleted one by one as soon as their own transfer has
+/// succeeded, and directories they leave empty are pruned. S3 sources are
+/// moved object by object (see `move_s3`).
+async fn move_one(
     client: &Client,
     source: &str,
     dest: &str,
     recursive: bool,
-    include: Vec<String>,
-    exclude: Vec<String>,
-    multipart_threshold: u64,
-    multipart_chunksize: u64,
+    filter: &FileFilter,
+    opts: &TransferOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    // First, copy the files
+    if let PathType::S3 { bucket, key } = parse_path(source)? {
+        return move_s3(client, &bucket, &key, dest, recursive, filter, opts).await;
+    }
+
+    let opts = TransferOptions {
+        remove_source: true,
+        ..opts.clone()
+    };
+
     cp::copy(
         client,
-        source,
+        &[source.to_string()],
         dest,
         recursive,
-        include.clone(),
-        exclude.clone(),
+        filter,
         None, // No checksum for move operations
         None,
-        multipart_threshold,
-        multipart_chunksize,
+        &opts,
     )
-    .await?;
+    .await
+}
+
+/// Move S3 objects, pairing each copy with a delete of the same object
+///
+/// The objects to move are listed once up front, so objects created at the
+/// source afterwards are left alone. Each object is deleted only after its
+/// own download or copy succeeded (see `move_object`). Objects are moved
+/// concurrently; failures are reported per object and the move fails at the
+/// end if any object could not be moved.
+async fn move_s3(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+    dest: &str,
+    recursive: bool,
+    filter: &FileFilter,
+    opts: &TransferOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let dest = parse_path(dest)?;
+    let objects = collect_objects(client, bucket, key, recursive, filter, opts).await?;
+
+    let root = symlink_root(&dest, &objects);
+    let moves = move_targets(objects, &dest, opts.dir_markers);
+
+    let total = moves.len();
+    let root = root.as_path();
+    let mut errors: Vec<(String, Box<dyn std::error::Error>)> = stream::iter(moves)
+        .map(|(key, target)| async move {
+            let result = move_object(client, bucket, &key, &target, root, opts).await;
+            result.err().map(|e| (key, e))
+        })
+        .buffer_unordered(MOVE_CONCURRENCY)
+        .filter_map(|error| async move { error })
+        .collect()
+        .await;
+
+    if total == 1 {
+        if let Some((_, e)) = errors.into_iter().next() {
+            return Err(e);
+        }
+        return Ok(());
+    }
+
+    errors.sort_by(|a, b| a.0.cmp(&b.0));
+    for (key, e) in &errors {
+        eprintln!("Error: s3://{}/{}: {}", bucket, key, e);
+    }
+    if !errors.is_empty() {
+        return Err(format!("{} of {} objects could not be moved", errors.len(), total).into());
+    }
+
+    Ok(())
+}
+
+/// Directory that downloaded symlinks must point inside
+fn symlink_root(dest: &PathType, objects: &[(String, Option<String>)]) -> PathBuf {
+    match dest {
+        PathType::Local(dir) if objects.iter().any(|(_, relative)| relative.is_some()) => {
+            PathBuf::from(dir)
+        }
+        PathType::Local(path) => Path::new(path)
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default(),
+        PathType::S3 { .. } => PathBuf::new(),
+    }
+}
+
+/// Pair each listed object with its destination
+///
+/// Directory markers are only transferred to local paths with --dir-markers.
+fn move_targets(
+    objects: Vec<(String, Option<String>)>,
+    dest: &PathType,
+    dir_markers: bool,
+) -> Vec<(String, PathType)> {
+    objects
+        .into_iter()
+        .filter(|(key, _)| {
+            !is_dir_marker(key) || dir_markers || !matches!(dest, PathType::Local(_))
+        })
+        .map(|(key, relative)| {
+            let target = match (dest, relative) {
+                (_, None) => dest.clone(),
+                (PathType::Local(dir), Some(relative)) => {
+                    PathType::Local(Path::new(dir).join(relative).to_string_lossy().to_string())
+                }
+                (PathType::S3 { bucket, key }, Some(relative)) => PathType::S3 {
+                    bucket: bucket.clone(),
+                    key: join_s3_key(key, &relative),
+                },
+            };
+            (key, target)
+        })
+        .collect()
+}
+
+/// List the objects a move applies to, with their paths relative to the source
+///
+/// A single key has no relative path: it is moved to `dest` itself.
+async fn collect_objects(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+    recursive: bool,
+    filter: &FileFilter,
+    opts: &TransferOptions,
+) -> Result<Vec<(String, Option<String>)>, Box<dyn std::error::Error>> {
+    if wildcard::has_wildcard(key, opts.literal_keys) {
+        let pattern = WildcardPattern::new(key)?;
+        let objects = wildcard::list_matching(client, bucket, &pattern).await?;
+        if objects.is_empty() {
+            return Err(format!("No objects match s3://{}/{}", bucket, key).into());
+        }
+
+        return Ok(objects
+            .iter()
+            .filter_map(|obj| {
+                let key = obj.key()?;
+                let relative = pattern.relative(key);
+                (filter.matches_object(relative, obj) && !cp::skip_archived(bucket, obj, opts))
+                    .then(|| (key.to_string(), Some(relative.to_string())))
+            })
+            .collect());
+    }
 
-    // Then, delete the source
-    // Only delete from S3 (moving from local would delete local files)
-    if source.starts_with("s3://") {
-        println!("\nRemoving source files...");
-        rm::remove(client, source, recursive, include, exclude).await?;
-    } else {
-        println!("Note: Source files in local filesystem were not removed");
+    if !recursive {
+        return Ok(vec![(key.to_string(), None)]);
     }
 
+    let mut objects = Vec::new();
+    let mut continuation_token: Option<String> = None;
+
+    loop {
+        let mut request = client.list_objects_v2().bucket(bucket);
+
+        if !key.is_empty() {
+            request = request.prefix(key);
+        }
+
+        if let Some(token) = continuation_token {
+            request = request.continuation_token(token);
+        }
+
+        let response = request.send().await?;
+
+        for obj in response.contents() {
+            if let Some(obj_key) = obj.key() {
+                let relative = relative_key(obj_key, key);
+
+                // Apply filters
+                if !filter.matches_object(relative, obj) || cp::skip_archived(bucket, obj, opts) {
+                    continue;
+                }
+
+                objects.push((obj_key.to_string(), Some(relative.to_string())));
+            }
+        }
+
+        if response.is_truncated() == Some(true) {
+            continuation_token = response.next_continuation_token().map(|s| s.to_string());
+        } else {
+            break;
+        }
+    }
+
+    Ok(objects)
+}
+
+/// Move one object: copy its current version, then delete it if unchanged
+///
+/// The version seen before the copy is the one copied. The source is deleted
+/// only if that version (and ETag) is still current afterwards, so an object
+/// overwritten during the move is reported instead of being lost. The delete
+/// itself is conditional on the verified ETag (If-Match), which closes the
+/// window between that check and the delete; an overwrite with identical
+/// content in between is the only change it cannot see. Deleting by key
+/// leaves a delete marker in versioned buckets rather than making an older
+/// version current again.
+async fn move_object(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+    target: &PathType,
+    root: &Path,
+    opts: &TransferOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Copying an object onto itself keeps its ETag, so the checks below
+    // would pass and the only copy would be deleted
+    if *target
+        == (PathType::S3 {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+        })
+    {
+        return Err("cannot move an object onto itself (use cp to change it in place)".into());
+    }
+
+    // SSE-C sources are read with the copy-source key when copied within S3
+    let customer_key = match target {
+        PathType::S3 { .. } => opts.sse.copy_source_key.as_ref(),
+        PathType::Local(_) => opts.sse.customer_key.as_ref(),
+    };
+    let head = || {
+        client
+            .head_object()
+            .bucket(bucket)
+            .key(key)
+            .sse_customer(customer_key)
+            .send()
+    };
+
+    let before = head().await?;
+    let version_id = before.version_id().filter(|v| *v != "null");
+
+    match target {
+        PathType::Local(path) if is_dir_marker(key) => {
+            cp::download_dir_marker(bucket, key, Path::new(path), opts).await?
+        }
+        PathType::Local(path) => {
+            if !cp::download_file(client, bucket, key, version_id, path, root, None, opts).await? {
+                return Err("symlink was not written, not deleted".into());
+            }
+        }
+        PathType::S3 {
+            bucket: dst_bucket,
+            key: dst_key,
+        } => cp::copy_s3_to_s3(client, bucket, key, version_id, dst_bucket, dst_key, opts).await?,
+    }
+
+    let after = head().await?;
+    if after.version_id() != before.version_id() || after.e_tag() != before.e_tag() {
+        return Err("source changed during the move, not deleted".into());
+    }
+
+    let deleted = client
+        .delete_object()
+        .bucket(bucket)
+        .key(key)
+        .set_if_match(after.e_tag().map(String::from))
+        .send()
+        .await;
+    match deleted {
+        Err(e) if e.code() == Some("PreconditionFailed") => {
+            return Err("source changed during the move, not deleted".into());
+        }
+        deleted => deleted?,
+    };
+
+    println!("Deleted: s3://{}/{}", bucket, key);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listed(entries: &[(&str, Option<&str>)]) -> Vec<(String, Option<String>)> {
+        entries
+            .iter()
+            .map(|(key, relative)| (key.to_string(), relative.map(String::from)))
+            .collect()
+    }
+
+    #[test]
+    fn test_move_targets() {
+        let objects = listed(&[
+            ("logs/a.txt", Some("a.txt")),
+            ("logs/sub/", Some("sub/")),
+            ("logs/sub/b.txt", Some("sub/b.txt")),
+        ]);
+
+        let local = PathType::Local("out".to_string());
+        let moves = move_targets(objects.clone(), &local, false);
+        let targets: Vec<_> = moves
+            .iter()
+            .map(|(key, target)| (key.as_str(), target))
+            .collect();
+        assert_eq!(
+            targets,
+            vec![
+                ("logs/a.txt", &PathType::Local("out/a.txt".to_string())),
+                (
+                    "logs/sub/b.txt",
+                    &PathType::Local("out/sub/b.txt".to_string())
+                ),
+            ]
+        );
+        assert_eq!(move_targets(objects.clone(), &local, true).len(), 3);
+
+        // Markers always move within S3
+        let s3 = PathType::S3 {
+            bucket: "dst".to_string(),
+            key: "archive/".to_string(),
+        };
+        let moves = move_targets(objects, &s3, false);
+        assert_eq!(moves.len(), 3);
+        assert_eq!(
+            moves[2].1,
+            PathType::S3 {
+                bucket: "dst".to_string(),
+                key: "archive/sub/b.txt".to_string(),
+            }
+        );
+
+        // A single key moves to the destination itself
+        let single = move_targets(listed(&[("logs/a.txt", None)]), &local, false);
+        assert_eq!(single, vec![("logs/a.txt".to_string(), local)]);
+    }
+
+    #[tokio::test]
+    async fn test_move_onto_itself() {
+        // Refused before any request is sent
+        let config = aws_sdk_s3::Config::builder()
+            .behavior_version_latest()
+            .build();
+        let client = Client::from_conf(config);
+        let opts = TransferOptions {
+            multipart_threshold: 8 << 20,
+            multipart_chunksize: 8 << 20,
+            preserve: false,
+            follow_symlinks: true,
+            dir_markers: false,
+            remove_source: false,
+            headers: Default::default(),
+            force_glacier_transfer: false,
+            sse: Default::default(),
+            encryption: None,
+            compress: None,
+            compress_suffix: false,
+            literal_keys: false,
+        };
+
+        // An in-place recursive move maps every key onto itself
+        let same = PathType::S3 {
+            bucket: "b".to_string(),
+            key: "logs/".to_string(),
+        };
+        let moves = move_targets(listed(&[("logs/a.txt", Some("a.txt"))]), &same, false);
+        let (key, target) = &moves[0];
+        assert_eq!(
+            target,
+            &PathType::S3 {
+                bucket: "b".to_string(),
+                key: key.clone(),
+            }
+        );
+
+        let result = move_object(&client, "b", key, target, Path::new(""), &opts).await;
+        assert!(result.unwrap_err().to_string().contains("onto itself"));
+    }
+
+    #[test]
+    fn test_symlink_root() {
+        let dir = PathType::Local("out".to_string());
+        let prefix = listed(&[("logs/a.txt", Some("a.txt"))]);
+        assert_eq!(symlink_root(&dir, &prefix), PathBuf::from("out"));
+
+        let file = PathType::Local("out/link".to_string());
+        let single = listed(&[("logs/link", None)]);
+        assert_eq!(symlink_root(&file, &single), PathBuf::from("out"));
+        assert_eq!(
+            symlink_root(&PathType::Local("link".to_string()), &single),
+            PathBuf::new()
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use crate::path_utils::{parse_s3_uri, PathType};
+use crate::presign::{PostPolicy, PresignMethod, SigningParams};
+use crate::s3_client::{self, S3ClientConfig};
+use aws_sdk_s3::presigning::PresigningConfig;
+use aws_sdk_s3::Client;
+use std::time::Duration;
+
+/// Print a presigned GET or PUT URL for an object
+///
+/// The URL is signed locally with the client's credentials, region and
+/// endpoint; anyone holding it can read (or write) the object until it expires.
+pub async fn presign(
+    client: &Client,
+    path: &str,
+    expires_in: Duration,
+    method: PresignMethod,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (bucket, key) = object_path(path)?;
+    let config = PresigningConfig::expires_in(expires_in)?;
+
+    let request = match method {
+        PresignMethod::Get => {
+            client
+                .get_object()
+                .bucket(&bucket)
+                .key(&key)
+                .presigned(config)
+                .await?
+        }
+        PresignMethod::Put => {
+            client
+                .put_object()
+                .bucket(&bucket)
+                .key(&key)
+                .presigned(config)
+                .await?
+        }
+    };
+
+    println!("{}", request.uri());
+    Ok(())
+}
+
+/// Print the URL and form fields of a presigned POST policy as JSON
+///
+/// A path ending in '/' (or a bare bucket) allows uploads of any key below
+/// that prefix; otherwise only the given key can be uploaded.
+pub async fn presign_post(
+    client: &Client,
+    config: &S3ClientConfig,
+    path: &str,
+    expires_in: Duration,
+    content_length_range: Option<(u64, u64)>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (bucket, key) = match parse_s3_uri(path)? {
+        PathType::S3 { bucket, key } => (bucket, key),
+        PathType::Local(_) => {
+            return Err("presign command requires S3 URI (s3://bucket/key)".into());
+        }
+    };
+
+    let credentials = s3_client::load_credentials(config).await?;
+    let region = client
+        .config()
+        .region()
+        .map(|region| region.to_string())
+        .unwrap_or_else(|| "us-east-1".to_string());
+
+    // Custom endpoints are addressed path-style, as by the client
+    let url = match config.endpoint_url() {
+        Some(endpoint) => format!("{}/{}", endpoint.trim_end_matches('/'), bucket),
+        None => format!("https://{}.s3.{}.amazonaws.com/", bucket, region),
+    };
+
+    let policy = PostPolicy {
+        bucket,
+        key_is_prefix: key.is_empty() || key.ends_with('/'),
+        key,
+        content_length_range,
+    };
+    let params = SigningParams {
+        access_key_id: credentials.access_key_id(),
+        secret_access_key: credentials.secret_access_key(),
+        session_token: credentials.session_token(),
+        region: &region,
+        time: chrono::Utc::now(),
+    };
+    let fields: serde_json::Map<String, serde_json::Value> = policy
+        .form_fields(&params, expires_in)?
+        .into_iter()
+        .map(|(name, value)| (name, serde_json::Value::String(value)))
+        .collect();
+
+    let form = serde_json::json!({ "url": url, "fields": fields });
+    println!("{}", serde_json::to_string_pretty(&form)?);
+    Ok(())
+}
+
+/// Bucket and key of a single object
+fn object_path(path: &str) -> Result<(String, String), Box<dyn std::error::Error>> {
+    match parse_s3_uri(path)? {
+        PathType::S3 { key, .. } if key.is_empty() || key.ends_with('/') => {
+            Err("presign requires an object key (s3://bucket/key)".into())
+        }
+        PathType::S3 { bucket, key } => Ok((bucket, key)),
+        PathType::Local(_) => Err("presign command requires S3 URI (s3://bucket/key)".into()),
+    }
+}
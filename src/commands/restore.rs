@@ -0,0 +1,100 @@
+use crate::commands::cp::check_failures;
+use crate::filters::FileFilter;
+use crate::path_utils::{parse_s3_uri, PathType};
+use crate::storage_class;
+use crate::wildcard;
+use aws_sdk_s3::error::ProvideErrorMetadata;
+use aws_sdk_s3::types::{GlacierJobParameters, RestoreRequest, Tier};
+use aws_sdk_s3::Client;
+
+/// Restore archived objects so they can be read
+///
+/// Sends a RestoreObject request for each GLACIER or DEEP_ARCHIVE object;
+/// the restored copy stays readable for `days` days. With `--recursive` or a
+/// wildcard, objects in other storage classes are skipped.
+pub async fn restore(
+    client: &Client,
+    path: &str,
+    recursive: bool,
+    filter: &FileFilter,
+    days: i32,
+    tier: Tier,
+    literal_keys: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (bucket, key) = match parse_s3_uri(path)? {
+        PathType::S3 { bucket, key } => (bucket, key),
+        PathType::Local(_) => {
+            return Err("restore command requires S3 URI (s3://bucket/key)".into());
+        }
+    };
+
+    let request = RestoreRequest::builder()
+        .days(days)
+        .glacier_job_parameters(GlacierJobParameters::builder().tier(tier).build()?)
+        .build();
+
+    if !wildcard::has_wildcard(&key, literal_keys) && !recursive {
+        if key.is_empty() {
+            return Err("Key is required; use --recursive to restore a bucket or prefix".into());
+        }
+        return restore_object(client, &bucket, &key, &request).await;
+    }
+
+    let objects = wildcard::list_objects(client, &bucket, &key, filter, literal_keys).await?;
+    let mut requested = 0;
+    let mut skipped = 0;
+    let mut failed = 0;
+
+    for obj in &objects {
+        let Some(obj_key) = obj.key() else { continue };
+        let class = obj
+            .storage_class()
+            .map(|c| c.as_str())
+            .unwrap_or("STANDARD");
+        if !storage_class::is_archived(class) {
+            skipped += 1;
+            continue;
+        }
+
+        match restore_object(client, &bucket, obj_key, &request).await {
+            Ok(()) => requested += 1,
+            Err(e) => {
+                eprintln!("Error: s3://{}/{}: {}", bucket, obj_key, e);
+                failed += 1;
+            }
+        }
+    }
+
+    println!(
+        "\nRestore: {} requested, {} skipped (not archived)",
+        requested, skipped
+    );
+    check_failures(failed, requested + failed)
+}
+
+/// Request the restore of one object
+///
+/// A restore that is already running is reported, not treated as an error.
+async fn restore_object(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+    request: &RestoreRequest,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let result = client
+        .restore_object()
+        .bucket(bucket)
+        .key(key)
+        .restore_request(request.clone())
+        .send()
+        .await;
+
+    match result {
+        Ok(_) => println!("Restore requested: s3://{}/{}", bucket, key),
+        Err(e) if e.code() == Some("RestoreAlreadyInProgress") => {
+            println!("Restore in progress: s3://{}/{}", bucket, key)
+        }
+        Err(e) => return Err(e.into()),
+    }
+    Ok(())
+}
@@ -1,14 +1,122 @@
+use crate::commands::cp::check_failures;
 use crate::filters::FileFilter;
-use crate::path_utils::{parse_s3_uri, PathType};
+use crate::manifest::ManifestEntry;
+use crate::path_utils::{parse_s3_uri, relative_key, PathType};
+use crate::tagging::TagFilter;
+use crate::wildcard::{self, WildcardPattern};
+use aws_sdk_s3::types::{Delete, ObjectIdentifier};
 use aws_sdk_s3::Client;
+use std::collections::BTreeMap;
+
+/// Maximum number of keys in one DeleteObjects request
+const DELETE_BATCH_SIZE: usize = 1000;
 
 /// Remove S3 objects
+///
+/// Every path is attempted; the command fails afterwards if any of them failed.
+/// With `--tag`, only objects carrying the given tags are removed.
 pub async fn remove(
+    client: &Client,
+    paths: &[String],
+    recursive: bool,
+    filter: &FileFilter,
+    tags: &TagFilter,
+    literal_keys: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut failed = 0;
+
+    for path in paths {
+        if let Err(e) = remove_one(client, path, recursive, filter, tags, literal_keys).await {
+            if paths.len() == 1 {
+                return Err(e);
+            }
+            eprintln!("Error: {}: {}", path, e);
+            failed += 1;
+        }
+    }
+
+    check_failures(failed, paths.len())
+}
+
+/// Remove exactly the objects listed in a `--files-from` manifest
+///
+/// Entries are resolved against the single bucket or prefix in `paths` and
+/// deleted with batched DeleteObjects requests, without listing the bucket.
+pub async fn remove_manifest(
+    client: &Client,
+    paths: &[String],
+    entries: &[ManifestEntry],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let [path] = paths else {
+        return Err("--files-from takes a single bucket or prefix".into());
+    };
+    let base = parse_s3_uri(path)?;
+    if let PathType::Local(_) = base {
+        return Err("rm command requires S3 URI (s3://bucket/prefix)".into());
+    }
+
+    // Group by bucket, since CSV manifests may name several
+    let mut batches: BTreeMap<String, Vec<ObjectIdentifier>> = BTreeMap::new();
+    for entry in entries {
+        if let (PathType::S3 { bucket, key }, _) = entry.resolve(&base) {
+            let object = ObjectIdentifier::builder()
+                .key(key)
+                .set_version_id(entry.version_id.clone())
+                .build()?;
+            batches.entry(bucket).or_default().push(object);
+        }
+    }
+
+    let mut deleted_count = 0;
+    let mut failed = 0;
+
+    for (bucket, objects) in batches {
+        for chunk in objects.chunks(DELETE_BATCH_SIZE) {
+            let delete = Delete::builder()
+                .set_objects(Some(chunk.to_vec()))
+                .quiet(false)
+                .build()?;
+            let response = client
+                .delete_objects()
+                .bucket(&bucket)
+                .delete(delete)
+                .send()
+                .await?;
+
+            for deleted in response.deleted() {
+                let key = deleted.key().unwrap_or_default();
+                match deleted.version_id() {
+                    Some(version_id) => {
+                        println!("Deleted: s3://{}/{} (version {})", bucket, key, version_id)
+                    }
+                    None => println!("Deleted: s3://{}/{}", bucket, key),
+                }
+                deleted_count += 1;
+            }
+            for error in response.errors() {
+                eprintln!(
+                    "Error: s3://{}/{}: {}",
+                    bucket,
+                    error.key().unwrap_or_default(),
+                    error.message().or(error.code()).unwrap_or("delete failed")
+                );
+                failed += 1;
+            }
+        }
+    }
+
+    println!("Total deleted: {} objects", deleted_count);
+    check_failures(failed, entries.len())
+}
+
+/// Remove the objects named by one path
+async fn remove_one(
     client: &Client,
     path: &str,
     recursive: bool,
-    include: Vec<String>,
-    exclude: Vec<String>,
+    filter: &FileFilter,
+    tags: &TagFilter,
+    literal_keys: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let path_type = parse_s3_uri(path)?;
 
@@ -19,11 +127,19 @@ pub async fn remove(
         }
     };
 
-    if recursive {
-        let filter = FileFilter::new(include, exclude)?;
-        remove_recursive(client, &bucket, &key, &filter).await
+    if wildcard::has_wildcard(&key, literal_keys) {
+        if !recursive {
+            return Err(format!(
+                "{} is a wildcard pattern: pass --recursive to remove every matching object, or --no-wildcards to remove the key as written",
+                path
+            )
+            .into());
+        }
+        remove_wildcard(client, &bucket, &key, filter, tags).await
+    } else if recursive {
+        remove_recursive(client, &bucket, &key, filter, tags).await
     } else {
-        remove_single(client, &bucket, &key).await
+        remove_single(client, &bucket, &key, tags).await
     }
 }
 
@@ -32,6 +148,7 @@ async fn remove_single(
     client: &Client,
     bucket: &str,
     key: &str,
+    tags: &TagFilter,
 ) -> Result<(), Box<dyn std::error::Error>> {
     if key.is_empty() {
         return Err(
@@ -39,6 +156,11 @@ async fn remove_single(
         );
     }
 
+    if !tags.matches_object(client, bucket, key).await? {
+        println!("Skipped: s3://{}/{} (tags do not match)", bucket, key);
+        return Ok(());
+    }
+
     client
         .delete_object()
         .bucket(bucket)
@@ -56,6 +178,7 @@ async fn remove_recursive(
     bucket: &str,
     prefix: &str,
     filter: &FileFilter,
+    tags: &TagFilter,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let mut continuation_token: Option<String> = None;
     let mut deleted_count = 0;
@@ -76,7 +199,9 @@ async fn remove_recursive(
         for obj in response.contents() {
             if let Some(key) = obj.key() {
                 // Apply filters
-                if !filter.matches(key) {
+                if !filter.matches_object(relative_key(key, prefix), obj)
+                    || !tags.matches_object(client, bucket, key).await?
+                {
                     continue;
                 }
 
@@ -102,3 +227,38 @@ async fn remove_recursive(
     println!("Total deleted: {} objects", deleted_count);
     Ok(())
 }
+
+/// Remove the objects whose keys match a wildcard pattern
+async fn remove_wildcard(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+    filter: &FileFilter,
+    tags: &TagFilter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let pattern = WildcardPattern::new(key)?;
+    let mut deleted_count = 0;
+
+    for obj in wildcard::list_matching(client, bucket, &pattern).await? {
+        if let Some(key) = obj.key() {
+            if !filter.matches_object(pattern.relative(key), &obj)
+                || !tags.matches_object(client, bucket, key).await?
+            {
+                continue;
+            }
+
+            client
+                .delete_object()
+                .bucket(bucket)
+                .key(key)
+                .send()
+                .await?;
+
+            println!("Deleted: s3://{}/{}", bucket, key);
+            deleted_count += 1;
+        }
+    }
+
+    println!("Total deleted: {} objects", deleted_count);
+    Ok(())
+}
@@ -1,4 +1,15 @@
-use crate::path_utils::{parse_path, PathType};
+use crate::bucket_config::Versioning;
+use crate::commands::bucket;
+use crate::commands::cp::check_failures;
+use crate::encryption;
+use crate::filters::FileFilter;
+use crate::headers;
+use crate::manifest::ManifestEntry;
+use crate::path_utils::{parse_path, relative_key, PathType};
+use crate::sse::{CustomerKey, SseCustomerHeaders};
+use crate::storage_class::RestoreStatus;
+use crate::walk;
+use crate::wildcard::{self, WildcardPattern};
 use aws_sdk_s3::Client;
 use crc32fast::Hasher as Crc32Hasher;
 use md5::{Digest, Md5};
@@ -7,15 +18,19 @@ use sha2::Sha256;
 use std::path::Path;
 use tokio::fs;
 use tokio::io::AsyncReadExt;
-use walkdir::WalkDir;
 
 /// Display information about S3 objects, buckets, or local files
+#[allow(clippy::too_many_arguments)]
 pub async fn stat(
     client: &Client,
     path: &str,
     recursive: bool,
     checksum_mode: Option<String>,
     checksum_algorithm: Option<String>,
+    follow_symlinks: bool,
+    filter: &FileFilter,
+    customer_key: Option<&CustomerKey>,
+    literal_keys: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let path_type = parse_path(path)?;
 
@@ -24,31 +39,98 @@ pub async fn stat(
             if key.is_empty() {
                 if recursive {
                     // Recursive stat all objects in bucket
-                    stat_s3_recursive(client, &bucket, "").await
+                    stat_s3_recursive(client, &bucket, "", filter, customer_key).await
                 } else {
                     // Bucket stat only
                     stat_bucket(client, &bucket).await
                 }
+            } else if wildcard::has_wildcard(&key, literal_keys) {
+                // Every object matching a wildcard pattern
+                stat_s3_wildcard(client, &bucket, &key, filter, customer_key).await
             } else if recursive {
                 // Recursive S3 object stat with prefix
-                stat_s3_recursive(client, &bucket, &key).await
+                stat_s3_recursive(client, &bucket, &key, filter, customer_key).await
             } else {
                 // Single S3 object stat
-                stat_object(client, &bucket, &key).await
+                stat_object(client, &bucket, &key, None, customer_key).await
             }
         }
         PathType::Local(local_path) => {
             if recursive {
                 // Recursive local stat
-                stat_local_recursive(&local_path, checksum_mode, checksum_algorithm).await
+                stat_local_recursive(
+                    &local_path,
+                    checksum_mode,
+                    checksum_algorithm,
+                    follow_symlinks,
+                    filter,
+                )
+                .await
             } else {
                 // Single local file/directory stat
-                stat_local(&local_path, checksum_mode, checksum_algorithm).await
+                stat_local(
+                    &local_path,
+                    checksum_mode,
+                    checksum_algorithm,
+                    follow_symlinks,
+                )
+                .await
             }
         }
     }
 }
 
+/// Display information about exactly the entries of a `--files-from` manifest
+///
+/// Entries are resolved against `path` (a local directory or S3 prefix).
+/// Every entry is attempted before the command fails.
+pub async fn stat_manifest(
+    client: &Client,
+    path: &str,
+    entries: &[ManifestEntry],
+    checksum_mode: Option<String>,
+    checksum_algorithm: Option<String>,
+    follow_symlinks: bool,
+    customer_key: Option<&CustomerKey>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let base = parse_path(path)?;
+    let mut failed = 0;
+
+    for entry in entries {
+        let result = match entry.resolve(&base).0 {
+            PathType::S3 { bucket, key } => {
+                stat_object(
+                    client,
+                    &bucket,
+                    &key,
+                    entry.version_id.as_deref(),
+                    customer_key,
+                )
+                .await
+            }
+            PathType::Local(local_path) => {
+                stat_local(
+                    &local_path,
+                    checksum_mode.clone(),
+                    checksum_algorithm.clone(),
+                    follow_symlinks,
+                )
+                .await
+            }
+        };
+
+        match result {
+            Ok(()) => println!(), // Blank line between entries
+            Err(e) => {
+                eprintln!("Error: {}: {}", entry.key, e);
+                failed += 1;
+            }
+        }
+    }
+
+    check_failures(failed, entries.len())
+}
+
 /// Display S3 bucket information
 async fn stat_bucket(client: &Client, bucket: &str) -> Result<(), Box<dyn std::error::Error>> {
     println!("Name      : {}", bucket);
@@ -74,24 +156,27 @@ async fn stat_bucket(client: &Client, bucket: &str) -> Result<(), Box<dyn std::e
             }
 
             // Get bucket versioning
-            match client.get_bucket_versioning().bucket(bucket).send().await {
-                Ok(versioning) => {
-                    if let Some(status) = versioning.status() {
-                        println!("Versioning: {}", status.as_str());
-                    }
-                }
-                Err(_) => {
-                    // Versioning info not available
-                }
+            if let Ok(Versioning {
+                status: Some(status),
+                ..
+            }) = bucket::get_versioning(client, bucket).await
+            {
+                println!("Versioning: {}", status);
             }
 
-            // Get bucket encryption
-            match client.get_bucket_encryption().bucket(bucket).send().await {
-                Ok(_encryption) => {
-                    println!("Encryption: Enabled");
-                }
-                Err(_) => {
-                    // Encryption not configured or not accessible
+            // Get bucket default encryption
+            if let Ok(Some(encryption)) = bucket::get_encryption(client, bucket).await {
+                let defaults = encryption
+                    .rules
+                    .iter()
+                    .filter_map(|rule| rule.apply_server_side_encryption_by_default.as_ref());
+                for default in defaults {
+                    match &default.kms_master_key_id {
+                        Some(key_id) => {
+                            println!("Encryption: {} ({})", default.sse_algorithm, key_id)
+                        }
+                        None => println!("Encryption: {}", default.sse_algorithm),
+                    }
                 }
             }
 
@@ -134,12 +219,25 @@ async fn stat_object(
     client: &Client,
     bucket: &str,
     key: &str,
+    version_id: Option<&str>,
+    customer_key: Option<&CustomerKey>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let response = client.head_object().bucket(bucket).key(key).send().await?;
+    let response = client
+        .head_object()
+        .bucket(bucket)
+        .key(key)
+        .set_version_id(version_id.map(String::from))
+        .sse_customer(customer_key)
+        .send()
+        .await?;
 
     println!("Name      : s3://{}/{}", bucket, key);
     println!("Type      : file");
 
+    if let Some(version_id) = response.version_id() {
+        println!("Version   : {}", version_id);
+    }
+
     // Size
     if let Some(size) = response.content_length() {
         println!(
@@ -169,6 +267,11 @@ async fn stat_object(
         println!("Storage   : {}", storage_class.as_str());
     }
 
+    // Restore status of archived objects
+    if let Some(status) = response.restore().and_then(RestoreStatus::parse) {
+        println!("Restore   : {}", status);
+    }
+
     // Checksums
     if let Some(checksum) = response.checksum_crc32() {
         println!("CRC32     : {}", checksum);
@@ -187,6 +290,15 @@ async fn stat_object(
     if let Some(sse) = response.server_side_encryption() {
         println!("Encryption: {}", sse.as_str());
     }
+    if let Some(key_id) = response.ssekms_key_id() {
+        println!("KMS Key   : {}", key_id);
+    }
+    if let Some(algorithm) = response.sse_customer_algorithm() {
+        println!("Encryption: SSE-C ({})", algorithm);
+    }
+    if let Some(size) = response.metadata().and_then(encryption::plaintext_size) {
+        println!("Encryption: client-side ({} bytes decrypted)", size);
+    }
 
     // Metadata
     if let Some(metadata) = response.metadata() {
@@ -216,16 +328,18 @@ async fn stat_local(
     path: &str,
     checksum_mode: Option<String>,
     checksum_algorithm: Option<String>,
+    follow_symlinks: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     // Normalize the path by stripping trailing slashes
     let normalized_path = path.trim_end_matches('/');
     let path_obj = Path::new(normalized_path);
 
-    if !path_obj.exists() {
-        return Err(format!("Path '{}' does not exist", normalized_path).into());
+    let metadata = if follow_symlinks {
+        fs::metadata(path_obj).await
+    } else {
+        fs::symlink_metadata(path_obj).await
     }
-
-    let metadata = fs::metadata(path_obj).await?;
+    .map_err(|_| format!("Path '{}' does not exist", normalized_path))?;
 
     println!("Name      : {}", normalized_path);
 
@@ -239,6 +353,11 @@ async fn stat_local(
     };
     println!("Type      : {}", file_type);
 
+    if metadata.is_symlink() {
+        let target = fs::read_link(path_obj).await?;
+        println!("Target    : {}", target.display());
+    }
+
     // Size
     let size = metadata.len();
     println!(
@@ -264,26 +383,12 @@ async fn stat_local(
             println!("ETag      : \"{}\"", etag);
         }
 
-        // Content-Type (basic detection)
-        if let Some(extension) = path_obj.extension() {
-            let content_type = match extension.to_str() {
-                Some("txt") => "text/plain",
-                Some("html") | Some("htm") => "text/html",
-                Some("json") => "application/json",
-                Some("xml") => "application/xml",
-                Some("pdf") => "application/pdf",
-                Some("jpg") | Some("jpeg") => "image/jpeg",
-                Some("png") => "image/png",
-                Some("gif") => "image/gif",
-                Some("zip") => "application/zip",
-                Some("tar") => "application/x-tar",
-                Some("gz") => "application/gzip",
-                _ => "application/octet-stream",
-            };
-            println!("Content   : {}", content_type);
-        } else {
-            println!("Content   : application/octet-stream");
-        }
+        // Content-Type (guessed from the extension, as on upload)
+        let content_type = headers::guess_content_type(path_obj);
+        println!(
+            "Content   : {}",
+            content_type.unwrap_or("application/octet-stream")
+        );
 
         // Calculate checksums if requested
         let calc_checksums = checksum_mode.as_deref() == Some("ENABLED")
@@ -421,6 +526,8 @@ async fn stat_local_recursive(
     path: &str,
     checksum_mode: Option<String>,
     checksum_algorithm: Option<String>,
+    follow_symlinks: bool,
+    filter: &FileFilter,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let path_obj = Path::new(path);
 
@@ -430,22 +537,23 @@ async fn stat_local_recursive(
 
     if !path_obj.is_dir() {
         // Single file
-        return stat_local(path, checksum_mode, checksum_algorithm).await;
+        return stat_local(path, checksum_mode, checksum_algorithm, follow_symlinks).await;
     }
 
     // Walk directory recursively
-    for entry in WalkDir::new(path).into_iter().filter_map(|e| e.ok()) {
-        let entry_path = entry.path();
-
-        if entry_path.is_file() {
-            stat_local(
-                entry_path.to_str().unwrap(),
-                checksum_mode.clone(),
-                checksum_algorithm.clone(),
-            )
-            .await?;
-            println!(); // Blank line between entries
+    for entry in walk::walk(path_obj, follow_symlinks, filter) {
+        if !filter.matches_local(&entry) {
+            continue;
         }
+
+        stat_local(
+            entry.path_str(),
+            checksum_mode.clone(),
+            checksum_algorithm.clone(),
+            follow_symlinks,
+        )
+        .await?;
+        println!(); // Blank line between entries
     }
 
     Ok(())
@@ -456,6 +564,8 @@ async fn stat_s3_recursive(
     client: &Client,
     bucket: &str,
     prefix: &str,
+    filter: &FileFilter,
+    customer_key: Option<&CustomerKey>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let mut continuation_token: Option<String> = None;
 
@@ -474,7 +584,11 @@ async fn stat_s3_recursive(
 
         for obj in response.contents() {
             if let Some(key) = obj.key() {
-                stat_object(client, bucket, key).await?;
+                if !filter.matches_object(relative_key(key, prefix), obj) {
+                    continue;
+                }
+
+                stat_object(client, bucket, key, None, customer_key).await?;
                 println!(); // Blank line between entries
             }
         }
@@ -488,3 +602,30 @@ async fn stat_s3_recursive(
 
     Ok(())
 }
+
+/// Stat S3 objects matching a wildcard pattern
+async fn stat_s3_wildcard(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+    filter: &FileFilter,
+    customer_key: Option<&CustomerKey>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let pattern = WildcardPattern::new(key)?;
+    let objects = wildcard::list_matching(client, bucket, &pattern).await?;
+    if objects.is_empty() {
+        return Err(format!("No objects match s3://{}/{}", bucket, key).into());
+    }
+
+    for obj in &objects {
+        if let Some(key) = obj.key() {
+            if !filter.matches_object(pattern.relative(key), obj) {
+                continue;
+            }
+            stat_object(client, bucket, key, None, customer_key).await?;
+            println!(); // Blank line between entries
+        }
+    }
+
+    Ok(())
+}
@@ -1,40 +1,30 @@
-use crate::filters::FileFilter;
-use crate::path_utils::{join_s3_key, parse_path, PathType};
+use crate::commands::cp::TransferOptions;
+use crate::filters::{FileAttributes, FileFilter};
+use crate::path_utils::{is_dir_marker, join_s3_key, parse_path, relative_key, PathType};
+use crate::walk;
+use aws_sdk_s3::types::Object;
 use aws_sdk_s3::Client;
 use std::collections::HashMap;
 use std::path::Path;
 use tokio::fs;
-use walkdir::WalkDir;
 
 /// Synchronize directories (copy only changed/new files)
 pub async fn sync(
     client: &Client,
     source: &str,
     dest: &str,
-    include: Vec<String>,
-    exclude: Vec<String>,
-    multipart_threshold: u64,
-    multipart_chunksize: u64,
+    filter: &FileFilter,
+    opts: &TransferOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let source_type = parse_path(source)?;
     let dest_type = parse_path(dest)?;
-    let filter = FileFilter::new(include, exclude)?;
 
     match (&source_type, &dest_type) {
         (PathType::Local(src), PathType::S3 { bucket, key }) => {
-            sync_local_to_s3(
-                client,
-                src,
-                bucket,
-                key,
-                &filter,
-                multipart_threshold,
-                multipart_chunksize,
-            )
-            .await
+            sync_local_to_s3(client, src, bucket, key, filter, opts).await
         }
         (PathType::S3 { bucket, key }, PathType::Local(dst)) => {
-            sync_s3_to_local(client, bucket, key, dst, &filter).await
+            sync_s3_to_local(client, bucket, key, dst, filter, opts).await
         }
         (
             PathType::S3 {
@@ -45,9 +35,14 @@ pub async fn sync(
                 bucket: dst_bucket,
                 key: dst_key,
             },
-        ) => sync_s3_to_s3(client, src_bucket, src_key, dst_bucket, dst_key, &filter).await,
-        (PathType::Local(_), PathType::Local(_)) => {
-            Err("Local to local sync not implemented. Use standard 'rsync' command.".into())
+        ) => {
+            sync_s3_to_s3(
+                client, src_bucket, src_key, dst_bucket, dst_key, filter, opts,
+            )
+            .await
+        }
+        (PathType::Local(src), PathType::Local(dst)) => {
+            sync_local_to_local(src, dst, filter, opts).await
         }
     }
 }
@@ -59,58 +54,62 @@ async fn sync_local_to_s3(
     bucket: &str,
     s3_prefix: &str,
     filter: &FileFilter,
-    multipart_threshold: u64,
-    multipart_chunksize: u64,
+    opts: &TransferOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    use crate::commands::cp::upload_file;
+    use crate::commands::cp::{same_size, upload_dir_marker, upload_file};
 
     // Get existing S3 objects with their ETags/sizes
     let s3_objects = get_s3_objects(client, bucket, s3_prefix).await?;
 
-    let base_path = Path::new(local_dir);
     let mut synced_count = 0;
     let mut skipped_count = 0;
 
-    for entry in WalkDir::new(local_dir).into_iter().filter_map(|e| e.ok()) {
-        let path = entry.path();
+    for entry in walk::walk(Path::new(local_dir), opts.follow_symlinks, filter) {
+        // Apply filters
+        if !filter.matches_local(&entry) {
+            continue;
+        }
 
-        if path.is_file() {
-            let relative_path = path
-                .strip_prefix(base_path)
-                .map_err(|e| format!("Path error: {}", e))?;
-            let relative_str = relative_path.to_string_lossy().to_string();
+        let s3_key = join_s3_key(s3_prefix, &entry.relative);
 
-            // Apply filters
-            if !filter.matches(&relative_str) {
-                continue;
+        // Check if file needs to be synced. Compressed objects differ in size
+        // from their files, so with --compress the file must be newer instead
+        let needs_sync = match s3_objects.get(&opts.upload_key(&s3_key)) {
+            Some(obj) if opts.compress.is_some() => {
+                let local = entry.metadata()?.modified().ok();
+                match (local, FileAttributes::from_object(obj).modified) {
+                    (Some(local), Some(remote)) => local > remote,
+                    _ => true,
+                }
             }
+            Some(obj) => {
+                let key = opts.upload_key(&s3_key);
+                let size = obj.size().unwrap_or(0);
+                !same_size(client, bucket, &key, entry.size()?, size, opts).await?
+            }
+            None => true, // File doesn't exist in S3
+        };
 
-            let s3_key = join_s3_key(s3_prefix, &relative_str.replace("\\", "/"));
+        if needs_sync {
+            upload_file(client, entry.path_str(), bucket, &s3_key, None, None, opts).await?;
+            synced_count += 1;
+        } else {
+            skipped_count += 1;
+        }
+    }
 
-            // Check if file needs to be synced
-            let needs_sync = match s3_objects.get(&s3_key) {
-                Some(s3_size) => {
-                    let local_size = fs::metadata(path).await?.len() as i64;
-                    local_size != *s3_size
-                }
-                None => true, // File doesn't exist in S3
-            };
-
-            if needs_sync {
-                upload_file(
-                    client,
-                    path.to_str().unwrap(),
-                    bucket,
-                    &s3_key,
-                    None,
-                    None,
-                    multipart_threshold,
-                    multipart_chunksize,
-                )
-                .await?;
-                synced_count += 1;
-            } else {
+    if opts.dir_markers {
+        for relative in walk::empty_dirs(Path::new(local_dir), opts.follow_symlinks, filter) {
+            if !filter.matches(&relative) {
+                continue;
+            }
+
+            let s3_key = join_s3_key(s3_prefix, &relative);
+            if s3_objects.contains_key(&s3_key) {
                 skipped_count += 1;
+            } else {
+                upload_dir_marker(client, bucket, &s3_key, &opts.sse).await?;
+                synced_count += 1;
             }
         }
     }
@@ -129,8 +128,9 @@ async fn sync_s3_to_local(
     prefix: &str,
     local_dir: &str,
     filter: &FileFilter,
+    opts: &TransferOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    use crate::commands::cp::download_file;
+    use crate::commands::cp::{download_dir_marker, download_file, same_size, skip_archived};
 
     let mut continuation_token: Option<String> = None;
     let mut synced_count = 0;
@@ -151,30 +151,45 @@ async fn sync_s3_to_local(
 
         for obj in response.contents() {
             if let Some(key) = obj.key() {
+                let relative_key = relative_key(key, prefix);
+
                 // Apply filters
-                if !filter.matches(key) {
+                if !filter.matches_object(relative_key, obj) || skip_archived(bucket, obj, opts) {
                     continue;
                 }
 
-                let relative_key = if !prefix.is_empty() && key.starts_with(prefix) {
-                    key[prefix.len()..].trim_start_matches('/')
-                } else {
-                    key
-                };
-
                 let local_path = Path::new(local_dir).join(relative_key);
 
-                // Check if file needs to be synced
-                let needs_sync = if local_path.exists() {
-                    let local_size = fs::metadata(&local_path).await?.len() as i64;
-                    let s3_size = obj.size().unwrap_or(0);
-                    local_size != s3_size
-                } else {
-                    true
+                if is_dir_marker(key) {
+                    if opts.dir_markers && !local_path.is_dir() {
+                        download_dir_marker(bucket, key, &local_path, opts).await?;
+                        synced_count += 1;
+                    }
+                    continue;
+                }
+
+                // Check if file needs to be synced (symlinks are compared
+                // by target length, the size of their marker objects)
+                let needs_sync = match fs::symlink_metadata(&local_path).await {
+                    Ok(metadata) => {
+                        let size = obj.size().unwrap_or(0);
+                        !same_size(client, bucket, key, metadata.len(), size, opts).await?
+                    }
+                    Err(_) => true,
                 };
 
                 if needs_sync {
-                    download_file(client, bucket, key, local_path.to_str().unwrap(), None).await?;
+                    download_file(
+                        client,
+                        bucket,
+                        key,
+                        None,
+                        local_path.to_str().unwrap(),
+                        Path::new(local_dir),
+                        None,
+                        opts,
+                    )
+                    .await?;
                     synced_count += 1;
                 } else {
                     skipped_count += 1;
@@ -204,8 +219,9 @@ async fn sync_s3_to_s3(
     dst_bucket: &str,
     dst_prefix: &str,
     filter: &FileFilter,
+    opts: &TransferOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    use crate::commands::cp::copy_s3_to_s3;
+    use crate::commands::cp::{copy_s3_to_s3, skip_archived};
 
     // Get destination objects
     let dst_objects = get_s3_objects(client, dst_bucket, dst_prefix).await?;
@@ -229,30 +245,25 @@ async fn sync_s3_to_s3(
 
         for obj in response.contents() {
             if let Some(key) = obj.key() {
+                let relative_key = relative_key(key, src_prefix);
+
                 // Apply filters
-                if !filter.matches(key) {
+                if !filter.matches_object(relative_key, obj) || skip_archived(src_bucket, obj, opts)
+                {
                     continue;
                 }
 
-                let relative_key = if !src_prefix.is_empty() && key.starts_with(src_prefix) {
-                    key[src_prefix.len()..].trim_start_matches('/')
-                } else {
-                    key
-                };
-
                 let dst_key = join_s3_key(dst_prefix, relative_key);
 
                 // Check if object needs to be synced
                 let needs_sync = match dst_objects.get(&dst_key) {
-                    Some(dst_size) => {
-                        let src_size = obj.size().unwrap_or(0);
-                        src_size != *dst_size
-                    }
+                    Some(dst) => obj.size() != dst.size(),
                     None => true,
                 };
 
                 if needs_sync {
-                    copy_s3_to_s3(client, src_bucket, key, dst_bucket, &dst_key).await?;
+                    copy_s3_to_s3(client, src_bucket, key, None, dst_bucket, &dst_key, opts)
+                        .await?;
                     synced_count += 1;
                 } else {
                     skipped_count += 1;
@@ -274,12 +285,72 @@ async fn sync_s3_to_s3(
     Ok(())
 }
 
-/// Get all objects in an S3 prefix as a map of key -> size
+/// Sync local directory to another local directory
+async fn sync_local_to_local(
+    src_dir: &str,
+    dst_dir: &str,
+    filter: &FileFilter,
+    opts: &TransferOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use crate::commands::cp::{check_local_destination, copy_local_file};
+
+    check_local_destination(src_dir, dst_dir).await?;
+
+    let mut synced_count = 0;
+    let mut skipped_count = 0;
+
+    for entry in walk::walk(Path::new(src_dir), opts.follow_symlinks, filter) {
+        // Apply filters
+        if !filter.matches_local(&entry) {
+            continue;
+        }
+
+        let local_path = Path::new(dst_dir).join(&entry.relative);
+
+        // Check if file needs to be synced (by size, as for the S3 paths)
+        let needs_sync = match fs::symlink_metadata(&local_path).await {
+            Ok(metadata) => metadata.len() != entry.size()?,
+            Err(_) => true,
+        };
+
+        if needs_sync {
+            copy_local_file(&entry, &local_path, opts).await?;
+            synced_count += 1;
+        } else {
+            skipped_count += 1;
+        }
+    }
+
+    if opts.dir_markers {
+        for relative in walk::empty_dirs(Path::new(src_dir), opts.follow_symlinks, filter) {
+            if !filter.matches(&relative) {
+                continue;
+            }
+
+            let local_path = Path::new(dst_dir).join(&relative);
+            if local_path.is_dir() {
+                skipped_count += 1;
+            } else {
+                fs::create_dir_all(&local_path).await?;
+                println!("Created directory: {}", local_path.display());
+                synced_count += 1;
+            }
+        }
+    }
+
+    println!(
+        "\nSync complete: {} copied, {} skipped (unchanged)",
+        synced_count, skipped_count
+    );
+    Ok(())
+}
+
+/// Get all objects in an S3 prefix as a map of key -> object
 async fn get_s3_objects(
     client: &Client,
     bucket: &str,
     prefix: &str,
-) -> Result<HashMap<String, i64>, Box<dyn std::error::Error>> {
+) -> Result<HashMap<String, Object>, Box<dyn std::error::Error>> {
     let mut objects = HashMap::new();
     let mut continuation_token: Option<String> = None;
 
@@ -298,8 +369,7 @@ async fn get_s3_objects(
 
         for obj in response.contents() {
             if let Some(key) = obj.key() {
-                let size = obj.size().unwrap_or(0);
-                objects.insert(key.to_string(), size);
+                objects.insert(key.to_string(), obj.clone());
             }
         }
 
@@ -0,0 +1,134 @@
+use crate::commands::cp::check_failures;
+use crate::filters::FileFilter;
+use crate::path_utils::{parse_s3_uri, PathType};
+use crate::tagging::{self, TagSet};
+use crate::wildcard;
+use aws_sdk_s3::Client;
+
+/// What `tag` does with each object
+pub enum TagOperation {
+    /// Print the tags
+    Get,
+    /// Replace the tags, or with `merge` add to and overwrite the existing ones
+    Set { tags: TagSet, merge: bool },
+    /// Remove all tags
+    Delete,
+}
+
+/// Get, set or delete the tags of one object, or of every object below a
+/// prefix (`--recursive`) or matching a wildcard
+///
+/// Every listed object is attempted; the command fails afterwards if any
+/// of them failed.
+pub async fn tag(
+    client: &Client,
+    path: &str,
+    recursive: bool,
+    filter: &FileFilter,
+    operation: &TagOperation,
+    literal_keys: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (bucket, key) = match parse_s3_uri(path)? {
+        PathType::S3 { bucket, key } => (bucket, key),
+        PathType::Local(_) => {
+            return Err("tag command requires S3 URI (s3://bucket/key)".into());
+        }
+    };
+
+    if !wildcard::has_wildcard(&key, literal_keys) && !recursive {
+        if key.is_empty() {
+            return Err("Key is required; use --recursive to tag a bucket or prefix".into());
+        }
+        if let TagOperation::Get = operation {
+            let tags = get_tags(client, &bucket, &key).await?;
+            if tags.is_empty() {
+                println!("No tags: s3://{}/{}", bucket, key);
+            }
+            for tag in &tags {
+                println!("{}={}", tag.key(), tag.value());
+            }
+            return Ok(());
+        }
+        return apply(client, &bucket, &key, operation).await;
+    }
+
+    let objects = wildcard::list_objects(client, &bucket, &key, filter, literal_keys).await?;
+    let mut failed = 0;
+
+    for obj in &objects {
+        let Some(obj_key) = obj.key() else { continue };
+
+        let result = match operation {
+            TagOperation::Get => get_tags(client, &bucket, obj_key).await.map(|tags| {
+                println!(
+                    "s3://{}/{}\t{}",
+                    bucket,
+                    obj_key,
+                    tagging::format_tags(&tags)
+                )
+            }),
+            _ => apply(client, &bucket, obj_key, operation).await,
+        };
+        if let Err(e) = result {
+            eprintln!("Error: s3://{}/{}: {}", bucket, obj_key, e);
+            failed += 1;
+        }
+    }
+
+    if !matches!(operation, TagOperation::Get) {
+        println!("\nTotal tagged: {} objects", objects.len() - failed);
+    }
+    check_failures(failed, objects.len())
+}
+
+/// Tags of one object
+async fn get_tags(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+) -> Result<Vec<aws_sdk_s3::types::Tag>, Box<dyn std::error::Error>> {
+    let response = client
+        .get_object_tagging()
+        .bucket(bucket)
+        .key(key)
+        .send()
+        .await?;
+    Ok(response.tag_set().to_vec())
+}
+
+/// Set or delete the tags of one object
+async fn apply(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+    operation: &TagOperation,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match operation {
+        TagOperation::Get => unreachable!("tags are read by the caller"),
+        TagOperation::Set { tags, merge } => {
+            let tags = if *merge {
+                tags.merged_over(&get_tags(client, bucket, key).await?)
+            } else {
+                tags.clone()
+            };
+            client
+                .put_object_tagging()
+                .bucket(bucket)
+                .key(key)
+                .tagging(tags.to_tagging()?)
+                .send()
+                .await?;
+            println!("Tagged: s3://{}/{}", bucket, key);
+        }
+        TagOperation::Delete => {
+            client
+                .delete_object_tagging()
+                .bucket(bucket)
+                .key(key)
+                .send()
+                .await?;
+            println!("Untagged: s3://{}/{}", bucket, key);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,274 @@
+use crate::checksum::{self, Algorithm};
+use crate::commands::diff::{collect_files, DiffOptions, FileInfo};
+use crate::filters::FileFilter;
+use crate::path_utils::{parse_path, PathType};
+use aws_sdk_s3::operation::head_object::HeadObjectOutput;
+use aws_sdk_s3::types::{ChecksumMode, ChecksumType};
+use aws_sdk_s3::Client;
+use futures_util::stream::{self, StreamExt};
+use std::io::Cursor;
+use std::path::PathBuf;
+
+/// Number of files checksummed concurrently
+const VERIFY_CONCURRENCY: usize = 8;
+
+#[derive(Debug)]
+enum Outcome {
+    Passed(Algorithm),
+    Failed(String),
+    MissingInS3,
+}
+
+/// Verify a local tree against an S3 prefix by content
+pub async fn verify(
+    client: &Client,
+    local: &str,
+    remote: &str,
+    filter: &FileFilter,
+    follow_symlinks: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let local_type = parse_path(local)?;
+    let remote_type = parse_path(remote)?;
+
+    let bucket = match (&local_type, &remote_type) {
+        (PathType::Local(_), PathType::S3 { bucket, .. }) => bucket.clone(),
+        _ => {
+            return Err(
+                "verify command requires a local path and an S3 URI (s3://bucket/prefix)".into(),
+            )
+        }
+    };
+
+    let collect_opts = DiffOptions {
+        compare_content: false,
+        follow_symlinks,
+        dir_markers: false,
+        client_side_encryption: false,
+    };
+
+    let local_files = collect_files(client, &local_type, filter, collect_opts).await?;
+    let remote_files = collect_files(client, &remote_type, filter, collect_opts).await?;
+
+    let mut paths: Vec<&String> = local_files.keys().collect();
+    paths.sort();
+
+    let mut results: Vec<(String, Outcome)> = stream::iter(paths)
+        .map(|path| {
+            let local_info = &local_files[path];
+            let remote_info = remote_files.get(path);
+            let bucket = &bucket;
+            async move {
+                let outcome = match remote_info {
+                    Some(remote_info) => verify_file(client, bucket, local_info, remote_info)
+                        .await
+                        .unwrap_or_else(|e| Outcome::Failed(e.to_string())),
+                    None => Outcome::MissingInS3,
+                };
+                (path.clone(), outcome)
+            }
+        })
+        .buffer_unordered(VERIFY_CONCURRENCY)
+        .collect()
+        .await;
+
+    results.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut only_remote: Vec<&String> = remote_files
+        .keys()
+        .filter(|path| !local_files.contains_key(*path))
+        .collect();
+    only_remote.sort();
+
+    let failures = display_report(local, remote, &results, &only_remote);
+
+    if failures > 0 {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Verify a single local file against its S3 object
+async fn verify_file(
+    client: &Client,
+    bucket: &str,
+    local: &FileInfo,
+    remote: &FileInfo,
+) -> Result<Outcome, Box<dyn std::error::Error>> {
+    if local.size != remote.size {
+        return Ok(Outcome::Failed(format!(
+            "size mismatch (local {} bytes, S3 {} bytes)",
+            local.size, remote.size
+        )));
+    }
+
+    // A plain ETag from the listing is the MD5 of the object for unencrypted
+    // and SSE-S3 single-part uploads, so no extra request is needed
+    let mut local_md5 = None;
+    if let Some(etag) = &remote.etag {
+        if let (value, None) = checksum::split_part_count(etag) {
+            let local_etag = local_checksum(local, Algorithm::Md5, None).await?;
+            if local_etag == value {
+                return Ok(Outcome::Passed(Algorithm::Md5));
+            }
+            local_md5 = Some(local_etag);
+        }
+    }
+
+    let head = client
+        .head_object()
+        .bucket(bucket)
+        .key(&remote.path)
+        .checksum_mode(ChecksumMode::Enabled)
+        .send()
+        .await?;
+
+    let (algorithm, expected) = match stored_checksum(&head) {
+        Some(stored) => stored,
+        None => match head.e_tag() {
+            Some(etag) => (Algorithm::Md5, etag.trim_matches('"').to_string()),
+            None => return Ok(Outcome::Failed("no checksum or ETag available".to_string())),
+        },
+    };
+
+    let composite = head.checksum_type() == Some(&ChecksumType::Composite)
+        || (algorithm == Algorithm::Md5 && checksum::split_part_count(&expected).1.is_some());
+
+    let part_size = if composite {
+        Some(first_part_size(client, bucket, &remote.path).await?)
+    } else {
+        None
+    };
+
+    let actual = match local_md5 {
+        Some(md5) if algorithm == Algorithm::Md5 && part_size.is_none() => md5,
+        _ => local_checksum(local, algorithm, part_size).await?,
+    };
+
+    if actual == expected {
+        Ok(Outcome::Passed(algorithm))
+    } else {
+        Ok(Outcome::Failed(format!(
+            "{} mismatch (local {}, S3 {})",
+            algorithm.name(),
+            actual,
+            expected
+        )))
+    }
+}
+
+/// Pick the strongest checksum stored with the object
+fn stored_checksum(head: &HeadObjectOutput) -> Option<(Algorithm, String)> {
+    let candidates = [
+        (Algorithm::Sha256, head.checksum_sha256()),
+        (Algorithm::Sha1, head.checksum_sha1()),
+        (Algorithm::Crc64Nvme, head.checksum_crc64_nvme()),
+        (Algorithm::Crc32C, head.checksum_crc32_c()),
+        (Algorithm::Crc32, head.checksum_crc32()),
+    ];
+
+    candidates
+        .into_iter()
+        .find_map(|(algorithm, value)| value.map(|v| (algorithm, v.to_string())))
+}
+
+/// Get the size of the first part of a multipart object
+async fn first_part_size(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+) -> Result<u64, Box<dyn std::error::Error>> {
+    let head = client
+        .head_object()
+        .bucket(bucket)
+        .key(key)
+        .part_number(1)
+        .send()
+        .await?;
+
+    head.content_length()
+        .map(|len| len as u64)
+        .ok_or_else(|| format!("Cannot determine part size of s3://{}/{}", bucket, key).into())
+}
+
+/// Checksum a local file on the blocking thread pool
+///
+/// Symlinks not being followed are checksummed as their marker content.
+async fn local_checksum(
+    local: &FileInfo,
+    algorithm: Algorithm,
+    part_size: Option<u64>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    if let Some(target) = &local.symlink_target {
+        let mut reader = Cursor::new(target.as_bytes());
+        return Ok(checksum::checksum_reader(
+            &mut reader,
+            algorithm,
+            part_size,
+        )?);
+    }
+
+    let path = PathBuf::from(&local.path);
+    let checksum =
+        tokio::task::spawn_blocking(move || checksum::file_checksum(&path, algorithm, part_size))
+            .await??;
+    Ok(checksum)
+}
+
+/// Print the verification report and return the number of failures
+fn display_report(
+    local: &str,
+    remote: &str,
+    results: &[(String, Outcome)],
+    only_remote: &[&String],
+) -> usize {
+    println!("Verifying:");
+    println!("  Local: {}", local);
+    println!("  S3:    {}", remote);
+    println!();
+
+    let mut passed = 0;
+    let mut failed = 0;
+    let mut missing = 0;
+
+    for (path, outcome) in results {
+        match outcome {
+            Outcome::Passed(algorithm) => {
+                println!("  OK      {} ({})", path, algorithm.name());
+                passed += 1;
+            }
+            Outcome::Failed(reason) => {
+                println!("  FAILED  {}: {}", path, reason);
+                failed += 1;
+            }
+            Outcome::MissingInS3 => {
+                println!("  MISSING {}", path);
+                missing += 1;
+            }
+        }
+    }
+
+    if !only_remote.is_empty() {
+        println!();
+        println!("Only in S3 ({} files):", only_remote.len());
+        for path in only_remote {
+            println!("  - {}", path);
+        }
+    }
+
+    println!();
+    println!("Summary:");
+    println!("  Passed:        {}", passed);
+    println!("  Failed:        {}", failed);
+    println!("  Missing in S3: {}", missing);
+    println!("  Only in S3:    {}", only_remote.len());
+
+    let failures = failed + missing;
+    if failures == 0 {
+        println!("Result: PASS");
+    } else {
+        println!("Result: FAIL");
+    }
+
+    failures
+}
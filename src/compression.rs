@@ -0,0 +1,198 @@
+use std::io::{self, Write};
+
+/// Compression formats: gzip and zstd for uploads, plus bzip2 for reading
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Codec {
+    Gzip,
+    Zstd,
+    Bzip2,
+}
+
+impl Codec {
+    /// `Content-Encoding` of objects compressed with this codec
+    pub fn content_encoding(self) -> &'static str {
+        match self {
+            Codec::Gzip => "gzip",
+            Codec::Zstd => "zstd",
+            Codec::Bzip2 => "bzip2",
+        }
+    }
+
+    /// Key and file name suffix of this codec
+    pub fn extension(self) -> &'static str {
+        match self {
+            Codec::Gzip => ".gz",
+            Codec::Zstd => ".zst",
+            Codec::Bzip2 => ".bz2",
+        }
+    }
+
+    /// Codec named by a `Content-Encoding` header, if hsc can decode it
+    pub fn from_content_encoding(encoding: &str) -> Option<Codec> {
+        match encoding.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => Some(Codec::Gzip),
+            "zstd" => Some(Codec::Zstd),
+            "bzip2" | "x-bzip2" => Some(Codec::Bzip2),
+            _ => None,
+        }
+    }
+
+    /// Codec suggested by a key or file name suffix
+    pub fn from_name(name: &str) -> Option<Codec> {
+        [Codec::Gzip, Codec::Zstd, Codec::Bzip2]
+            .into_iter()
+            .find(|codec| name.ends_with(codec.extension()))
+    }
+}
+
+/// Parse `--compress gzip|zstd`
+pub fn parse_compress(value: &str) -> Result<Codec, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "gzip" | "gz" => Ok(Codec::Gzip),
+        "zstd" | "zst" => Ok(Codec::Zstd),
+        _ => Err(format!("Invalid compression: {}. Use gzip or zstd", value)),
+    }
+}
+
+/// Compresses a stream that is fed in pieces
+pub enum Compressor {
+    Gzip(flate2::write::GzEncoder<Vec<u8>>),
+    Zstd(zstd::stream::write::Encoder<'static, Vec<u8>>),
+}
+
+impl Compressor {
+    pub fn new(codec: Codec) -> io::Result<Compressor> {
+        match codec {
+            Codec::Gzip => Ok(Compressor::Gzip(flate2::write::GzEncoder::new(
+                Vec::new(),
+                flate2::Compression::default(),
+            ))),
+            Codec::Zstd => Ok(Compressor::Zstd(zstd::stream::write::Encoder::new(
+                Vec::new(),
+                zstd::DEFAULT_COMPRESSION_LEVEL,
+            )?)),
+            Codec::Bzip2 => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "bzip2 compression is not supported",
+            )),
+        }
+    }
+
+    /// Add input; returns the compressed output produced so far
+    pub fn update(&mut self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Compressor::Gzip(encoder) => {
+                encoder.write_all(data)?;
+                Ok(std::mem::take(encoder.get_mut()))
+            }
+            Compressor::Zstd(encoder) => {
+                encoder.write_all(data)?;
+                Ok(std::mem::take(encoder.get_mut()))
+            }
+        }
+    }
+
+    /// End the stream and return the remaining output
+    pub fn finish(self) -> io::Result<Vec<u8>> {
+        match self {
+            Compressor::Gzip(encoder) => encoder.finish(),
+            Compressor::Zstd(encoder) => encoder.finish(),
+        }
+    }
+}
+
+/// Decompresses a stream that arrives in pieces
+pub enum Decompressor {
+    Gzip(flate2::write::MultiGzDecoder<Vec<u8>>),
+    Zstd(zstd::stream::write::Decoder<'static, Vec<u8>>),
+    Bzip2(bzip2::write::BzDecoder<Vec<u8>>),
+}
+
+impl Decompressor {
+    pub fn new(codec: Codec) -> io::Result<Decompressor> {
+        Ok(match codec {
+            Codec::Gzip => Decompressor::Gzip(flate2::write::MultiGzDecoder::new(Vec::new())),
+            Codec::Zstd => Decompressor::Zstd(zstd::stream::write::Decoder::new(Vec::new())?),
+            Codec::Bzip2 => Decompressor::Bzip2(bzip2::write::BzDecoder::new(Vec::new())),
+        })
+    }
+
+    /// Add compressed input; returns the output decompressed so far
+    pub fn update(&mut self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Decompressor::Gzip(decoder) => {
+                decoder.write_all(data)?;
+                Ok(std::mem::take(decoder.get_mut()))
+            }
+            Decompressor::Zstd(decoder) => {
+                decoder.write_all(data)?;
+                Ok(std::mem::take(decoder.get_mut()))
+            }
+            Decompressor::Bzip2(decoder) => {
+                decoder.write_all(data)?;
+                Ok(std::mem::take(decoder.get_mut()))
+            }
+        }
+    }
+
+    /// End the stream and return the remaining output
+    pub fn finish(self) -> io::Result<Vec<u8>> {
+        match self {
+            Decompressor::Gzip(decoder) => decoder.finish(),
+            Decompressor::Zstd(mut decoder) => {
+                decoder.flush()?;
+                Ok(decoder.into_inner())
+            }
+            Decompressor::Bzip2(mut decoder) => decoder.finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codec_names() {
+        assert_eq!(parse_compress("gzip").unwrap(), Codec::Gzip);
+        assert_eq!(parse_compress("ZSTD").unwrap(), Codec::Zstd);
+        assert!(parse_compress("bzip2").is_err());
+
+        assert_eq!(Codec::from_content_encoding("x-gzip"), Some(Codec::Gzip));
+        assert_eq!(Codec::from_content_encoding("br"), None);
+        assert_eq!(Codec::from_name("logs/app.log.zst"), Some(Codec::Zstd));
+        assert_eq!(Codec::from_name("archive.bz2"), Some(Codec::Bzip2));
+        assert_eq!(Codec::from_name("notes.txt"), None);
+    }
+
+    #[test]
+    fn test_round_trip_in_pieces() {
+        let data: Vec<u8> = (0..200_000).map(|i| (i % 97) as u8).collect();
+
+        for codec in [Codec::Gzip, Codec::Zstd] {
+            let mut compressor = Compressor::new(codec).unwrap();
+            let mut compressed = Vec::new();
+            for piece in data.chunks(7000) {
+                compressed.extend(compressor.update(piece).unwrap());
+            }
+            compressed.extend(compressor.finish().unwrap());
+            assert!(compressed.len() < data.len());
+
+            let mut decompressor = Decompressor::new(codec).unwrap();
+            let mut decompressed = Vec::new();
+            for piece in compressed.chunks(1000) {
+                decompressed.extend(decompressor.update(piece).unwrap());
+            }
+            decompressed.extend(decompressor.finish().unwrap());
+            assert_eq!(decompressed, data, "{:?}", codec);
+        }
+
+        let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::fast());
+        encoder.write_all(&data).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let mut decompressor = Decompressor::new(Codec::Bzip2).unwrap();
+        let mut decompressed = decompressor.update(&compressed).unwrap();
+        decompressed.extend(decompressor.finish().unwrap());
+        assert_eq!(decompressed, data);
+    }
+}
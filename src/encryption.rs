@@ -0,0 +1,378 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Plaintext bytes per encrypted chunk
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Bytes the GCM tag adds to each chunk
+const TAG_SIZE: usize = 16;
+
+/// Metadata naming the format of client-side encrypted objects
+const META_VERSION: &str = "hsc-cse";
+/// AES-256-GCM, `CHUNK_SIZE` chunks, nonces and AAD as in `Envelope::payload`
+const VERSION: &str = "v1";
+/// Data key wrapped by the master key: base64 of nonce and ciphertext
+const META_KEY: &str = "hsc-cse-key";
+/// Base nonce of the data chunks, base64
+const META_IV: &str = "hsc-cse-iv";
+/// Size of the plaintext
+const META_SIZE: &str = "hsc-cse-size";
+
+/// Master key that wraps the per-object data keys
+#[derive(Clone)]
+pub struct MasterKey(Key<Aes256Gcm>);
+
+impl MasterKey {
+    /// Load a key file: 32 raw bytes, or the key base64 encoded on one line
+    pub fn load(path: &Path) -> Result<MasterKey, String> {
+        let content = std::fs::read(path)
+            .map_err(|e| format!("Cannot read encryption key {}: {}", path.display(), e))?;
+        let key = if content.len() == 32 {
+            content
+        } else {
+            let text = String::from_utf8_lossy(&content);
+            BASE64.decode(text.trim()).map_err(|_| {
+                format!(
+                    "Encryption key {} must hold 32 raw bytes or a base64 encoded 256-bit key",
+                    path.display()
+                )
+            })?
+        };
+        if key.len() != 32 {
+            return Err(format!(
+                "Encryption key {} is {} bytes, expected 32",
+                path.display(),
+                key.len()
+            ));
+        }
+        Ok(MasterKey(*Key::<Aes256Gcm>::from_slice(&key)))
+    }
+}
+
+// Never print key material, e.g. in --debug output
+impl std::fmt::Debug for MasterKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MasterKey")
+    }
+}
+
+/// Per-object data key and base nonce of a client-side encrypted object
+///
+/// The plaintext is split into `CHUNK_SIZE` chunks that are encrypted
+/// separately, so uploads and downloads can stream; each chunk carries its
+/// index and whether it is the last one, which detects reordered or truncated
+/// objects. The data key is stored in the object's metadata, wrapped by the
+/// master key.
+pub struct Envelope {
+    cipher: Aes256Gcm,
+    data_key: Key<Aes256Gcm>,
+    iv: [u8; 12],
+}
+
+impl Envelope {
+    /// A fresh envelope with a random data key, for a new object
+    pub fn generate() -> Envelope {
+        let data_key = Aes256Gcm::generate_key(OsRng);
+        Envelope {
+            cipher: Aes256Gcm::new(&data_key),
+            data_key,
+            iv: Aes256Gcm::generate_nonce(OsRng).into(),
+        }
+    }
+
+    /// Object metadata describing the envelope, to store with the ciphertext
+    pub fn to_metadata(&self, master: &MasterKey, plaintext_size: u64) -> HashMap<String, String> {
+        let nonce = Aes256Gcm::generate_nonce(OsRng);
+        let wrapped = Aes256Gcm::new(&master.0)
+            .encrypt(&nonce, self.data_key.as_slice())
+            .expect("AES-GCM encryption of a data key cannot fail");
+
+        let mut key = nonce.to_vec();
+        key.extend(wrapped);
+
+        HashMap::from([
+            (META_VERSION.to_string(), VERSION.to_string()),
+            (META_KEY.to_string(), BASE64.encode(key)),
+            (META_IV.to_string(), BASE64.encode(self.iv)),
+            (META_SIZE.to_string(), plaintext_size.to_string()),
+        ])
+    }
+
+    /// Read the envelope of an object; `None` if it is not client-side encrypted
+    pub fn from_metadata(
+        master: &MasterKey,
+        metadata: &HashMap<String, String>,
+    ) -> Result<Option<Envelope>, String> {
+        match metadata.get(META_VERSION).map(String::as_str) {
+            None => return Ok(None),
+            Some(VERSION) => {}
+            Some(other) => return Err(format!("unsupported client-side encryption {}", other)),
+        }
+
+        let field = |name: &str| {
+            metadata
+                .get(name)
+                .and_then(|value| BASE64.decode(value).ok())
+                .ok_or_else(|| format!("encryption metadata {} is missing or invalid", name))
+        };
+        let key = field(META_KEY)?;
+        let iv: [u8; 12] = field(META_IV)?
+            .try_into()
+            .map_err(|_| format!("encryption metadata {} is invalid", META_IV))?;
+
+        if key.len() < 12 {
+            return Err(format!("encryption metadata {} is invalid", META_KEY));
+        }
+        let (nonce, wrapped) = key.split_at(12);
+        let data_key = Aes256Gcm::new(&master.0)
+            .decrypt(Nonce::from_slice(nonce), wrapped)
+            .map_err(|_| "cannot unwrap the data key: wrong encryption key".to_string())?;
+        if data_key.len() != 32 {
+            return Err("cannot unwrap the data key: wrong encryption key".to_string());
+        }
+        let data_key = *Key::<Aes256Gcm>::from_slice(&data_key);
+
+        Ok(Some(Envelope {
+            cipher: Aes256Gcm::new(&data_key),
+            data_key,
+            iv,
+        }))
+    }
+
+    /// Encrypt `data`, which starts at chunk `first_index` of the plaintext
+    ///
+    /// `data` must be a whole number of chunks unless it ends the plaintext
+    /// (`last`), as with multipart parts that are multiples of `CHUNK_SIZE`.
+    pub fn encrypt(&self, first_index: u64, data: &[u8], last: bool) -> Vec<u8> {
+        if data.is_empty() {
+            return if last {
+                self.encrypt_chunk(first_index, true, &[])
+            } else {
+                Vec::new()
+            };
+        }
+
+        let count = data.len().div_ceil(CHUNK_SIZE);
+        let mut ciphertext = Vec::with_capacity(data.len() + count * TAG_SIZE);
+        for (i, chunk) in data.chunks(CHUNK_SIZE).enumerate() {
+            let index = first_index + i as u64;
+            ciphertext.extend(self.encrypt_chunk(index, last && i + 1 == count, chunk));
+        }
+        ciphertext
+    }
+
+    /// Encrypt chunk `index` of the plaintext (at most `CHUNK_SIZE` bytes)
+    pub fn encrypt_chunk(&self, index: u64, last: bool, chunk: &[u8]) -> Vec<u8> {
+        let (nonce, aad) = self.payload(index, last);
+        self.cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: chunk,
+                    aad: &aad,
+                },
+            )
+            .expect("AES-GCM encryption cannot fail")
+    }
+
+    /// Decrypt chunk `index` of the ciphertext
+    pub fn decrypt_chunk(&self, index: u64, last: bool, chunk: &[u8]) -> Result<Vec<u8>, String> {
+        let (nonce, aad) = self.payload(index, last);
+        self.cipher
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: chunk,
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| {
+                format!(
+                    "decryption of chunk {} failed: object is corrupt or truncated",
+                    index
+                )
+            })
+    }
+
+    /// Nonce (base nonce XOR chunk index) and AAD (index and last-chunk flag)
+    fn payload(&self, index: u64, last: bool) -> ([u8; 12], [u8; 9]) {
+        let mut nonce = self.iv;
+        for (byte, counter) in nonce[4..].iter_mut().zip(index.to_be_bytes()) {
+            *byte ^= counter;
+        }
+        let mut aad = [0u8; 9];
+        aad[..8].copy_from_slice(&index.to_be_bytes());
+        aad[8] = last as u8;
+        (nonce, aad)
+    }
+}
+
+/// Whether object metadata describes a client-side encrypted object
+pub fn is_encrypted(metadata: &HashMap<String, String>) -> bool {
+    metadata.contains_key(META_VERSION)
+}
+
+/// Plaintext size recorded in an encrypted object's metadata
+pub fn plaintext_size(metadata: &HashMap<String, String>) -> Option<u64> {
+    metadata.get(META_SIZE)?.parse().ok()
+}
+
+/// Size of the data an object holds: the recorded plaintext size of a
+/// client-side encrypted object, the object's own size otherwise
+///
+/// `None` for an encrypted object whose plaintext size was not recorded.
+pub fn data_size(metadata: &HashMap<String, String>, object_size: u64) -> Option<u64> {
+    if is_encrypted(metadata) {
+        plaintext_size(metadata)
+    } else {
+        Some(object_size)
+    }
+}
+
+/// Size of the object that holds `plaintext_size` bytes once encrypted
+pub fn encrypted_size(plaintext_size: u64) -> u64 {
+    let chunks = plaintext_size.div_ceil(CHUNK_SIZE as u64).max(1);
+    plaintext_size + chunks * TAG_SIZE as u64
+}
+
+/// Encryption metadata of an object, to carry over when its metadata is replaced
+pub fn envelope_metadata(metadata: &HashMap<String, String>) -> HashMap<String, String> {
+    metadata
+        .iter()
+        .filter(|(key, _)| key.as_str() == META_VERSION || key.starts_with("hsc-cse-"))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+/// Decrypts a ciphertext stream that arrives in arbitrary pieces
+pub struct Decryptor {
+    envelope: Envelope,
+    buffer: Vec<u8>,
+    index: u64,
+}
+
+impl Decryptor {
+    pub fn new(envelope: Envelope) -> Decryptor {
+        Decryptor {
+            envelope,
+            buffer: Vec::new(),
+            index: 0,
+        }
+    }
+
+    /// Add ciphertext; returns the plaintext of every chunk known not to be the last
+    pub fn update(&mut self, data: &[u8]) -> Result<Vec<u8>, String> {
+        self.buffer.extend_from_slice(data);
+        let mut plaintext = Vec::new();
+        let sealed = CHUNK_SIZE + TAG_SIZE;
+
+        // Keep the final chunk buffered until the stream ends
+        while self.buffer.len() > sealed {
+            let chunk: Vec<u8> = self.buffer.drain(..sealed).collect();
+            plaintext.extend(self.envelope.decrypt_chunk(self.index, false, &chunk)?);
+            self.index += 1;
+        }
+        Ok(plaintext)
+    }
+
+    /// Decrypt the final chunk at the end of the stream
+    pub fn finish(self) -> Result<Vec<u8>, String> {
+        self.envelope.decrypt_chunk(self.index, true, &self.buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn master() -> MasterKey {
+        MasterKey(*Key::<Aes256Gcm>::from_slice(&[3u8; 32]))
+    }
+
+    /// Encrypt `data` in two parts, as a multipart upload does
+    fn seal(envelope: &Envelope, data: &[u8]) -> Vec<u8> {
+        let split = (data.len() / CHUNK_SIZE / 2) * CHUNK_SIZE;
+        let mut ciphertext = envelope.encrypt(0, &data[..split], false);
+        ciphertext.extend(envelope.encrypt((split / CHUNK_SIZE) as u64, &data[split..], true));
+        ciphertext
+    }
+
+    #[test]
+    fn test_round_trip_in_pieces() {
+        for size in [0, 10, CHUNK_SIZE, CHUNK_SIZE * 2 + 7] {
+            let data: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+            let envelope = Envelope::generate();
+            let metadata = envelope.to_metadata(&master(), size as u64);
+            let ciphertext = seal(&envelope, &data);
+            assert_eq!(ciphertext.len() as u64, encrypted_size(size as u64));
+            assert_eq!(plaintext_size(&metadata), Some(size as u64));
+
+            let envelope = Envelope::from_metadata(&master(), &metadata)
+                .unwrap()
+                .unwrap();
+            let mut decryptor = Decryptor::new(envelope);
+            let mut plaintext = Vec::new();
+            for piece in ciphertext.chunks(1000) {
+                plaintext.extend(decryptor.update(piece).unwrap());
+            }
+            plaintext.extend(decryptor.finish().unwrap());
+            assert_eq!(plaintext, data);
+        }
+    }
+
+    #[test]
+    fn test_wrong_key_and_truncation() {
+        let envelope = Envelope::generate();
+        let metadata = envelope.to_metadata(&master(), 0);
+        let other = MasterKey(*Key::<Aes256Gcm>::from_slice(&[4u8; 32]));
+        assert!(Envelope::from_metadata(&other, &metadata).is_err());
+        assert!(Envelope::from_metadata(&master(), &HashMap::new())
+            .unwrap()
+            .is_none());
+
+        // Dropping the last chunk leaves a chunk that was not sealed as last
+        let data = vec![1u8; CHUNK_SIZE * 2];
+        let ciphertext = seal(&envelope, &data);
+        let mut decryptor = Decryptor::new(envelope);
+        decryptor
+            .update(&ciphertext[..CHUNK_SIZE + TAG_SIZE])
+            .unwrap();
+        assert!(decryptor.finish().is_err());
+    }
+
+    #[test]
+    fn test_data_size() {
+        // An encrypted copy of a 100-byte file and a plain 116-byte object
+        // have the same size; only the metadata tells them apart
+        let encrypted = Envelope::generate().to_metadata(&master(), 100);
+        let size = encrypted_size(100);
+        assert_eq!(size, 116);
+        assert_eq!(data_size(&encrypted, size), Some(100));
+        assert_eq!(data_size(&HashMap::new(), size), Some(116));
+
+        let mut unrecorded = encrypted.clone();
+        unrecorded.remove(META_SIZE);
+        assert_eq!(data_size(&unrecorded, size), None);
+    }
+
+    #[test]
+    fn test_envelope_metadata() {
+        let metadata = HashMap::from([
+            ("hsc-cse".to_string(), "v1".to_string()),
+            ("hsc-cse-iv".to_string(), "x".to_string()),
+            ("owner".to_string(), "me".to_string()),
+        ]);
+        let kept = envelope_metadata(&metadata);
+        assert_eq!(kept.len(), 2);
+        assert!(!kept.contains_key("owner"));
+        assert_eq!(encrypted_size(0), 16);
+        assert_eq!(
+            encrypted_size(CHUNK_SIZE as u64 + 1),
+            CHUNK_SIZE as u64 + 33
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+use std::fs::{File, FileTimes};
+use std::path::{Component, Path};
+use std::time::{Duration, UNIX_EPOCH};
+
+/// User metadata keys (sent as x-amz-meta-*) used by --preserve.
+/// Values are decimal, matching the s3fs convention.
+pub const META_MTIME: &str = "mtime";
+pub const META_MODE: &str = "mode";
+pub const META_UID: &str = "uid";
+pub const META_GID: &str = "gid";
+
+/// File type bits of `st_mode` and the value marking a symbolic link
+const S_IFMT: u32 = 0o170000;
+const S_IFLNK: u32 = 0o120000;
+
+/// Build S3 user metadata describing a local file's POSIX attributes
+pub fn to_s3_metadata(metadata: &std::fs::Metadata) -> HashMap<String, String> {
+    let mut result = HashMap::new();
+
+    if let Ok(modified) = metadata.modified() {
+        if let Ok(since_epoch) = modified.duration_since(UNIX_EPOCH) {
+            result.insert(META_MTIME.to_string(), since_epoch.as_secs().to_string());
+        }
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        result.insert(META_MODE.to_string(), metadata.mode().to_string());
+        result.insert(META_UID.to_string(), metadata.uid().to_string());
+        result.insert(META_GID.to_string(), metadata.gid().to_string());
+    }
+
+    result
+}
+
+/// Build S3 user metadata for a symlink marker object
+///
+/// The link's own `lstat` attributes are used, so the mode carries the
+/// symlink file type that `PosixAttributes::is_symlink` detects on download.
+pub fn symlink_marker_metadata(path: &Path) -> std::io::Result<HashMap<String, String>> {
+    let metadata = std::fs::symlink_metadata(path)?;
+    let mut result = to_s3_metadata(&metadata);
+    result
+        .entry(META_MODE.to_string())
+        .or_insert_with(|| (S_IFLNK | 0o777).to_string());
+    Ok(result)
+}
+
+/// Whether a symlink at `link` (a path below `root`) pointing at `target`
+/// stays inside `root`
+///
+/// Judged on the paths alone: absolute targets and targets whose `..`
+/// components climb above `root` are refused, as is a link that is not
+/// below `root` itself.
+pub fn symlink_stays_within(root: &Path, link: &Path, target: &str) -> bool {
+    let Ok(relative) = link.strip_prefix(root) else {
+        return false;
+    };
+    // Depth of the directory holding the link, below root
+    let mut depth = 0usize;
+    for component in relative.parent().unwrap_or(Path::new("")).components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            _ => return false,
+        }
+    }
+
+    for component in Path::new(target).components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir if depth > 0 => depth -= 1,
+            _ => return false,
+        }
+    }
+    true
+}
+
+/// POSIX attributes parsed from S3 user metadata
+#[derive(Debug, Default, PartialEq)]
+pub struct PosixAttributes {
+    pub mtime: Option<u64>,
+    pub mode: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+impl PosixAttributes {
+    /// Whether the object is a symlink marker (its body holds the link target)
+    pub fn is_symlink(&self) -> bool {
+        self.mode.is_some_and(|mode| mode & S_IFMT == S_IFLNK)
+    }
+
+    /// Parse attributes from object metadata, ignoring malformed values
+    pub fn from_s3_metadata(metadata: &HashMap<String, String>) -> Self {
+        let get = |key: &str| metadata.get(key).map(|v| v.trim());
+        PosixAttributes {
+            mtime: get(META_MTIME).and_then(|v| v.parse().ok()),
+            mode: get(META_MODE).and_then(|v| v.parse().ok()),
+            uid: get(META_UID).and_then(|v| v.parse().ok()),
+            gid: get(META_GID).and_then(|v| v.parse().ok()),
+        }
+    }
+
+    /// Apply the attributes to a local file
+    ///
+    /// Ownership changes need privileges, so permission errors from chown are
+    /// ignored; other failures are returned.
+    pub fn apply(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(mtime) = self.mtime {
+            let file = File::options().write(true).open(path)?;
+            let time = UNIX_EPOCH + Duration::from_secs(mtime);
+            file.set_times(FileTimes::new().set_modified(time).set_accessed(time))?;
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            if self.uid.is_some() || self.gid.is_some() {
+                match std::os::unix::fs::chown(path, self.uid, self.gid) {
+                    Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {}
+                    other => other?,
+                }
+            }
+
+            if let Some(mode) = self.mode {
+                std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode & 0o7777))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_attributes() {
+        let metadata: HashMap<String, String> = [
+            ("mtime", "1700000000"),
+            ("mode", "33188"),
+            ("uid", "1000"),
+            ("gid", "bad"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+        let attrs = PosixAttributes::from_s3_metadata(&metadata);
+        assert_eq!(attrs.mtime, Some(1700000000));
+        assert_eq!(attrs.mode, Some(0o100644));
+        assert_eq!(attrs.uid, Some(1000));
+        assert_eq!(attrs.gid, None);
+        assert!(!attrs.is_symlink());
+    }
+
+    #[test]
+    fn test_symlink_stays_within() {
+        let root = Path::new("/restore");
+        let link = Path::new("/restore/a/b/link");
+        assert!(symlink_stays_within(root, link, "target.txt"));
+        assert!(symlink_stays_within(root, link, "../../c/file"));
+        assert!(!symlink_stays_within(root, link, "../../../etc/passwd"));
+        assert!(!symlink_stays_within(root, link, "/etc/passwd"));
+        assert!(!symlink_stays_within(root, link, "x/../../../../y"));
+        assert!(!symlink_stays_within(
+            root,
+            Path::new("/elsewhere/link"),
+            "file"
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_marker_metadata() {
+        let dir = std::env::temp_dir().join(format!("hsc-meta-link-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let link = dir.join("link");
+        let _ = std::fs::remove_file(&link);
+        std::os::unix::fs::symlink("missing-target", &link).unwrap();
+
+        let metadata = symlink_marker_metadata(&link).unwrap();
+        assert!(PosixAttributes::from_s3_metadata(&metadata).is_symlink());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_round_trip() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("hsc-meta-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("source.txt");
+        let target = dir.join("target.txt");
+        std::fs::write(&source, b"data").unwrap();
+        std::fs::write(&target, b"data").unwrap();
+        std::fs::set_permissions(&source, std::fs::Permissions::from_mode(0o640)).unwrap();
+        File::options()
+            .write(true)
+            .open(&source)
+            .unwrap()
+            .set_modified(UNIX_EPOCH + Duration::from_secs(1_600_000_000))
+            .unwrap();
+
+        let metadata = to_s3_metadata(&std::fs::metadata(&source).unwrap());
+        PosixAttributes::from_s3_metadata(&metadata)
+            .apply(&target)
+            .unwrap();
+
+        let restored = std::fs::metadata(&target).unwrap();
+        assert_eq!(restored.permissions().mode() & 0o777, 0o640);
+        assert_eq!(
+            restored.modified().unwrap(),
+            UNIX_EPOCH + Duration::from_secs(1_600_000_000)
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,63 +1,501 @@
-use glob::Pattern;
+use crate::walk::LocalEntry;
+use aws_sdk_s3::types::Object;
+use glob::{MatchOptions, Pattern};
+use regex::{Regex, RegexBuilder};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
 
+/// Match options shared by all filter patterns: `*` and `?` never cross '/'
+fn match_options(ignore_case: bool) -> MatchOptions {
+    MatchOptions {
+        case_sensitive: !ignore_case,
+        require_literal_separator: true,
+        require_literal_leading_dot: false,
+    }
+}
+
+/// A single include or exclude pattern
+///
+/// Paths are always relative to the source root and use '/' separators.
+/// A pattern without '/' matches a file name at any depth; a pattern
+/// containing '/' (or starting with '/', which anchors it) matches the whole
+/// relative path. `**` matches any number of directories.
+struct FilterPattern {
+    pattern: Pattern,
+}
+
+impl FilterPattern {
+    fn new(pattern: &str) -> Result<Self, glob::PatternError> {
+        let pattern = match pattern.strip_prefix('/') {
+            Some(anchored) => Pattern::new(anchored)?,
+            None if pattern.contains('/') => Pattern::new(pattern)?,
+            None => Pattern::new(&format!("**/{}", pattern))?,
+        };
+        Ok(FilterPattern { pattern })
+    }
+
+    fn matches(&self, path: &str, options: MatchOptions) -> bool {
+        self.pattern.matches_with(path, options)
+    }
+}
+
+/// What a filter rule does with the paths it matches
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Action {
+    Include,
+    Exclude,
+}
+
+/// An ordered filter rule (`--filter`, `--filter-from`, `--exclude-from`)
+struct FilterRule {
+    action: Action,
+    pattern: FilterPattern,
+    /// Pattern had a trailing '/' and only matches directories
+    dir_only: bool,
+}
+
+impl FilterRule {
+    fn new(action: Action, pattern: &str) -> Result<Self, String> {
+        let dir_only = pattern.len() > 1 && pattern.ends_with('/');
+        let glob = pattern.trim_end_matches('/');
+        let pattern = FilterPattern::new(glob)
+            .map_err(|e| format!("Invalid filter pattern '{}': {}", glob, e))?;
+        Ok(FilterRule {
+            action,
+            pattern,
+            dir_only,
+        })
+    }
+
+    /// Parse an rsync-style rule: "+ pattern" includes, "- pattern" excludes
+    fn parse(rule: &str) -> Result<Self, String> {
+        let action = match rule.chars().next() {
+            Some('+') => Action::Include,
+            Some('-') => Action::Exclude,
+            _ => {
+                return Err(format!(
+                    "Invalid filter rule '{}': expected \"+ pattern\" or \"- pattern\"",
+                    rule
+                ))
+            }
+        };
+
+        let pattern = rule[1..].strip_prefix(' ').unwrap_or(&rule[1..]);
+        if pattern.is_empty() {
+            return Err(format!("Invalid filter rule '{}': missing pattern", rule));
+        }
+        FilterRule::new(action, pattern)
+    }
+
+    /// Parse a gitignore-style line: a pattern excludes, `!pattern` re-includes
+    fn parse_gitignore(line: &str) -> Result<Self, String> {
+        let line = line.trim_end();
+        match line.strip_prefix('!') {
+            Some(pattern) => FilterRule::new(Action::Include, pattern),
+            None => {
+                // "\!" and "\#" escape a literal leading character
+                let pattern = line.strip_prefix('\\').unwrap_or(line);
+                FilterRule::new(Action::Exclude, pattern)
+            }
+        }
+    }
+
+    fn matches(&self, path: &str, is_dir: bool, options: MatchOptions) -> bool {
+        (is_dir || !self.dir_only) && self.pattern.matches(path, options)
+    }
+}
+
+/// Size, age and storage class limits applied alongside the path patterns
+#[derive(Debug, Default, Clone)]
+pub struct Predicates {
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    /// Only files modified after this time
+    pub newer_than: Option<SystemTime>,
+    /// Only files modified before this time
+    pub older_than: Option<SystemTime>,
+    /// Accepted storage classes (upper case); empty accepts all
+    pub storage_classes: Vec<String>,
+}
+
+impl Predicates {
+    fn is_empty(&self) -> bool {
+        self.min_size.is_none()
+            && self.max_size.is_none()
+            && self.newer_than.is_none()
+            && self.older_than.is_none()
+            && self.storage_classes.is_empty()
+    }
+
+    fn matches(&self, attrs: &FileAttributes) -> bool {
+        if self.min_size.is_some_and(|min| attrs.size < min)
+            || self.max_size.is_some_and(|max| attrs.size > max)
+        {
+            return false;
+        }
+
+        if self.newer_than.is_some() || self.older_than.is_some() {
+            let Some(modified) = attrs.modified else {
+                return false;
+            };
+            if self.newer_than.is_some_and(|t| modified <= t)
+                || self.older_than.is_some_and(|t| modified >= t)
+            {
+                return false;
+            }
+        }
+
+        self.storage_classes.is_empty()
+            || self
+                .storage_classes
+                .iter()
+                .any(|class| class.eq_ignore_ascii_case(attrs.storage_class))
+    }
+}
+
+/// Attributes of a local file or S3 object that predicates are checked against
+pub struct FileAttributes<'a> {
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    pub storage_class: &'a str,
+}
+
+impl FileAttributes<'_> {
+    /// Attributes of a local file; local data counts as STANDARD storage
+    pub fn from_metadata(metadata: &std::fs::Metadata) -> Self {
+        FileAttributes {
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+            storage_class: "STANDARD",
+        }
+    }
+
+    /// Attributes of an S3 list entry (S3 omits the class for STANDARD)
+    pub fn from_object(object: &Object) -> FileAttributes<'_> {
+        FileAttributes {
+            size: object.size().unwrap_or(0).max(0) as u64,
+            modified: object
+                .last_modified()
+                .and_then(|t| SystemTime::try_from(*t).ok()),
+            storage_class: object
+                .storage_class()
+                .map(|class| class.as_str())
+                .unwrap_or("STANDARD"),
+        }
+    }
+}
+
+#[derive(Default)]
 pub struct FileFilter {
-    include_patterns: Vec<Pattern>,
-    exclude_patterns: Vec<Pattern>,
+    rules: Vec<FilterRule>,
+    include_patterns: Vec<FilterPattern>,
+    exclude_patterns: Vec<FilterPattern>,
+    include_regexes: Vec<Regex>,
+    exclude_regexes: Vec<Regex>,
+    ignore_case: bool,
+    predicates: Predicates,
 }
 
 impl FileFilter {
     /// Create a new FileFilter with include and exclude patterns
     pub fn new(include: Vec<String>, exclude: Vec<String>) -> Result<Self, String> {
         let include_patterns = include
-            .into_iter()
-            .map(|p| Pattern::new(&p).map_err(|e| format!("Invalid include pattern: {}", e)))
+            .iter()
+            .map(|p| {
+                FilterPattern::new(p).map_err(|e| format!("Invalid include pattern '{}': {}", p, e))
+            })
             .collect::<Result<Vec<_>, _>>()?;
 
         let exclude_patterns = exclude
-            .into_iter()
-            .map(|p| Pattern::new(&p).map_err(|e| format!("Invalid exclude pattern: {}", e)))
+            .iter()
+            .map(|p| {
+                FilterPattern::new(p).map_err(|e| format!("Invalid exclude pattern '{}': {}", p, e))
+            })
             .collect::<Result<Vec<_>, _>>()?;
 
         Ok(FileFilter {
+            rules: Vec::new(),
             include_patterns,
             exclude_patterns,
+            ..Default::default()
         })
     }
 
+    /// Match glob patterns, rules and regexes case-insensitively
+    ///
+    /// Regexes are compiled when added, so call this before `add_regexes`.
+    pub fn set_ignore_case(&mut self, ignore_case: bool) {
+        self.ignore_case = ignore_case;
+    }
+
+    /// Add regexes that are searched for anywhere in the relative path
+    /// (anchor them with `^`/`$`); they combine with the glob include and
+    /// exclude patterns
+    pub fn add_regexes(&mut self, include: &[String], exclude: &[String]) -> Result<(), String> {
+        let compile = |pattern: &String, kind: &str| {
+            RegexBuilder::new(pattern)
+                .case_insensitive(self.ignore_case)
+                .build()
+                .map_err(|e| format!("Invalid {} regex '{}': {}", kind, pattern, e))
+        };
+
+        let include = include
+            .iter()
+            .map(|p| compile(p, "include"))
+            .collect::<Result<Vec<_>, _>>()?;
+        let exclude = exclude
+            .iter()
+            .map(|p| compile(p, "exclude"))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.include_regexes.extend(include);
+        self.exclude_regexes.extend(exclude);
+        Ok(())
+    }
+
+    /// Set the size, age and storage class limits
+    pub fn set_predicates(&mut self, predicates: Predicates) {
+        self.predicates = predicates;
+    }
+
+    /// Append an rsync-style "+ pattern" or "- pattern" rule
+    pub fn add_rule(&mut self, rule: &str) -> Result<(), String> {
+        self.rules.push(FilterRule::parse(rule)?);
+        Ok(())
+    }
+
+    /// Append the rules of a filter file: one "+ pattern" or "- pattern" per
+    /// line, blank lines and lines starting with '#' or ';' are ignored
+    pub fn add_filter_file(&mut self, path: &Path) -> Result<(), String> {
+        for line in read_rule_lines(path)? {
+            if line.starts_with(';') {
+                continue;
+            }
+            self.add_rule(&line)
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+        Ok(())
+    }
+
+    /// Append the patterns of a gitignore-style exclude file
+    ///
+    /// Later lines override earlier ones in gitignore, so the rules are added
+    /// in reverse to keep first-match evaluation.
+    pub fn add_exclude_file(&mut self, path: &Path) -> Result<(), String> {
+        let rules = read_rule_lines(path)?
+            .iter()
+            .map(|line| FilterRule::parse_gitignore(line))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        self.rules.extend(rules.into_iter().rev());
+        Ok(())
+    }
+
     /// Check if a file path should be included based on filter rules
     /// Returns true if the file passes the filters
     ///
+    /// `path` is relative to the source root (local directory or S3 prefix);
+    /// a trailing '/' marks a directory (marker).
+    ///
     /// Rules:
-    /// 1. If exclude pattern matches, return false (exclude takes precedence)
-    /// 2. If include patterns exist and none match, return false
-    /// 3. Otherwise return true
+    /// 1. If a parent directory is excluded, return false (see `includes_dir`)
+    /// 2. If a filter rule matches, the first matching rule decides
+    /// 3. If an exclude pattern or regex matches, return false (exclude takes precedence)
+    /// 4. If include patterns or regexes exist and none match, return false
+    /// 5. Otherwise return true
     pub fn matches(&self, path: &str) -> bool {
+        let path = path.trim_start_matches('/');
+        let is_dir = path.ends_with('/');
+        let path = path.trim_end_matches('/');
+
+        let mut parents = path.match_indices('/').map(|(i, _)| &path[..i]);
+        if parents.any(|dir| !self.includes_dir(dir)) {
+            return false;
+        }
+
+        if let Some(action) = self.first_rule(path, is_dir) {
+            return action == Action::Include;
+        }
+
+        let options = match_options(self.ignore_case);
+
         // Check exclude patterns first (they take precedence)
-        for pattern in &self.exclude_patterns {
-            if pattern.matches(path) {
-                return false;
-            }
+        if self
+            .exclude_patterns
+            .iter()
+            .any(|p| p.matches(path, options))
+            || self.exclude_regexes.iter().any(|r| r.is_match(path))
+        {
+            return false;
         }
 
         // If there are include patterns, at least one must match
-        if !self.include_patterns.is_empty() {
-            for pattern in &self.include_patterns {
-                if pattern.matches(path) {
-                    return true;
-                }
+        if self.include_patterns.is_empty() && self.include_regexes.is_empty() {
+            return true;
+        }
+        self.include_patterns
+            .iter()
+            .any(|p| p.matches(path, options))
+            || self.include_regexes.iter().any(|r| r.is_match(path))
+    }
+
+    /// Check a file's path and attributes against all filters
+    ///
+    /// Predicates only apply to files; directory markers (paths ending in
+    /// '/') are checked by path alone.
+    pub fn matches_file(&self, path: &str, attrs: &FileAttributes) -> bool {
+        self.matches(path) && (path.ends_with('/') || self.predicates.matches(attrs))
+    }
+
+    /// Check an S3 list entry; `path` is its key relative to the listed prefix
+    pub fn matches_object(&self, path: &str, object: &Object) -> bool {
+        self.matches_file(path, &FileAttributes::from_object(object))
+    }
+
+    /// Check an entry of a local directory walk
+    ///
+    /// The entry is only stat'ed when predicates are set; entries whose
+    /// metadata cannot be read do not match.
+    pub fn matches_local(&self, entry: &LocalEntry) -> bool {
+        if self.predicates.is_empty() {
+            return self.matches(&entry.relative);
+        }
+
+        match entry.metadata() {
+            Ok(metadata) => {
+                self.matches_file(&entry.relative, &FileAttributes::from_metadata(&metadata))
             }
-            return false;
+            Err(e) => {
+                eprintln!("Warning: skipping {}: {}", entry.path.display(), e);
+                false
+            }
+        }
+    }
+
+    /// Check if a directory should be descended into
+    ///
+    /// A directory is pruned, with everything below it, when the first filter
+    /// rule matching it excludes it, or when no rule matches and an exclude
+    /// pattern does. Include patterns and regexes never prune directories.
+    pub fn includes_dir(&self, dir: &str) -> bool {
+        let options = match_options(self.ignore_case);
+        match self.first_rule(dir, true) {
+            Some(action) => action == Action::Include,
+            None => !self
+                .exclude_patterns
+                .iter()
+                .any(|p| p.matches(dir, options)),
         }
+    }
 
-        // No include patterns, and not excluded
-        true
+    fn first_rule(&self, path: &str, is_dir: bool) -> Option<Action> {
+        let options = match_options(self.ignore_case);
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(path, is_dir, options))
+            .map(|rule| rule.action)
     }
 
     /// Check if any filters are set
-    #[allow(dead_code)]
     pub fn has_filters(&self) -> bool {
-        !self.include_patterns.is_empty() || !self.exclude_patterns.is_empty()
+        !self.rules.is_empty()
+            || !self.include_patterns.is_empty()
+            || !self.exclude_patterns.is_empty()
+            || !self.include_regexes.is_empty()
+            || !self.exclude_regexes.is_empty()
+            || !self.predicates.is_empty()
+    }
+}
+
+/// Parse a size such as "1048576", "500K", "1.5G" or "2GiB" (1K = 1024 bytes)
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => {
+            return Err(format!(
+                "Invalid size unit in '{}' (use B, K, M, G or T)",
+                value
+            ))
+        }
+    };
+
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("Invalid size '{}'", value))?;
+    Ok((number * multiplier as f64) as u64)
+}
+
+/// Parse a point in time: a duration before now ("90s", "30m", "12h", "7d",
+/// "2w") or a UTC timestamp ("2024-01-31", "2024-01-31T12:00:00",
+/// RFC 3339 with offset)
+pub fn parse_time(value: &str) -> Result<SystemTime, String> {
+    let value = value.trim();
+    let invalid = || {
+        format!(
+            "Invalid time '{}': use a duration (e.g. 7d, 12h) or a date (YYYY-MM-DD[THH:MM:SS])",
+            value
+        )
+    };
+
+    if let Some(duration) = parse_duration(value) {
+        return SystemTime::now().checked_sub(duration).ok_or_else(invalid);
     }
+
+    parse_date(value).ok_or_else(invalid)
+}
+
+/// Parse a duration with a unit suffix: "90s", "30m", "12h", "7d" or "2w"
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let unit = value.chars().last().filter(|c| c.is_ascii_alphabetic())?;
+    let amount = value[..value.len() - 1].parse::<u64>().ok()?;
+    let seconds = match unit.to_ascii_lowercase() {
+        's' => 1,
+        'm' => 60,
+        'h' => 3600,
+        'd' => 86400,
+        'w' => 7 * 86400,
+        _ => return None,
+    };
+    Some(Duration::from_secs(amount.saturating_mul(seconds)))
+}
+
+/// Parse a UTC date or timestamp ("2024-01-31", "2024-01-31T12:00:00",
+/// RFC 3339 with offset)
+pub fn parse_date(value: &str) -> Option<SystemTime> {
+    let timestamp = if let Ok(time) = chrono::DateTime::parse_from_rfc3339(value) {
+        time.timestamp()
+    } else if let Ok(time) = chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S") {
+        time.and_utc().timestamp()
+    } else if let Ok(date) = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        date.and_hms_opt(0, 0, 0)?.and_utc().timestamp()
+    } else {
+        return None;
+    };
+
+    let seconds = u64::try_from(timestamp).ok()?;
+    Some(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+/// Read the non-blank, non-comment lines of a rule file
+fn read_rule_lines(path: &Path) -> Result<Vec<String>, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read filter file {}: {}", path.display(), e))?;
+
+    Ok(content
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_string())
+        .collect())
 }
 
 #[cfg(test)]
@@ -93,4 +531,230 @@ mod tests {
         assert!(filter.matches("any_file.txt"));
         assert!(filter.matches("any_file.rs"));
     }
+
+    #[test]
+    fn test_unanchored_matches_any_depth() {
+        let filter = FileFilter::new(vec!["*.txt".to_string()], vec![]).unwrap();
+        assert!(filter.matches("a/b/file.txt"));
+        assert!(!filter.matches("a/file.txt/data.bin"));
+
+        let filter = FileFilter::new(vec![], vec!["temp*".to_string()]).unwrap();
+        assert!(!filter.matches("dir/temp1"));
+        assert!(filter.matches("dir/data"));
+    }
+
+    #[test]
+    fn test_star_does_not_cross_separator() {
+        let filter = FileFilter::new(vec!["data/*.csv".to_string()], vec![]).unwrap();
+        assert!(filter.matches("data/2021.csv"));
+        assert!(!filter.matches("data/old/2020.csv"));
+        assert!(!filter.matches("other/data/2021.csv"));
+    }
+
+    #[test]
+    fn test_double_star() {
+        let filter = FileFilter::new(vec!["data/**/*.csv".to_string()], vec![]).unwrap();
+        assert!(filter.matches("data/2021.csv"));
+        assert!(filter.matches("data/old/2020.csv"));
+
+        let filter = FileFilter::new(vec![], vec!["logs/**".to_string()]).unwrap();
+        assert!(!filter.matches("logs/a/b.log"));
+        assert!(filter.matches("app/logs.txt"));
+    }
+
+    #[test]
+    fn test_anchored_pattern() {
+        let filter = FileFilter::new(vec!["/*.txt".to_string()], vec![]).unwrap();
+        assert!(filter.matches("top.txt"));
+        assert!(!filter.matches("sub/nested.txt"));
+    }
+
+    #[test]
+    fn test_dir_marker_path() {
+        let filter = FileFilter::new(vec![], vec!["tmp".to_string()]).unwrap();
+        assert!(!filter.matches("a/tmp/"));
+        assert!(filter.matches("a/keep/"));
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        assert!(FileFilter::new(vec!["[".to_string()], vec![]).is_err());
+    }
+
+    #[test]
+    fn test_filter_rules_first_match() {
+        let mut filter = FileFilter::default();
+        filter.add_rule("+ logs/keep.log").unwrap();
+        filter.add_rule("- logs/*").unwrap();
+        assert!(filter.matches("logs/keep.log"));
+        assert!(!filter.matches("logs/other.log"));
+        assert!(filter.matches("app.log"));
+    }
+
+    #[test]
+    fn test_filter_rules_before_patterns() {
+        let mut filter = FileFilter::new(vec![], vec!["*.log".to_string()]).unwrap();
+        filter.add_rule("+ important.log").unwrap();
+        assert!(filter.matches("important.log"));
+        assert!(!filter.matches("debug.log"));
+    }
+
+    #[test]
+    fn test_directory_pruning() {
+        let mut filter = FileFilter::default();
+        filter.add_rule("- build/").unwrap();
+        assert!(!filter.includes_dir("build"));
+        assert!(!filter.matches("build/out/app.bin"));
+        assert!(!filter.matches("src/build/"));
+        // Directory-only rules do not match files
+        assert!(filter.matches("build"));
+
+        let filter = FileFilter::new(vec![], vec![".git".to_string()]).unwrap();
+        assert!(!filter.matches(".git/config"));
+        assert!(filter.matches("src/main.rs"));
+    }
+
+    #[test]
+    fn test_include_only_subtree() {
+        let mut filter = FileFilter::default();
+        filter.add_rule("+ */").unwrap();
+        filter.add_rule("+ *.txt").unwrap();
+        filter.add_rule("- *").unwrap();
+        assert!(filter.matches("a/b/c.txt"));
+        assert!(!filter.matches("a/b/c.rs"));
+    }
+
+    #[test]
+    fn test_invalid_rule() {
+        let mut filter = FileFilter::default();
+        assert!(filter.add_rule("* missing action").is_err());
+        assert!(filter.add_rule("+").is_err());
+    }
+
+    #[test]
+    fn test_exclude_file_gitignore() {
+        let path = std::env::temp_dir().join(format!("hsc-ignore-{}", std::process::id()));
+        std::fs::write(
+            &path,
+            "# comment\n\nlogs/*\n!logs/keep.log\ntarget/\n\\#literal\n",
+        )
+        .unwrap();
+
+        let mut filter = FileFilter::default();
+        filter.add_exclude_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(filter.matches("logs/keep.log"));
+        assert!(!filter.matches("logs/debug.log"));
+        assert!(!filter.matches("target/release/hsc"));
+        assert!(!filter.matches("#literal"));
+        assert!(filter.matches("src/main.rs"));
+    }
+
+    #[test]
+    fn test_filter_file() {
+        let path = std::env::temp_dir().join(format!("hsc-filter-{}", std::process::id()));
+        std::fs::write(&path, "; comment\n+ *.csv\n- *\n").unwrap();
+
+        let mut filter = FileFilter::default();
+        filter.add_filter_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(filter.matches("data.csv"));
+        assert!(!filter.matches("data.json"));
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1048576"), Ok(1048576));
+        assert_eq!(parse_size("500K"), Ok(500 * 1024));
+        assert_eq!(parse_size("1.5G"), Ok(3 << 29));
+        assert_eq!(parse_size("2GiB"), Ok(2 << 30));
+        assert_eq!(parse_size("10 mb"), Ok(10 << 20));
+        assert!(parse_size("10X").is_err());
+        assert!(parse_size("G").is_err());
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let epoch = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+        assert_eq!(parse_time("2024-01-01"), Ok(epoch(1704067200)));
+        assert_eq!(parse_time("2024-01-01T01:00:00"), Ok(epoch(1704070800)));
+        assert_eq!(
+            parse_time("2024-01-01T02:00:00+01:00"),
+            Ok(epoch(1704070800))
+        );
+
+        let week_ago = parse_time("7d").unwrap();
+        let age = SystemTime::now().duration_since(week_ago).unwrap();
+        assert!(age >= Duration::from_secs(7 * 86400));
+        assert!(age < Duration::from_secs(7 * 86400 + 60));
+
+        assert!(parse_time("7y").is_err());
+        assert!(parse_time("yesterday").is_err());
+    }
+
+    #[test]
+    fn test_predicates() {
+        let mut filter = FileFilter::default();
+        filter.set_predicates(Predicates {
+            min_size: Some(10),
+            max_size: Some(100),
+            newer_than: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1000)),
+            older_than: None,
+            storage_classes: vec!["GLACIER".to_string()],
+        });
+
+        let attrs = |size, modified, storage_class| FileAttributes {
+            size,
+            modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(modified)),
+            storage_class,
+        };
+
+        assert!(filter.matches_file("a.bin", &attrs(50, 2000, "GLACIER")));
+        assert!(!filter.matches_file("a.bin", &attrs(5, 2000, "GLACIER")));
+        assert!(!filter.matches_file("a.bin", &attrs(500, 2000, "GLACIER")));
+        assert!(!filter.matches_file("a.bin", &attrs(50, 500, "GLACIER")));
+        assert!(!filter.matches_file("a.bin", &attrs(50, 2000, "STANDARD")));
+        // Directory markers are matched by path only
+        assert!(filter.matches_file("dir/", &attrs(0, 0, "STANDARD")));
+    }
+
+    #[test]
+    fn test_ignore_case() {
+        let mut filter = FileFilter::new(vec!["*.csv".to_string()], vec![]).unwrap();
+        assert!(!filter.matches("data/a.CSV"));
+        filter.set_ignore_case(true);
+        assert!(filter.matches("data/a.CSV"));
+        assert!(filter.matches("data/b.Csv"));
+
+        filter.add_rule("- ARCHIVE/").unwrap();
+        assert!(!filter.matches("archive/old.csv"));
+    }
+
+    #[test]
+    fn test_regexes() {
+        let mut filter = FileFilter::default();
+        filter
+            .add_regexes(
+                &[r"^year=(2023|2024)/".to_string()],
+                &[r"\.tmp$".to_string()],
+            )
+            .unwrap();
+        assert!(filter.matches("year=2023/month=01/part-0.parquet"));
+        assert!(filter.matches("year=2024/part-1.parquet"));
+        assert!(!filter.matches("year=2022/part-0.parquet"));
+        assert!(!filter.matches("year=2024/part-1.tmp"));
+
+        let mut filter = FileFilter::new(vec!["*.json".to_string()], vec![]).unwrap();
+        filter.set_ignore_case(true);
+        filter.add_regexes(&[r"\.csv$".to_string()], &[]).unwrap();
+        assert!(filter.matches("a.JSON"));
+        assert!(filter.matches("b.CSV"));
+        assert!(!filter.matches("c.txt"));
+
+        assert!(FileFilter::default()
+            .add_regexes(&["(".to_string()], &[])
+            .is_err());
+    }
 }
@@ -0,0 +1,311 @@
+use crate::filters;
+use aws_sdk_s3::operation::head_object::HeadObjectOutput;
+use aws_sdk_s3::primitives::DateTime;
+use aws_sdk_s3::types::{MetadataDirective, StorageClass};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// HTTP headers and user metadata set on uploaded and copied objects
+#[derive(Debug, Clone, Default)]
+pub struct ObjectHeaders {
+    /// Explicit Content-Type; guessed from the file extension when unset
+    pub content_type: Option<String>,
+    pub cache_control: Option<String>,
+    pub content_disposition: Option<String>,
+    pub content_encoding: Option<String>,
+    pub expires: Option<SystemTime>,
+    /// User metadata, sent as `x-amz-meta-*`
+    pub metadata: HashMap<String, String>,
+    /// Whether S3-to-S3 copies keep the source headers (COPY) or use these (REPLACE)
+    pub metadata_directive: Option<MetadataDirective>,
+    /// Storage class of new objects; applies to copies with either directive
+    pub storage_class: Option<StorageClass>,
+    /// Tags of new objects as the URL-encoded `x-amz-tagging` value; copies
+    /// keep the source tags when unset
+    pub tagging: Option<String>,
+}
+
+impl ObjectHeaders {
+    /// Content-Type for an object named `name`: the explicit one or a guess
+    pub fn content_type_for(&self, name: &str) -> Option<String> {
+        self.content_type
+            .clone()
+            .or_else(|| guess_content_type(Path::new(name)).map(String::from))
+    }
+
+    pub fn expires(&self) -> Option<DateTime> {
+        self.expires.map(DateTime::from)
+    }
+
+    /// User metadata merged over `base` (e.g. `--preserve` attributes)
+    pub fn metadata_with(
+        &self,
+        base: Option<HashMap<String, String>>,
+    ) -> Option<HashMap<String, String>> {
+        if base.is_none() && self.metadata.is_empty() {
+            return None;
+        }
+        let mut metadata = base.unwrap_or_default();
+        metadata.extend(self.metadata.clone());
+        Some(metadata)
+    }
+
+    /// Whether any header or metadata was given explicitly
+    pub fn is_set(&self) -> bool {
+        self.content_type.is_some()
+            || self.cache_control.is_some()
+            || self.content_disposition.is_some()
+            || self.content_encoding.is_some()
+            || self.expires.is_some()
+            || !self.metadata.is_empty()
+    }
+
+    /// Headers and metadata of a copy source
+    pub fn from_source(head: &HeadObjectOutput) -> ObjectHeaders {
+        #[allow(deprecated)]
+        let expires = head
+            .expires()
+            .and_then(|time| SystemTime::try_from(*time).ok());
+        ObjectHeaders {
+            content_type: head.content_type().map(String::from),
+            cache_control: head.cache_control().map(String::from),
+            content_disposition: head.content_disposition().map(String::from),
+            content_encoding: head.content_encoding().map(String::from),
+            expires,
+            metadata: head.metadata().cloned().unwrap_or_default(),
+            ..Default::default()
+        }
+    }
+
+    /// These headers laid over a copy source's, like `aws s3 cp --copy-props`
+    ///
+    /// Headers given explicitly win and metadata is merged key by key; the
+    /// rest is kept from the source, so setting one header does not drop the
+    /// others, `--preserve` attributes or a symlink's mode.
+    pub fn over(&self, source: &ObjectHeaders) -> ObjectHeaders {
+        let mut metadata = source.metadata.clone();
+        metadata.extend(self.metadata.clone());
+        ObjectHeaders {
+            content_type: self.content_type.clone().or(source.content_type.clone()),
+            cache_control: self.cache_control.clone().or(source.cache_control.clone()),
+            content_disposition: self
+                .content_disposition
+                .clone()
+                .or(source.content_disposition.clone()),
+            content_encoding: self
+                .content_encoding
+                .clone()
+                .or(source.content_encoding.clone()),
+            expires: self.expires.or(source.expires),
+            metadata,
+            ..self.clone()
+        }
+    }
+
+    /// Directive for S3-to-S3 copies
+    ///
+    /// Defaults to REPLACE when headers were given (they would be ignored with
+    /// COPY) and to COPY otherwise, which keeps the source object's headers.
+    /// An implied REPLACE sends the source headers merged with these (see
+    /// [`ObjectHeaders::over`]).
+    pub fn copy_directive(&self) -> MetadataDirective {
+        self.metadata_directive.clone().unwrap_or(if self.is_set() {
+            MetadataDirective::Replace
+        } else {
+            MetadataDirective::Copy
+        })
+    }
+}
+
+/// Guess a Content-Type from a file extension (case-insensitive)
+pub fn guess_content_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    let content_type = match extension.as_str() {
+        "txt" | "text" | "log" => "text/plain",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "csv" => "text/csv",
+        "tsv" => "text/tab-separated-values",
+        "md" => "text/markdown",
+        "js" | "mjs" => "text/javascript",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "yaml" | "yml" => "application/yaml",
+        "pdf" => "application/pdf",
+        "wasm" => "application/wasm",
+        "parquet" => "application/vnd.apache.parquet",
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "ico" => "image/vnd.microsoft.icon",
+        "bmp" => "image/bmp",
+        "tif" | "tiff" => "image/tiff",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "ogg" => "audio/ogg",
+        "flac" => "audio/flac",
+        "m4a" => "audio/mp4",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "mov" => "video/quicktime",
+        "mkv" => "video/x-matroska",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "zip" => "application/zip",
+        "tar" => "application/x-tar",
+        "gz" | "tgz" => "application/gzip",
+        "bz2" => "application/x-bzip2",
+        "xz" => "application/x-xz",
+        "zst" => "application/zstd",
+        "7z" => "application/x-7z-compressed",
+        _ => return None,
+    };
+    Some(content_type)
+}
+
+/// Parse a `--metadata KEY=VALUE` argument
+pub fn parse_metadata(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_ascii_lowercase(), value.to_string()))
+        }
+        _ => Err(format!("Invalid metadata '{}': expected KEY=VALUE", value)),
+    }
+}
+
+/// Parse an `--expires` time: a duration from now ("30d") or a UTC date
+pub fn parse_expires(value: &str) -> Result<SystemTime, String> {
+    let value = value.trim();
+    let time = match filters::parse_duration(value) {
+        Some(duration) => SystemTime::now().checked_add(duration),
+        None => filters::parse_date(value),
+    };
+    time.ok_or_else(|| {
+        format!(
+            "Invalid time '{}': use a duration from now (e.g. 30d) or a date (YYYY-MM-DD[THH:MM:SS])",
+            value
+        )
+    })
+}
+
+/// Parse `--metadata-directive COPY|REPLACE`
+pub fn parse_metadata_directive(value: &str) -> Result<MetadataDirective, String> {
+    match value.to_uppercase().as_str() {
+        "COPY" => Ok(MetadataDirective::Copy),
+        "REPLACE" => Ok(MetadataDirective::Replace),
+        _ => Err(format!(
+            "Invalid metadata directive: {}. Use COPY or REPLACE",
+            value
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guess_content_type() {
+        assert_eq!(
+            guess_content_type(Path::new("a/index.HTML")),
+            Some("text/html")
+        );
+        assert_eq!(
+            guess_content_type(Path::new("data.tar.gz")),
+            Some("application/gzip")
+        );
+        assert_eq!(guess_content_type(Path::new("README")), None);
+
+        let headers = ObjectHeaders {
+            content_type: Some("text/x-custom".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            headers.content_type_for("photo.png").as_deref(),
+            Some("text/x-custom")
+        );
+        assert_eq!(
+            ObjectHeaders::default()
+                .content_type_for("photo.png")
+                .as_deref(),
+            Some("image/png")
+        );
+    }
+
+    #[test]
+    fn test_parse_arguments() {
+        assert_eq!(
+            parse_metadata("Owner=team=data").unwrap(),
+            ("owner".to_string(), "team=data".to_string())
+        );
+        assert!(parse_metadata("novalue").is_err());
+        assert!(parse_metadata("=x").is_err());
+
+        assert_eq!(
+            parse_expires("2030-01-01").unwrap(),
+            parse_expires("2030-01-01T00:00:00Z").unwrap()
+        );
+        assert!(parse_expires("1d").unwrap() > SystemTime::now());
+        assert!(parse_expires("soon").is_err());
+
+        assert_eq!(
+            parse_metadata_directive("replace").unwrap(),
+            MetadataDirective::Replace
+        );
+        assert!(parse_metadata_directive("MERGE").is_err());
+    }
+
+    #[test]
+    fn test_copy_directive_and_metadata() {
+        let mut headers = ObjectHeaders::default();
+        assert_eq!(headers.copy_directive(), MetadataDirective::Copy);
+        assert_eq!(headers.metadata_with(None), None);
+
+        headers
+            .metadata
+            .insert("mtime".to_string(), "1".to_string());
+        assert_eq!(headers.copy_directive(), MetadataDirective::Replace);
+
+        let base = HashMap::from([
+            ("mtime".to_string(), "5".to_string()),
+            ("mode".to_string(), "420".to_string()),
+        ]);
+        let merged = headers.metadata_with(Some(base)).unwrap();
+        assert_eq!(merged["mtime"], "1");
+        assert_eq!(merged["mode"], "420");
+    }
+
+    #[test]
+    fn test_headers_over_source() {
+        let source = ObjectHeaders {
+            content_type: Some("image/png".to_string()),
+            cache_control: Some("no-cache".to_string()),
+            metadata: HashMap::from([
+                ("mode".to_string(), "41471".to_string()),
+                ("owner".to_string(), "a".to_string()),
+            ]),
+            ..Default::default()
+        };
+        let headers = ObjectHeaders {
+            cache_control: Some("max-age=60".to_string()),
+            metadata: HashMap::from([("owner".to_string(), "b".to_string())]),
+            ..Default::default()
+        };
+
+        let merged = headers.over(&source);
+        assert_eq!(merged.content_type.as_deref(), Some("image/png"));
+        assert_eq!(merged.cache_control.as_deref(), Some("max-age=60"));
+        assert_eq!(merged.metadata["mode"], "41471");
+        assert_eq!(merged.metadata["owner"], "b");
+        assert_eq!(
+            merged.content_type_for("copy.txt").as_deref(),
+            Some("image/png")
+        );
+    }
+}
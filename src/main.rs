@@ -1,9 +1,25 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+use std::time::SystemTime;
 
+mod bucket_config;
+mod checksum;
 mod commands;
+mod compression;
+mod encryption;
+mod file_metadata;
 mod filters;
+mod headers;
+mod manifest;
 mod path_utils;
+mod presign;
 mod s3_client;
+mod sse;
+mod storage_class;
+mod tagging;
+mod temp_file;
+mod walk;
+mod wildcard;
 
 #[derive(Parser)]
 #[command(name = "hsc")]
@@ -30,16 +46,253 @@ struct Cli {
     #[arg(long, global = true)]
     region: Option<String>,
 
+    /// Master key for client-side encryption (32 raw bytes or base64);
+    /// defaults to HSC_ENCRYPTION_KEY_FILE
+    #[arg(long, global = true, value_name = "PATH")]
+    encryption_key_file: Option<PathBuf>,
+
+    /// Treat '*' and '?' in S3 keys as part of the key instead of as wildcards
+    #[arg(long, global = true)]
+    no_wildcards: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+/// Symlink handling for local directory walks
+#[derive(Args)]
+struct SymlinkArgs {
+    /// Follow symbolic links and transfer what they point to (default)
+    #[arg(long, overrides_with = "no_follow_symlinks")]
+    follow_symlinks: bool,
+    /// Store symbolic links as marker objects instead of following them
+    #[arg(long)]
+    no_follow_symlinks: bool,
+}
+
+impl SymlinkArgs {
+    fn follow(&self) -> bool {
+        !self.no_follow_symlinks
+    }
+}
+
+/// Include/exclude options for commands that operate on many files
+#[derive(Args)]
+struct FilterArgs {
+    /// Include files matching pattern (can be specified multiple times)
+    #[arg(long)]
+    include: Vec<String>,
+    /// Exclude files matching pattern (can be specified multiple times)
+    #[arg(long)]
+    exclude: Vec<String>,
+    /// Include files whose relative path matches a regular expression
+    #[arg(long, value_name = "REGEX")]
+    include_regex: Vec<String>,
+    /// Exclude files whose relative path matches a regular expression
+    #[arg(long, value_name = "REGEX")]
+    exclude_regex: Vec<String>,
+    /// Match patterns, filter rules and regexes case-insensitively
+    #[arg(long)]
+    ignore_case: bool,
+    /// Ordered rule "+ PATTERN" (include) or "- PATTERN" (exclude); the first match wins
+    #[arg(long, value_name = "RULE", allow_hyphen_values = true)]
+    filter: Vec<String>,
+    /// Read filter rules from a file, one per line
+    #[arg(long, value_name = "FILE")]
+    filter_from: Vec<PathBuf>,
+    /// Read exclude patterns from a file in .gitignore syntax
+    #[arg(long, value_name = "FILE")]
+    exclude_from: Vec<PathBuf>,
+    /// Only files of at least this size (e.g. 500K, 1G)
+    #[arg(long, value_name = "SIZE", value_parser = filters::parse_size)]
+    min_size: Option<u64>,
+    /// Only files of at most this size (e.g. 500K, 1G)
+    #[arg(long, value_name = "SIZE", value_parser = filters::parse_size)]
+    max_size: Option<u64>,
+    /// Only files modified within a duration (e.g. 7d, 12h) or after a date (YYYY-MM-DD)
+    #[arg(long, value_name = "TIME", value_parser = filters::parse_time)]
+    newer_than: Option<SystemTime>,
+    /// Only files modified longer ago than a duration (e.g. 90d) or before a date
+    #[arg(long, value_name = "TIME", value_parser = filters::parse_time)]
+    older_than: Option<SystemTime>,
+    /// Only objects in these storage classes (e.g. GLACIER,DEEP_ARCHIVE); local files count as STANDARD
+    #[arg(long, value_name = "CLASS", value_delimiter = ',')]
+    only_storage_class: Vec<String>,
+}
+
+impl FilterArgs {
+    /// Build the filter: rules from --filter, --filter-from and --exclude-from
+    /// are checked in that order before --include/--exclude
+    fn build(self) -> Result<filters::FileFilter, String> {
+        let mut filter = filters::FileFilter::new(self.include, self.exclude)?;
+        filter.set_ignore_case(self.ignore_case);
+        filter.add_regexes(&self.include_regex, &self.exclude_regex)?;
+        for rule in &self.filter {
+            filter.add_rule(rule)?;
+        }
+        for path in &self.filter_from {
+            filter.add_filter_file(path)?;
+        }
+        for path in &self.exclude_from {
+            filter.add_exclude_file(path)?;
+        }
+        filter.set_predicates(filters::Predicates {
+            min_size: self.min_size,
+            max_size: self.max_size,
+            newer_than: self.newer_than,
+            older_than: self.older_than,
+            storage_classes: self.only_storage_class,
+        });
+        Ok(filter)
+    }
+}
+
+/// HTTP headers and user metadata for uploaded and copied objects
+#[derive(Args)]
+struct HeaderArgs {
+    /// Content-Type of uploaded objects (guessed from the file extension by default)
+    #[arg(long, value_name = "TYPE")]
+    content_type: Option<String>,
+    /// Cache-Control header (e.g. "max-age=3600")
+    #[arg(long, value_name = "VALUE")]
+    cache_control: Option<String>,
+    /// Content-Disposition header (e.g. "attachment")
+    #[arg(long, value_name = "VALUE")]
+    content_disposition: Option<String>,
+    /// Content-Encoding header (e.g. "gzip")
+    #[arg(long, value_name = "VALUE")]
+    content_encoding: Option<String>,
+    /// Expires header: a duration from now (e.g. 30d) or a date (YYYY-MM-DD)
+    #[arg(long, value_name = "TIME", value_parser = headers::parse_expires)]
+    expires: Option<SystemTime>,
+    /// User metadata KEY=VALUE (can be specified multiple times)
+    #[arg(long, value_name = "KEY=VALUE", value_parser = headers::parse_metadata)]
+    metadata: Vec<(String, String)>,
+    /// Keep the source headers (COPY) or replace them (REPLACE) on S3-to-S3 copies
+    #[arg(long, value_name = "DIRECTIVE", value_parser = headers::parse_metadata_directive)]
+    metadata_directive: Option<aws_sdk_s3::types::MetadataDirective>,
+    /// Storage class of uploaded and copied objects (e.g. STANDARD_IA, GLACIER, DEEP_ARCHIVE)
+    #[arg(long, value_name = "CLASS", value_parser = storage_class::parse_storage_class)]
+    storage_class: Option<aws_sdk_s3::types::StorageClass>,
+    /// Tags of uploaded and copied objects: KEY=VALUE[&KEY2=VALUE2...] (copies keep the source tags by default)
+    #[arg(long, value_name = "TAGS", value_parser = tagging::parse_tags)]
+    tags: Option<tagging::TagSet>,
+}
+
+impl HeaderArgs {
+    fn build(self) -> headers::ObjectHeaders {
+        let headers = headers::ObjectHeaders {
+            content_type: self.content_type,
+            cache_control: self.cache_control,
+            content_disposition: self.content_disposition,
+            content_encoding: self.content_encoding,
+            expires: self.expires,
+            metadata: self.metadata.into_iter().collect(),
+            metadata_directive: self.metadata_directive,
+            storage_class: self.storage_class,
+            tagging: self.tags.map(|tags| tags.to_header()),
+        };
+        if headers.metadata_directive == Some(aws_sdk_s3::types::MetadataDirective::Copy)
+            && headers.is_set()
+        {
+            eprintln!(
+                "Warning: header and metadata options are ignored by S3-to-S3 copies with --metadata-directive COPY"
+            );
+        }
+        headers
+    }
+}
+
+/// Compression of uploaded files
+#[derive(Args, Default)]
+struct CompressArgs {
+    /// Compress uploads with gzip or zstd while streaming and set Content-Encoding
+    #[arg(long, value_name = "CODEC", value_parser = compression::parse_compress,
+          conflicts_with = "content_encoding")]
+    compress: Option<compression::Codec>,
+    /// Add .gz or .zst to the keys of compressed uploads
+    #[arg(long, requires = "compress")]
+    compress_suffix: bool,
+}
+
+/// SSE-C key for reading (and writing) objects encrypted with a customer key
+#[derive(Args)]
+struct SseCustomerArgs {
+    /// Use server-side encryption with a customer-provided key (AES256)
+    #[arg(long, value_name = "ALGORITHM", num_args = 0..=1, default_missing_value = "AES256",
+          value_parser = ["AES256"], requires = "sse_c_key")]
+    sse_c: Option<String>,
+    /// SSE-C key: 32 bytes, base64 encoded, or fileb://PATH to a file with the raw key
+    #[arg(long, value_name = "KEY", value_parser = sse::parse_customer_key, requires = "sse_c")]
+    sse_c_key: Option<sse::CustomerKey>,
+}
+
+/// Server-side encryption options for uploads and copies
+#[derive(Args)]
+struct SseArgs {
+    /// Server-side encryption of new objects: AES256 (SSE-S3), aws:kms or aws:kms:dsse (SSE-KMS)
+    #[arg(long, value_name = "ALGORITHM", value_parser = sse::parse_sse, conflicts_with = "sse_c")]
+    sse: Option<aws_sdk_s3::types::ServerSideEncryption>,
+    /// KMS key ID, ARN or alias for SSE-KMS (the bucket's default key otherwise)
+    #[arg(long, value_name = "KEY_ID")]
+    sse_kms_key_id: Option<String>,
+    #[command(flatten)]
+    customer: SseCustomerArgs,
+    /// The source of S3-to-S3 copies is encrypted with a customer-provided key (AES256)
+    #[arg(long, value_name = "ALGORITHM", num_args = 0..=1, default_missing_value = "AES256",
+          value_parser = ["AES256"], requires = "sse_c_copy_source_key")]
+    sse_c_copy_source: Option<String>,
+    /// SSE-C key of the copy source, in the same forms as --sse-c-key
+    #[arg(long, value_name = "KEY", value_parser = sse::parse_customer_key,
+          requires = "sse_c_copy_source")]
+    sse_c_copy_source_key: Option<sse::CustomerKey>,
+}
+
+impl SseArgs {
+    fn build(self) -> Result<sse::SseOptions, String> {
+        let kms = matches!(
+            self.sse,
+            Some(aws_sdk_s3::types::ServerSideEncryption::AwsKms)
+                | Some(aws_sdk_s3::types::ServerSideEncryption::AwsKmsDsse)
+        );
+        if self.sse_kms_key_id.is_some() && !kms {
+            return Err("--sse-kms-key-id requires --sse aws:kms or aws:kms:dsse".to_string());
+        }
+        Ok(sse::SseOptions {
+            sse: self.sse,
+            kms_key_id: self.sse_kms_key_id,
+            customer_key: self.customer.sse_c_key,
+            copy_source_key: self.sse_c_copy_source_key,
+        })
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Create an S3 bucket
     Mb {
         /// S3 URI (s3://bucket-name)
         bucket: String,
+        /// Enable S3 Object Lock on the new bucket (also enables versioning)
+        #[arg(long)]
+        object_lock: bool,
+        /// Enable versioning on the new bucket
+        #[arg(long)]
+        versioning: bool,
+        /// Canned ACL of the new bucket
+        #[arg(long, value_parser = ["private", "public-read", "public-read-write", "authenticated-read"])]
+        acl: Option<String>,
+        /// Succeed without changes if the bucket already exists and is owned by you
+        #[arg(long)]
+        ignore_existing: bool,
+        /// Tags of the new bucket: KEY=VALUE[&KEY2=VALUE2...]
+        #[arg(long, value_name = "TAGS", value_parser = tagging::parse_tags)]
+        tags: Option<tagging::TagSet>,
+    },
+    /// Get, put, delete or apply bucket configuration (versioning, lifecycle, CORS, policy...)
+    Bucket {
+        #[command(subcommand)]
+        action: BucketAction,
     },
     /// Remove an S3 bucket
     Rb {
@@ -56,28 +309,53 @@ enum Commands {
         /// List all objects recursively
         #[arg(long)]
         recursive: bool,
+        #[command(flatten)]
+        filters: FilterArgs,
+        /// Only list objects with tag KEY, or KEY=VALUE (can be specified multiple times)
+        #[arg(long = "tag", value_name = "KEY[=VALUE]", value_parser = tagging::parse_tag_predicate)]
+        tag_filters: Vec<tagging::TagPredicate>,
     },
     /// Copy files
     Cp {
-        /// Source path (local path or s3://bucket/key)
-        source: String,
-        /// Destination path (local path or s3://bucket/key)
+        /// Source paths (local paths or s3://bucket/key)
+        #[arg(required = true, num_args = 1..)]
+        sources: Vec<String>,
+        /// Destination path (local path or s3://bucket/key); a directory or prefix with several sources
         dest: String,
         /// Copy directories recursively
         #[arg(long)]
         recursive: bool,
-        /// Include files matching pattern (can be specified multiple times)
-        #[arg(long)]
-        include: Vec<String>,
-        /// Exclude files matching pattern (can be specified multiple times)
-        #[arg(long)]
-        exclude: Vec<String>,
+        /// Act on exactly the keys or paths listed in FILE ("-" for stdin), relative to the source
+        #[arg(long, value_name = "FILE", conflicts_with = "recursive")]
+        files_from: Option<String>,
+        /// Format of the --files-from manifest: csv (bucket,key[,versionId] rows) or lines; by default CSV only for .csv files
+        #[arg(long, value_name = "FORMAT", value_parser = manifest::parse_manifest_format, requires = "files_from")]
+        manifest_format: Option<manifest::ManifestFormat>,
+        #[command(flatten)]
+        filters: FilterArgs,
         /// Checksum mode (ENABLED for single object operations)
         #[arg(long)]
         checksum_mode: Option<String>,
         /// Checksum algorithm (CRC32, CRC32C, SHA1, SHA256)
         #[arg(long)]
         checksum_algorithm: Option<String>,
+        /// Preserve mtime, mode and ownership via object metadata
+        #[arg(long)]
+        preserve: bool,
+        #[command(flatten)]
+        symlinks: SymlinkArgs,
+        /// Upload empty directories as `prefix/` marker objects and recreate them on download
+        #[arg(long)]
+        dir_markers: bool,
+        /// Transfer GLACIER and DEEP_ARCHIVE objects instead of skipping them (they must be restored)
+        #[arg(long)]
+        force_glacier_transfer: bool,
+        #[command(flatten)]
+        headers: HeaderArgs,
+        #[command(flatten)]
+        sse: SseArgs,
+        #[command(flatten)]
+        compress: CompressArgs,
     },
     /// Synchronize directories
     Sync {
@@ -85,42 +363,113 @@ enum Commands {
         source: String,
         /// Destination path (local path or s3://bucket/prefix)
         dest: String,
-        /// Include files matching pattern (can be specified multiple times)
+        #[command(flatten)]
+        filters: FilterArgs,
+        /// Preserve mtime, mode and ownership via object metadata
         #[arg(long)]
-        include: Vec<String>,
-        /// Exclude files matching pattern (can be specified multiple times)
+        preserve: bool,
+        #[command(flatten)]
+        symlinks: SymlinkArgs,
+        /// Upload empty directories as `prefix/` marker objects and recreate them on download
         #[arg(long)]
-        exclude: Vec<String>,
+        dir_markers: bool,
+        /// Transfer GLACIER and DEEP_ARCHIVE objects instead of skipping them (they must be restored)
+        #[arg(long)]
+        force_glacier_transfer: bool,
+        #[command(flatten)]
+        headers: HeaderArgs,
+        #[command(flatten)]
+        sse: SseArgs,
+        #[command(flatten)]
+        compress: CompressArgs,
     },
     /// Move files
     Mv {
-        /// Source path (local path or s3://bucket/key)
-        source: String,
-        /// Destination path (local path or s3://bucket/key)
+        /// Source paths (local paths or s3://bucket/key)
+        #[arg(required = true, num_args = 1..)]
+        sources: Vec<String>,
+        /// Destination path (local path or s3://bucket/key); a directory or prefix with several sources
         dest: String,
         /// Move directories recursively
         #[arg(long)]
         recursive: bool,
-        /// Include files matching pattern (can be specified multiple times)
+        #[command(flatten)]
+        filters: FilterArgs,
+        /// Preserve mtime, mode and ownership via object metadata
+        #[arg(long)]
+        preserve: bool,
+        #[command(flatten)]
+        symlinks: SymlinkArgs,
+        /// Upload empty directories as `prefix/` marker objects and recreate them on download
         #[arg(long)]
-        include: Vec<String>,
-        /// Exclude files matching pattern (can be specified multiple times)
+        dir_markers: bool,
+        /// Transfer GLACIER and DEEP_ARCHIVE objects instead of skipping them (they must be restored)
         #[arg(long)]
-        exclude: Vec<String>,
+        force_glacier_transfer: bool,
+        #[command(flatten)]
+        headers: HeaderArgs,
+        #[command(flatten)]
+        sse: SseArgs,
     },
     /// Remove S3 objects
     Rm {
-        /// S3 URI (s3://bucket/key)
-        path: String,
-        /// Remove objects recursively
+        /// S3 URIs (s3://bucket/key)
+        #[arg(required = true, num_args = 1..)]
+        paths: Vec<String>,
+        /// Remove objects recursively (required for wildcards)
         #[arg(long)]
         recursive: bool,
-        /// Include files matching pattern (can be specified multiple times)
-        #[arg(long)]
-        include: Vec<String>,
-        /// Exclude files matching pattern (can be specified multiple times)
+        /// Act on exactly the keys or paths listed in FILE ("-" for stdin), relative to the path
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["recursive", "tag_filters"])]
+        files_from: Option<String>,
+        /// Format of the --files-from manifest: csv (bucket,key[,versionId] rows) or lines; by default CSV only for .csv files
+        #[arg(long, value_name = "FORMAT", value_parser = manifest::parse_manifest_format, requires = "files_from")]
+        manifest_format: Option<manifest::ManifestFormat>,
+        #[command(flatten)]
+        filters: FilterArgs,
+        /// Only remove objects with tag KEY, or KEY=VALUE (can be specified multiple times)
+        #[arg(long = "tag", value_name = "KEY[=VALUE]", value_parser = tagging::parse_tag_predicate)]
+        tag_filters: Vec<tagging::TagPredicate>,
+    },
+    /// Get, set or delete object tags
+    Tag {
+        #[command(subcommand)]
+        action: TagAction,
+    },
+    /// Generate a presigned URL, or a presigned POST form for browser uploads
+    Presign {
+        /// S3 URI (s3://bucket/key; with --post also a prefix ending in '/')
+        path: String,
+        /// How long the URL stays valid (e.g. 900, 30m, 12h, 7d; at most 7d)
+        #[arg(long, value_name = "DURATION", default_value = "1h",
+              value_parser = presign::parse_expires_in)]
+        expires_in: std::time::Duration,
+        /// HTTP method the URL is signed for: GET or PUT
+        #[arg(long, default_value = "GET", value_parser = presign::parse_method)]
+        method: presign::PresignMethod,
+        /// Print a presigned POST policy (URL and form fields as JSON) instead
+        #[arg(long, conflicts_with = "method")]
+        post: bool,
+        /// Sizes POST uploads must be within, e.g. 1-10M
+        #[arg(long, value_name = "MIN-MAX", value_parser = presign::parse_length_range,
+              requires = "post")]
+        content_length_range: Option<(u64, u64)>,
+    },
+    /// Restore archived (GLACIER, DEEP_ARCHIVE) objects so they can be read
+    Restore {
+        /// S3 URI (s3://bucket/key, or a prefix with --recursive)
+        path: String,
+        /// Restore every archived object below the prefix
         #[arg(long)]
-        exclude: Vec<String>,
+        recursive: bool,
+        /// Number of days the restored copy stays available
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(i32).range(1..))]
+        days: i32,
+        /// Retrieval tier: Bulk, Standard or Expedited
+        #[arg(long, default_value = "Standard", value_parser = storage_class::parse_tier)]
+        tier: aws_sdk_s3::types::Tier,
+        #[command(flatten)]
+        filters: FilterArgs,
     },
     /// Display file or object information
     Stat {
@@ -129,12 +478,24 @@ enum Commands {
         /// Stat objects recursively
         #[arg(long)]
         recursive: bool,
+        /// Act on exactly the keys or paths listed in FILE ("-" for stdin), relative to the path
+        #[arg(long, value_name = "FILE", conflicts_with = "recursive")]
+        files_from: Option<String>,
+        /// Format of the --files-from manifest: csv (bucket,key[,versionId] rows) or lines; by default CSV only for .csv files
+        #[arg(long, value_name = "FORMAT", value_parser = manifest::parse_manifest_format, requires = "files_from")]
+        manifest_format: Option<manifest::ManifestFormat>,
         /// Checksum mode (ENABLED for local files)
         #[arg(long)]
         checksum_mode: Option<String>,
         /// Checksum algorithm (CRC32, CRC32C, SHA1, SHA256)
         #[arg(long)]
         checksum_algorithm: Option<String>,
+        #[command(flatten)]
+        symlinks: SymlinkArgs,
+        #[command(flatten)]
+        filters: FilterArgs,
+        #[command(flatten)]
+        sse: SseCustomerArgs,
     },
     /// Compare directories or buckets and show differences
     Diff {
@@ -145,12 +506,13 @@ enum Commands {
         /// Compare object contents using ETag/checksums (slower)
         #[arg(long)]
         compare_content: bool,
-        /// Include files matching pattern (can be specified multiple times)
-        #[arg(long)]
-        include: Vec<String>,
-        /// Exclude files matching pattern (can be specified multiple times)
+        #[command(flatten)]
+        filters: FilterArgs,
+        #[command(flatten)]
+        symlinks: SymlinkArgs,
+        /// Compare empty directories and `prefix/` marker objects
         #[arg(long)]
-        exclude: Vec<String>,
+        dir_markers: bool,
     },
     /// Concatenate and print file or object content to STDOUT
     Cat {
@@ -165,6 +527,17 @@ enum Commands {
         /// Number of bytes to read (used with --offset)
         #[arg(long)]
         size: Option<u64>,
+        /// Print exactly the keys or paths listed in FILE ("-" for stdin), relative to the path
+        #[arg(long, value_name = "FILE")]
+        files_from: Option<String>,
+        /// Format of the --files-from manifest: csv (bucket,key[,versionId] rows) or lines; by default CSV only for .csv files
+        #[arg(long, value_name = "FORMAT", value_parser = manifest::parse_manifest_format, requires = "files_from")]
+        manifest_format: Option<manifest::ManifestFormat>,
+        /// Decompress .gz, .zst and .bz2 files and objects that have no Content-Encoding
+        #[arg(long)]
+        decompress: bool,
+        #[command(flatten)]
+        sse: SseCustomerArgs,
     },
     /// Compare two files or objects byte-by-byte
     Cmp {
@@ -181,12 +554,169 @@ enum Commands {
         /// Number of bytes to compare (used with --offset)
         #[arg(long)]
         size: Option<u64>,
+        #[command(flatten)]
+        sse: SseCustomerArgs,
+    },
+    /// Verify a local directory against an S3 prefix by checksum
+    Verify {
+        /// Local path (file or directory)
+        local: String,
+        /// S3 URI (s3://bucket/prefix)
+        remote: String,
+        #[command(flatten)]
+        filters: FilterArgs,
+        #[command(flatten)]
+        symlinks: SymlinkArgs,
+    },
+}
+
+/// `bucket` subcommands
+#[derive(Subcommand)]
+enum BucketAction {
+    /// Print a bucket setting as JSON or YAML
+    Get {
+        /// versioning, lifecycle, cors, policy, public-access-block, ownership-controls, encryption or tags
+        #[arg(value_parser = bucket_config::parse_setting)]
+        setting: bucket_config::Setting,
+        /// S3 URI (s3://bucket-name)
+        bucket: String,
+        /// Output format: json or yaml
+        #[arg(long, default_value = "json", value_parser = bucket_config::parse_format)]
+        format: bucket_config::Format,
+    },
+    /// Set a bucket setting from a JSON or YAML file
+    Put {
+        /// versioning, lifecycle, cors, policy, public-access-block, ownership-controls, encryption or tags
+        #[arg(value_parser = bucket_config::parse_setting)]
+        setting: bucket_config::Setting,
+        /// S3 URI (s3://bucket-name)
+        bucket: String,
+        /// Configuration document (.json, .yaml or .yml; "-" for stdin)
+        #[arg(short, long, value_name = "FILE")]
+        file: String,
+    },
+    /// Remove a bucket setting
+    Delete {
+        /// lifecycle, cors, policy, public-access-block, ownership-controls, encryption or tags
+        #[arg(value_parser = bucket_config::parse_setting)]
+        setting: bucket_config::Setting,
+        /// S3 URI (s3://bucket-name)
+        bucket: String,
+    },
+    /// Compare buckets against a configuration file and apply the differences
+    Apply {
+        /// Desired bucket configuration (.json, .yaml or .yml; "-" for stdin)
+        #[arg(short, long, value_name = "FILE")]
+        file: String,
+        /// Create and change buckets to match instead of only printing the plan
+        #[arg(long)]
+        yes: bool,
+        /// Exit with status 1 if any bucket differs, changing nothing
+        #[arg(long, conflicts_with = "yes")]
+        check: bool,
     },
 }
 
+/// `tag` subcommands
+#[derive(Subcommand)]
+enum TagAction {
+    /// Print the tags of objects
+    Get {
+        /// S3 URI (s3://bucket/key, a wildcard, or a prefix with --recursive)
+        path: String,
+        /// Print the tags of every object below the prefix
+        #[arg(long)]
+        recursive: bool,
+        #[command(flatten)]
+        filters: FilterArgs,
+    },
+    /// Replace the tags of objects
+    Set {
+        /// S3 URI (s3://bucket/key, a wildcard, or a prefix with --recursive)
+        path: String,
+        /// Tags: KEY=VALUE[&KEY2=VALUE2...]
+        #[arg(value_parser = tagging::parse_tags)]
+        tags: tagging::TagSet,
+        /// Keep existing tags that are not given (given tags are overwritten)
+        #[arg(long)]
+        merge: bool,
+        /// Tag every object below the prefix
+        #[arg(long)]
+        recursive: bool,
+        #[command(flatten)]
+        filters: FilterArgs,
+    },
+    /// Remove all tags from objects
+    Delete {
+        /// S3 URI (s3://bucket/key, a wildcard, or a prefix with --recursive)
+        path: String,
+        /// Remove the tags of every object below the prefix
+        #[arg(long)]
+        recursive: bool,
+        #[command(flatten)]
+        filters: FilterArgs,
+    },
+}
+
+/// Build transfer options from the client configuration and command flags
+#[allow(clippy::too_many_arguments)]
+fn transfer_options(
+    config: &s3_client::S3ClientConfig,
+    preserve: bool,
+    follow_symlinks: bool,
+    dir_markers: bool,
+    force_glacier_transfer: bool,
+    headers: HeaderArgs,
+    sse: SseArgs,
+    compress: CompressArgs,
+    encryption: Option<encryption::MasterKey>,
+    literal_keys: bool,
+) -> Result<commands::cp::TransferOptions, String> {
+    if compress.compress.is_some() && encryption.is_some() {
+        return Err("--compress cannot be combined with client-side encryption".to_string());
+    }
+    Ok(commands::cp::TransferOptions {
+        multipart_threshold: config.multipart_threshold,
+        multipart_chunksize: config.multipart_chunksize,
+        preserve,
+        follow_symlinks,
+        dir_markers,
+        remove_source: false,
+        headers: headers.build(),
+        force_glacier_transfer,
+        sse: sse.build()?,
+        encryption,
+        compress: compress.compress,
+        compress_suffix: compress.compress_suffix,
+        literal_keys,
+    })
+}
+
+/// Load the client-side encryption key from `--encryption-key-file` or
+/// `HSC_ENCRYPTION_KEY_FILE`, if either is set
+fn load_encryption_key(path: Option<PathBuf>) -> Result<Option<encryption::MasterKey>, String> {
+    path.or_else(|| std::env::var_os("HSC_ENCRYPTION_KEY_FILE").map(PathBuf::from))
+        .map(|path| encryption::MasterKey::load(&path))
+        .transpose()
+}
+
+/// Read a `--files-from` manifest; filters do not apply to listed entries
+fn read_manifest(
+    files_from: &str,
+    format: Option<manifest::ManifestFormat>,
+    filter: &filters::FileFilter,
+) -> Result<Vec<manifest::ManifestEntry>, String> {
+    if filter.has_filters() {
+        eprintln!("Warning: filters are ignored with --files-from");
+    }
+    manifest::read_manifest(files_from, format)
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
+    let literal_keys = cli.no_wildcards;
+    let encryption = load_encryption_key(cli.encryption_key_file)?;
 
     // Initialize S3 client with global options
     let client_config = s3_client::S3ClientConfig {
@@ -202,104 +732,454 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let client_config_clone = client_config.clone();
     let client = s3_client::create_s3_client(client_config).await?;
 
+    // Remove partial downloads if interrupted
+    temp_file::cleanup_on_signal();
+
     match cli.command {
-        Commands::Mb { bucket } => commands::mb::make_bucket(&client, &bucket).await,
+        Commands::Mb {
+            bucket,
+            object_lock,
+            versioning,
+            acl,
+            ignore_existing,
+            tags,
+        } => {
+            let opts = commands::mb::BucketOptions {
+                object_lock,
+                versioning,
+                acl: acl.map(|acl| aws_sdk_s3::types::BucketCannedAcl::from(acl.as_str())),
+                ignore_existing,
+                tags,
+            };
+            commands::mb::make_bucket(&client, &bucket, &opts).await
+        }
+        Commands::Bucket { action } => match action {
+            BucketAction::Get {
+                setting,
+                bucket,
+                format,
+            } => commands::bucket::get(&client, &bucket, setting, format).await,
+            BucketAction::Put {
+                setting,
+                bucket,
+                file,
+            } => commands::bucket::put(&client, &bucket, setting, &file).await,
+            BucketAction::Delete { setting, bucket } => {
+                commands::bucket::delete(&client, &bucket, setting).await
+            }
+            BucketAction::Apply { file, yes, check } => {
+                commands::bucket::apply(&client, &file, yes, check).await
+            }
+        },
         Commands::Rb { bucket, force } => {
             commands::rb::remove_bucket(&client, &bucket, force).await
         }
-        Commands::Ls { path, recursive } => commands::ls::list(&client, path, recursive).await,
+        Commands::Ls {
+            path,
+            recursive,
+            filters,
+            tag_filters,
+        } => {
+            let tags = tagging::TagFilter::new(tag_filters);
+            commands::ls::list(
+                &client,
+                path,
+                recursive,
+                &filters.build()?,
+                &tags,
+                literal_keys,
+            )
+            .await
+        }
         Commands::Cp {
-            source,
+            sources,
+            dest,
+            files_from: Some(files_from),
+            manifest_format,
+            filters,
+            checksum_mode,
+            checksum_algorithm,
+            preserve,
+            symlinks,
+            dir_markers,
+            force_glacier_transfer,
+            headers,
+            sse,
+            compress,
+            ..
+        } => {
+            let entries = read_manifest(&files_from, manifest_format, &filters.build()?)?;
+            commands::cp::copy_manifest(
+                &client,
+                &sources,
+                &dest,
+                &entries,
+                checksum_mode,
+                checksum_algorithm,
+                &transfer_options(
+                    &client_config_clone,
+                    preserve,
+                    symlinks.follow(),
+                    dir_markers,
+                    force_glacier_transfer,
+                    headers,
+                    sse,
+                    compress,
+                    encryption,
+                    literal_keys,
+                )?,
+            )
+            .await
+        }
+        Commands::Cp {
+            sources,
             dest,
             recursive,
-            include,
-            exclude,
+            files_from: None,
+            manifest_format: _,
+            filters,
             checksum_mode,
             checksum_algorithm,
+            preserve,
+            symlinks,
+            dir_markers,
+            force_glacier_transfer,
+            headers,
+            sse,
+            compress,
         } => {
             commands::cp::copy(
                 &client,
-                &source,
+                &sources,
                 &dest,
                 recursive,
-                include,
-                exclude,
+                &filters.build()?,
                 checksum_mode,
                 checksum_algorithm,
-                client_config_clone.multipart_threshold,
-                client_config_clone.multipart_chunksize,
+                &transfer_options(
+                    &client_config_clone,
+                    preserve,
+                    symlinks.follow(),
+                    dir_markers,
+                    force_glacier_transfer,
+                    headers,
+                    sse,
+                    compress,
+                    encryption,
+                    literal_keys,
+                )?,
             )
             .await
         }
         Commands::Sync {
             source,
             dest,
-            include,
-            exclude,
+            filters,
+            preserve,
+            symlinks,
+            dir_markers,
+            force_glacier_transfer,
+            headers,
+            sse,
+            compress,
         } => {
             commands::sync::sync(
                 &client,
                 &source,
                 &dest,
-                include,
-                exclude,
-                client_config_clone.multipart_threshold,
-                client_config_clone.multipart_chunksize,
+                &filters.build()?,
+                &transfer_options(
+                    &client_config_clone,
+                    preserve,
+                    symlinks.follow(),
+                    dir_markers,
+                    force_glacier_transfer,
+                    headers,
+                    sse,
+                    compress,
+                    encryption,
+                    literal_keys,
+                )?,
             )
             .await
         }
         Commands::Mv {
-            source,
+            sources,
             dest,
             recursive,
-            include,
-            exclude,
+            filters,
+            preserve,
+            symlinks,
+            dir_markers,
+            force_glacier_transfer,
+            headers,
+            sse,
         } => {
             commands::mv::move_files(
                 &client,
-                &source,
+                &sources,
                 &dest,
                 recursive,
-                include,
-                exclude,
-                client_config_clone.multipart_threshold,
-                client_config_clone.multipart_chunksize,
+                &filters.build()?,
+                &transfer_options(
+                    &client_config_clone,
+                    preserve,
+                    symlinks.follow(),
+                    dir_markers,
+                    force_glacier_transfer,
+                    headers,
+                    sse,
+                    CompressArgs::default(),
+                    encryption,
+                    literal_keys,
+                )?,
             )
             .await
         }
         Commands::Rm {
+            paths,
+            files_from: Some(files_from),
+            manifest_format,
+            filters,
+            ..
+        } => {
+            let entries = read_manifest(&files_from, manifest_format, &filters.build()?)?;
+            commands::rm::remove_manifest(&client, &paths, &entries).await
+        }
+        Commands::Rm {
+            paths,
+            recursive,
+            files_from: None,
+            manifest_format: _,
+            filters,
+            tag_filters,
+        } => {
+            let tags = tagging::TagFilter::new(tag_filters);
+            commands::rm::remove(
+                &client,
+                &paths,
+                recursive,
+                &filters.build()?,
+                &tags,
+                literal_keys,
+            )
+            .await
+        }
+        Commands::Tag { action } => {
+            let (path, recursive, filters, operation) = match action {
+                TagAction::Get {
+                    path,
+                    recursive,
+                    filters,
+                } => (path, recursive, filters, commands::tag::TagOperation::Get),
+                TagAction::Set {
+                    path,
+                    tags,
+                    merge,
+                    recursive,
+                    filters,
+                } => (
+                    path,
+                    recursive,
+                    filters,
+                    commands::tag::TagOperation::Set { tags, merge },
+                ),
+                TagAction::Delete {
+                    path,
+                    recursive,
+                    filters,
+                } => (
+                    path,
+                    recursive,
+                    filters,
+                    commands::tag::TagOperation::Delete,
+                ),
+            };
+            commands::tag::tag(
+                &client,
+                &path,
+                recursive,
+                &filters.build()?,
+                &operation,
+                literal_keys,
+            )
+            .await
+        }
+        Commands::Presign {
+            path,
+            expires_in,
+            post: true,
+            content_length_range,
+            ..
+        } => {
+            commands::presign::presign_post(
+                &client,
+                &client_config_clone,
+                &path,
+                expires_in,
+                content_length_range,
+            )
+            .await
+        }
+        Commands::Presign {
+            path,
+            expires_in,
+            method,
+            ..
+        } => commands::presign::presign(&client, &path, expires_in, method).await,
+        Commands::Restore {
             path,
             recursive,
-            include,
-            exclude,
-        } => commands::rm::remove(&client, &path, recursive, include, exclude).await,
+            days,
+            tier,
+            filters,
+        } => {
+            commands::restore::restore(
+                &client,
+                &path,
+                recursive,
+                &filters.build()?,
+                days,
+                tier,
+                literal_keys,
+            )
+            .await
+        }
+        Commands::Stat {
+            path,
+            files_from: Some(files_from),
+            manifest_format,
+            checksum_mode,
+            checksum_algorithm,
+            symlinks,
+            filters,
+            sse,
+            ..
+        } => {
+            let entries = read_manifest(&files_from, manifest_format, &filters.build()?)?;
+            commands::stat::stat_manifest(
+                &client,
+                &path,
+                &entries,
+                checksum_mode,
+                checksum_algorithm,
+                symlinks.follow(),
+                sse.sse_c_key.as_ref(),
+            )
+            .await
+        }
         Commands::Stat {
             path,
             recursive,
+            files_from: None,
+            manifest_format: _,
             checksum_mode,
             checksum_algorithm,
+            symlinks,
+            filters,
+            sse,
         } => {
-            commands::stat::stat(&client, &path, recursive, checksum_mode, checksum_algorithm).await
+            commands::stat::stat(
+                &client,
+                &path,
+                recursive,
+                checksum_mode,
+                checksum_algorithm,
+                symlinks.follow(),
+                &filters.build()?,
+                sse.sse_c_key.as_ref(),
+                literal_keys,
+            )
+            .await
         }
         Commands::Diff {
             source,
             dest,
             compare_content,
-            include,
-            exclude,
-        } => commands::diff::diff(&client, &source, &dest, compare_content, include, exclude).await,
+            filters,
+            symlinks,
+            dir_markers,
+        } => {
+            let opts = commands::diff::DiffOptions {
+                compare_content,
+                follow_symlinks: symlinks.follow(),
+                dir_markers,
+                client_side_encryption: encryption.is_some(),
+            };
+            commands::diff::diff(&client, &source, &dest, &filters.build()?, opts).await
+        }
+        Commands::Cat {
+            path,
+            range,
+            offset,
+            size,
+            files_from: Some(files_from),
+            manifest_format,
+            decompress,
+            sse,
+        } => {
+            let entries = manifest::read_manifest(&files_from, manifest_format)?;
+            commands::cat::cat_manifest(
+                &client,
+                &path,
+                &entries,
+                range,
+                offset,
+                size,
+                sse.sse_c_key.as_ref(),
+                encryption.as_ref(),
+                decompress,
+            )
+            .await
+        }
         Commands::Cat {
             path,
             range,
             offset,
             size,
-        } => commands::cat::cat(&client, &path, range, offset, size).await,
+            files_from: None,
+            manifest_format: _,
+            decompress,
+            sse,
+        } => {
+            commands::cat::cat(
+                &client,
+                &path,
+                range,
+                offset,
+                size,
+                sse.sse_c_key.as_ref(),
+                encryption.as_ref(),
+                decompress,
+                literal_keys,
+            )
+            .await
+        }
         Commands::Cmp {
             path1,
             path2,
             range,
             offset,
             size,
-        } => commands::cmp::cmp(&client, &path1, &path2, range, offset, size).await,
+            sse,
+        } => {
+            let customer_key = sse.sse_c_key.as_ref();
+            commands::cmp::cmp(&client, &path1, &path2, range, offset, size, customer_key).await
+        }
+        Commands::Verify {
+            local,
+            remote,
+            filters,
+            symlinks,
+        } => {
+            commands::verify::verify(
+                &client,
+                &local,
+                &remote,
+                &filters.build()?,
+                symlinks.follow(),
+            )
+            .await
+        }
     }
 }
@@ -0,0 +1,298 @@
+use crate::path_utils::{join_s3_key, parse_s3_uri, PathType};
+use std::io::Read;
+use std::path::Path;
+
+/// One entry of a `--files-from` manifest
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManifestEntry {
+    /// Bucket named by the entry itself (CSV rows and `s3://` lines)
+    pub bucket: Option<String>,
+    /// Key or path, relative to the base path unless `bucket` is set
+    pub key: String,
+    pub version_id: Option<String>,
+}
+
+impl ManifestEntry {
+    /// Resolve the entry against the command's base path
+    ///
+    /// Returns the full path and the path relative to the base, which is used
+    /// to name the entry at a destination.
+    pub fn resolve(&self, base: &PathType) -> (PathType, &str) {
+        let path = match (&self.bucket, base) {
+            (Some(bucket), _) => PathType::S3 {
+                bucket: bucket.clone(),
+                key: self.key.clone(),
+            },
+            (None, PathType::S3 { bucket, key }) => PathType::S3 {
+                bucket: bucket.clone(),
+                key: join_s3_key(key, &self.key),
+            },
+            (None, PathType::Local(dir)) => {
+                PathType::Local(Path::new(dir).join(&self.key).to_string_lossy().to_string())
+            }
+        };
+        (path, self.key.trim_start_matches('/'))
+    }
+}
+
+/// How the entries of a manifest are written
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ManifestFormat {
+    /// S3 Batch Operations CSV: `bucket,key[,versionId]` rows with URL-encoded keys
+    Csv,
+    /// One key, path or `s3://` URI per line (or NUL-separated)
+    Lines,
+}
+
+/// Parse `--manifest-format csv|lines`
+pub fn parse_manifest_format(value: &str) -> Result<ManifestFormat, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "csv" => Ok(ManifestFormat::Csv),
+        "lines" => Ok(ManifestFormat::Lines),
+        _ => Err(format!(
+            "Invalid manifest format: {}. Use csv or lines",
+            value
+        )),
+    }
+}
+
+/// Read a manifest from a file, or from stdin when `source` is "-"
+///
+/// Entries are NUL-separated if the input contains a NUL byte and
+/// newline-separated otherwise. Without an explicit `format`, files ending in
+/// `.csv` are read as CSV and everything else (including stdin) as lines.
+pub fn read_manifest(
+    source: &str,
+    format: Option<ManifestFormat>,
+) -> Result<Vec<ManifestEntry>, String> {
+    let mut content = Vec::new();
+    if source == "-" {
+        std::io::stdin()
+            .read_to_end(&mut content)
+            .map_err(|e| format!("Cannot read manifest from stdin: {}", e))?;
+    } else {
+        content =
+            std::fs::read(source).map_err(|e| format!("Cannot read manifest {}: {}", source, e))?;
+    }
+
+    let content = String::from_utf8(content)
+        .map_err(|_| format!("Manifest {} is not valid UTF-8", source))?;
+    let csv = match format {
+        Some(format) => format == ManifestFormat::Csv,
+        None => source.to_ascii_lowercase().ends_with(".csv"),
+    };
+
+    parse_manifest(&content, csv).map_err(|e| format!("Manifest {}: {}", source, e))
+}
+
+fn parse_manifest(content: &str, csv: bool) -> Result<Vec<ManifestEntry>, String> {
+    if content.contains('\0') {
+        return content
+            .split('\0')
+            .filter(|entry| !entry.is_empty())
+            .map(parse_line)
+            .collect();
+    }
+
+    content
+        .lines()
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(n, line)| {
+            if csv {
+                parse_csv_row(line).map_err(|e| format!("line {}: {}", n + 1, e))
+            } else {
+                parse_line(line)
+            }
+        })
+        .collect()
+}
+
+/// A plain entry: a key or path, or a full `s3://bucket/key` URI
+fn parse_line(line: &str) -> Result<ManifestEntry, String> {
+    if line.starts_with("s3://") {
+        if let PathType::S3 { bucket, key } = parse_s3_uri(line)? {
+            return Ok(ManifestEntry {
+                bucket: Some(bucket),
+                key,
+                version_id: None,
+            });
+        }
+    }
+
+    Ok(ManifestEntry {
+        bucket: None,
+        key: line.to_string(),
+        version_id: None,
+    })
+}
+
+/// A `bucket,key[,versionId]` row; fields may be double-quoted
+fn parse_csv_row(line: &str) -> Result<ManifestEntry, String> {
+    let fields = split_csv(line);
+    if fields.len() < 2 || fields.len() > 3 {
+        return Err(format!("expected bucket,key[,versionId], got '{}'", line));
+    }
+
+    let key = url_decode(&fields[1])?;
+    if fields[0].is_empty() || key.is_empty() {
+        return Err(format!("empty bucket or key in '{}'", line));
+    }
+
+    Ok(ManifestEntry {
+        bucket: Some(fields[0].clone()),
+        key,
+        version_id: fields.get(2).filter(|v| !v.is_empty()).cloned(),
+    })
+}
+
+fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// Decode a URL-encoded key (`%XX` escapes, '+' for space)
+fn url_decode(value: &str) -> Result<String, String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = value
+                    .get(i + 1..i + 3)
+                    .and_then(|h| u8::from_str_radix(h, 16).ok())
+                    .ok_or_else(|| format!("invalid URL encoding in '{}'", value))?;
+                decoded.push(hex);
+                i += 3;
+            }
+            b'+' => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            b => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8(decoded).map_err(|_| format!("key '{}' is not valid UTF-8", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(bucket: Option<&str>, key: &str, version_id: Option<&str>) -> ManifestEntry {
+        ManifestEntry {
+            bucket: bucket.map(|b| b.to_string()),
+            key: key.to_string(),
+            version_id: version_id.map(|v| v.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_lines_and_nul() {
+        assert_eq!(
+            parse_manifest("a.txt\r\n\ndir/b c.txt\ns3://other/k\n", false).unwrap(),
+            vec![
+                entry(None, "a.txt", None),
+                entry(None, "dir/b c.txt", None),
+                entry(Some("other"), "k", None),
+            ]
+        );
+
+        assert_eq!(
+            parse_manifest("line\nbreak\0plain\0", false).unwrap(),
+            vec![entry(None, "line\nbreak", None), entry(None, "plain", None)]
+        );
+    }
+
+    #[test]
+    fn test_batch_operations_csv() {
+        let csv = "bkt,photos/a%20b.jpg\n\"bkt\",\"dir/x+y,z.txt\",v123\nbkt,k%2Bplus,\n";
+        assert_eq!(
+            parse_manifest(csv, true).unwrap(),
+            vec![
+                entry(Some("bkt"), "photos/a b.jpg", None),
+                entry(Some("bkt"), "dir/x y,z.txt", Some("v123")),
+                entry(Some("bkt"), "k+plus", None),
+            ]
+        );
+
+        assert!(parse_manifest("only-one-field\n", true).is_err());
+        assert!(parse_manifest("bkt,bad%zz\n", true).is_err());
+
+        assert_eq!(parse_manifest_format("CSV").unwrap(), ManifestFormat::Csv);
+        assert_eq!(
+            parse_manifest_format("lines").unwrap(),
+            ManifestFormat::Lines
+        );
+        assert!(parse_manifest_format("json").is_err());
+
+        // Without a .csv extension (as with stdin) CSV must be asked for
+        let path = std::env::temp_dir().join(format!("hsc-manifest-{}", std::process::id()));
+        std::fs::write(&path, "bkt,a%20b\n").unwrap();
+        let source = path.to_string_lossy();
+        assert_eq!(
+            read_manifest(&source, None).unwrap(),
+            vec![entry(None, "bkt,a%20b", None)]
+        );
+        assert_eq!(
+            read_manifest(&source, Some(ManifestFormat::Csv)).unwrap(),
+            vec![entry(Some("bkt"), "a b", None)]
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_resolve() {
+        let base = PathType::S3 {
+            bucket: "b".to_string(),
+            key: "prefix/".to_string(),
+        };
+        let relative_entry = entry(None, "a/b.txt", None);
+        let (path, relative) = relative_entry.resolve(&base);
+        assert_eq!(
+            path,
+            PathType::S3 {
+                bucket: "b".to_string(),
+                key: "prefix/a/b.txt".to_string()
+            }
+        );
+        assert_eq!(relative, "a/b.txt");
+
+        let bucket_entry = entry(Some("o"), "k/x", None);
+        let (path, relative) = bucket_entry.resolve(&base);
+        assert_eq!(
+            path,
+            PathType::S3 {
+                bucket: "o".to_string(),
+                key: "k/x".to_string()
+            }
+        );
+        assert_eq!(relative, "k/x");
+
+        let (path, _) = entry(None, "f.txt", None).resolve(&PathType::Local("dir".to_string()));
+        assert_eq!(path, PathType::Local("dir/f.txt".to_string()));
+    }
+}
@@ -60,6 +60,28 @@ pub fn normalize_s3_key(key: &str) -> String {
     key.trim_start_matches('/').to_string()
 }
 
+/// Check if an S3 key is a directory marker ("folder" object ending in '/')
+pub fn is_dir_marker(key: &str) -> bool {
+    key.ends_with('/')
+}
+
+/// Key of an object relative to the listed prefix (the source root)
+///
+/// When the key is the prefix itself, its last path component is returned,
+/// mirroring how a single local file is named relative to its own path.
+pub fn relative_key<'a>(key: &'a str, prefix: &str) -> &'a str {
+    let relative = match key.strip_prefix(prefix) {
+        Some(rest) if !prefix.is_empty() => rest.trim_start_matches('/'),
+        _ => key,
+    };
+
+    if relative.is_empty() {
+        key.rsplit('/').find(|c| !c.is_empty()).unwrap_or(key)
+    } else {
+        relative
+    }
+}
+
 /// Join S3 key components
 pub fn join_s3_key(prefix: &str, name: &str) -> String {
     if prefix.is_empty() {
@@ -71,6 +93,108 @@ pub fn join_s3_key(prefix: &str, name: &str) -> String {
     }
 }
 
+/// Pair each source with the destination it is copied or moved to
+///
+/// A single source keeps `dest` as given. With several sources, `dest` is a
+/// directory or prefix and each source goes below it under its own name, like
+/// POSIX `cp a b dir/`. Wildcard sources already expand into a directory, so
+/// they keep `dest` unchanged (unless `literal_keys` takes them literally).
+pub fn source_destinations(
+    sources: &[String],
+    dest: &str,
+    literal_keys: bool,
+) -> Result<Vec<(String, String)>, String> {
+    if sources.len() == 1 {
+        return Ok(vec![(sources[0].clone(), dest.to_string())]);
+    }
+
+    let dest_type = parse_path(dest)?;
+    sources
+        .iter()
+        .map(|source| {
+            if matches!(parse_path(source)?, PathType::S3 { ref key, .. } if crate::wildcard::has_wildcard(key, literal_keys))
+            {
+                return Ok((source.clone(), dest.to_string()));
+            }
+
+            let name = source_name(source)?;
+            let target = match &dest_type {
+                PathType::S3 { bucket, key } => {
+                    format!("s3://{}/{}", bucket, join_s3_key(key, &name))
+                }
+                PathType::Local(dir) => Path::new(dir).join(&name).to_string_lossy().to_string(),
+            };
+            Ok((source.clone(), target))
+        })
+        .collect()
+}
+
+/// Check a name against the S3 bucket naming rules
+///
+/// 3 to 63 lowercase letters, digits, dots and hyphens, starting and ending
+/// with a letter or digit, without ".." and not shaped like an IP address.
+/// Names with prefixes and suffixes S3 reserves are refused as well.
+pub fn validate_bucket_name(name: &str) -> Result<(), String> {
+    let invalid = |reason: &str| Err(format!("Invalid bucket name '{}': {}", name, reason));
+
+    if name.len() < 3 || name.len() > 63 {
+        return invalid("must be 3 to 63 characters long");
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '.' || c == '-')
+    {
+        return invalid("only lowercase letters, digits, '.' and '-' are allowed");
+    }
+    let alphanumeric = |c: Option<char>| c.is_some_and(|c| c.is_ascii_alphanumeric());
+    if !alphanumeric(name.chars().next()) || !alphanumeric(name.chars().last()) {
+        return invalid("must start and end with a letter or digit");
+    }
+    if name.contains("..") {
+        return invalid("must not contain '..'");
+    }
+    if name.parse::<std::net::Ipv4Addr>().is_ok() {
+        return invalid("must not be formatted as an IP address");
+    }
+    if ["xn--", "sthree-", "amzn-s3-demo-"]
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+        || ["-s3alias", "--ol-s3", ".mrap", "--x-s3", "--table-s3"]
+            .iter()
+            .any(|suffix| name.ends_with(suffix))
+    {
+        return invalid("uses a prefix or suffix reserved by S3");
+    }
+    Ok(())
+}
+
+/// Last path component of a local path or S3 URI (the bucket for a bucket root)
+fn source_name(source: &str) -> Result<String, String> {
+    let name = match parse_path(source)? {
+        PathType::S3 { bucket, key } => key
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .filter(|name| !name.is_empty())
+            .unwrap_or(&bucket)
+            .to_string(),
+        PathType::Local(path) => {
+            let path = Path::new(&path);
+            let name = match path.file_name() {
+                Some(name) => Some(name.to_os_string()),
+                // "." or ".." name the directory they resolve to
+                None => path
+                    .canonicalize()
+                    .ok()
+                    .and_then(|p| p.file_name().map(|n| n.to_os_string())),
+            };
+            name.and_then(|n| n.to_str().map(|n| n.to_string()))
+                .ok_or_else(|| format!("Cannot determine a destination name for '{}'", source))?
+        }
+    };
+    Ok(name)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -92,10 +216,81 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_validate_bucket_name() {
+        for name in ["my-bucket", "logs.example.com", "abc", "0data"] {
+            assert!(validate_bucket_name(name).is_ok(), "{}", name);
+        }
+        for name in [
+            "ab",
+            &"a".repeat(64),
+            "My-Bucket",
+            "under_score",
+            "-leading",
+            "trailing.",
+            "two..dots",
+            "192.168.1.1",
+            "xn--bucket",
+            "data-s3alias",
+        ] {
+            assert!(validate_bucket_name(name).is_err(), "{}", name);
+        }
+    }
+
     #[test]
     fn test_join_s3_key() {
         assert_eq!(join_s3_key("prefix", "file.txt"), "prefix/file.txt");
         assert_eq!(join_s3_key("prefix/", "file.txt"), "prefix/file.txt");
         assert_eq!(join_s3_key("", "file.txt"), "file.txt");
     }
+
+    #[test]
+    fn test_relative_key() {
+        assert_eq!(relative_key("data/a/b.txt", "data/"), "a/b.txt");
+        assert_eq!(relative_key("data/a/b.txt", "data"), "a/b.txt");
+        assert_eq!(relative_key("data/a/b.txt", ""), "data/a/b.txt");
+        assert_eq!(relative_key("data/b.txt", "data/b.txt"), "b.txt");
+    }
+
+    #[test]
+    fn test_source_destinations() {
+        let sources = |s: &[&str]| s.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            source_destinations(&sources(&["a.txt"]), "s3://b/x.txt", false).unwrap(),
+            vec![("a.txt".to_string(), "s3://b/x.txt".to_string())]
+        );
+
+        assert_eq!(
+            source_destinations(
+                &sources(&["dir/a.txt", "s3://src/logs/", "s3://src", "s3://src/p/*.gz"]),
+                "s3://b/out/",
+                false
+            )
+            .unwrap(),
+            vec![
+                ("dir/a.txt".to_string(), "s3://b/out/a.txt".to_string()),
+                ("s3://src/logs/".to_string(), "s3://b/out/logs".to_string()),
+                ("s3://src".to_string(), "s3://b/out/src".to_string()),
+                ("s3://src/p/*.gz".to_string(), "s3://b/out/".to_string()),
+            ]
+        );
+
+        assert_eq!(
+            source_destinations(&sources(&["s3://b/k/a.txt", "/tmp/c"]), "out", false).unwrap(),
+            vec![
+                ("s3://b/k/a.txt".to_string(), "out/a.txt".to_string()),
+                ("/tmp/c".to_string(), "out/c".to_string()),
+            ]
+        );
+
+        // With --no-wildcards a key with '*' is named like any other
+        assert_eq!(
+            source_destinations(&sources(&["s3://src/p/*.gz", "/tmp/c"]), "out", true).unwrap(),
+            vec![
+                ("s3://src/p/*.gz".to_string(), "out/*.gz".to_string()),
+                ("/tmp/c".to_string(), "out/c".to_string()),
+            ]
+        );
+    }
 }
@@ -0,0 +1,249 @@
+use crate::filters;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::time::Duration;
+
+/// Longest validity SigV4 allows for presigned requests
+pub const MAX_EXPIRES_IN: Duration = Duration::from_secs(7 * 86400);
+
+/// Parse `--expires-in`: a duration ("90s", "30m", "12h", "7d") or seconds
+pub fn parse_expires_in(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let duration = filters::parse_duration(value)
+        .or_else(|| value.parse::<u64>().ok().map(Duration::from_secs))
+        .ok_or_else(|| format!("Invalid duration '{}' (e.g. 3600, 30m, 12h, 7d)", value))?;
+
+    if duration.is_zero() || duration > MAX_EXPIRES_IN {
+        return Err(format!(
+            "Invalid duration '{}': must be between 1s and 7d",
+            value
+        ));
+    }
+    Ok(duration)
+}
+
+/// HTTP method of a presigned URL
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PresignMethod {
+    Get,
+    Put,
+}
+
+/// Parse `--method GET|PUT`
+pub fn parse_method(value: &str) -> Result<PresignMethod, String> {
+    match value.to_ascii_uppercase().as_str() {
+        "GET" => Ok(PresignMethod::Get),
+        "PUT" => Ok(PresignMethod::Put),
+        _ => Err(format!("Invalid method: {}. Use GET or PUT", value)),
+    }
+}
+
+/// Parse `--content-length-range MIN-MAX` (sizes like "1K" or "10M")
+pub fn parse_length_range(value: &str) -> Result<(u64, u64), String> {
+    let (min, max) = value
+        .split_once('-')
+        .ok_or_else(|| format!("Invalid range '{}': expected MIN-MAX", value))?;
+    let min = filters::parse_size(min)?;
+    let max = filters::parse_size(max)?;
+    if min > max {
+        return Err(format!("Invalid range '{}': MIN is larger than MAX", value));
+    }
+    Ok((min, max))
+}
+
+/// Conditions of a presigned POST policy for browser uploads
+#[derive(Debug, Clone)]
+pub struct PostPolicy {
+    pub bucket: String,
+    /// The object key, or the prefix keys must start with
+    pub key: String,
+    /// Whether `key` is a prefix: the form then names the object itself
+    pub key_is_prefix: bool,
+    /// Smallest and largest upload accepted, in bytes
+    pub content_length_range: Option<(u64, u64)>,
+}
+
+/// Credentials and scope a POST policy is signed with
+pub struct SigningParams<'a> {
+    pub access_key_id: &'a str,
+    pub secret_access_key: &'a str,
+    pub session_token: Option<&'a str>,
+    pub region: &'a str,
+    pub time: DateTime<Utc>,
+}
+
+impl PostPolicy {
+    /// Form fields of the upload form, signed with SigV4
+    ///
+    /// With a prefix the `key` field is `PREFIX${filename}`, which S3 replaces
+    /// with the name of the uploaded file; a form may also set its own key
+    /// below the prefix.
+    pub fn form_fields(
+        &self,
+        params: &SigningParams,
+        expires_in: Duration,
+    ) -> Result<Vec<(String, String)>, String> {
+        let date = params.time.format("%Y%m%d").to_string();
+        let amz_date = params.time.format("%Y%m%dT%H%M%SZ").to_string();
+        let credential = format!(
+            "{}/{}/{}/s3/aws4_request",
+            params.access_key_id, date, params.region
+        );
+        let expiration =
+            params.time + chrono::Duration::from_std(expires_in).map_err(|e| e.to_string())?;
+
+        let mut conditions = vec![serde_json::json!({ "bucket": self.bucket })];
+        conditions.push(if self.key_is_prefix {
+            serde_json::json!(["starts-with", "$key", self.key])
+        } else {
+            serde_json::json!({ "key": self.key })
+        });
+        if let Some((min, max)) = self.content_length_range {
+            conditions.push(serde_json::json!(["content-length-range", min, max]));
+        }
+        conditions.push(serde_json::json!({ "x-amz-algorithm": "AWS4-HMAC-SHA256" }));
+        conditions.push(serde_json::json!({ "x-amz-credential": credential }));
+        conditions.push(serde_json::json!({ "x-amz-date": amz_date }));
+        if let Some(token) = params.session_token {
+            conditions.push(serde_json::json!({ "x-amz-security-token": token }));
+        }
+
+        let policy = serde_json::json!({
+            "expiration": expiration.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            "conditions": conditions,
+        });
+        let policy = BASE64.encode(policy.to_string());
+
+        let key = signing_key(params.secret_access_key, &date, params.region, "s3");
+        let signature = hex(&hmac_sha256(&key, policy.as_bytes()));
+
+        let key_field = if self.key_is_prefix {
+            format!("{}${{filename}}", self.key)
+        } else {
+            self.key.clone()
+        };
+        let mut fields = vec![
+            ("key".to_string(), key_field),
+            (
+                "x-amz-algorithm".to_string(),
+                "AWS4-HMAC-SHA256".to_string(),
+            ),
+            ("x-amz-credential".to_string(), credential),
+            ("x-amz-date".to_string(), amz_date),
+        ];
+        if let Some(token) = params.session_token {
+            fields.push(("x-amz-security-token".to_string(), token.to_string()));
+        }
+        fields.push(("policy".to_string(), policy));
+        fields.push(("x-amz-signature".to_string(), signature));
+        Ok(fields)
+    }
+}
+
+/// SigV4 signing key for one day, region and service
+fn signing_key(secret: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let key = hmac_sha256(format!("AWS4{}", secret).as_bytes(), date.as_bytes());
+    let key = hmac_sha256(&key, region.as_bytes());
+    let key = hmac_sha256(&key, service.as_bytes());
+    hmac_sha256(&key, b"aws4_request")
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_options() {
+        assert_eq!(parse_expires_in("1h").unwrap(), Duration::from_secs(3600));
+        assert_eq!(parse_expires_in("900").unwrap(), Duration::from_secs(900));
+        assert!(parse_expires_in("8d").is_err());
+        assert!(parse_expires_in("0").is_err());
+        assert!(parse_expires_in("soon").is_err());
+
+        assert_eq!(parse_method("put").unwrap(), PresignMethod::Put);
+        assert!(parse_method("DELETE").is_err());
+
+        assert_eq!(parse_length_range("1-10M").unwrap(), (1, 10 << 20));
+        assert!(parse_length_range("10M-1K").is_err());
+        assert!(parse_length_range("10M").is_err());
+    }
+
+    #[test]
+    fn test_signing_key() {
+        // Example from the AWS Signature Version 4 documentation
+        let key = signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20120215",
+            "us-east-1",
+            "iam",
+        );
+        assert_eq!(
+            hex(&key),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+    }
+
+    #[test]
+    fn test_post_form_fields() {
+        let policy = PostPolicy {
+            bucket: "uploads".to_string(),
+            key: "incoming/".to_string(),
+            key_is_prefix: true,
+            content_length_range: Some((1, 1 << 20)),
+        };
+        let params = SigningParams {
+            access_key_id: "AKIDEXAMPLE",
+            secret_access_key: "secret",
+            session_token: None,
+            region: "eu-west-1",
+            time: DateTime::parse_from_rfc3339("2026-03-01T12:00:00Z")
+                .unwrap()
+                .with_timezone(&Utc),
+        };
+        let fields = policy
+            .form_fields(&params, Duration::from_secs(3600))
+            .unwrap();
+        let field = |name: &str| {
+            fields
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+                .unwrap()
+        };
+
+        assert_eq!(field("key"), "incoming/${filename}");
+        assert_eq!(
+            field("x-amz-credential"),
+            "AKIDEXAMPLE/20260301/eu-west-1/s3/aws4_request"
+        );
+        let document = String::from_utf8(BASE64.decode(field("policy")).unwrap()).unwrap();
+        assert_eq!(
+            document,
+            concat!(
+                r#"{"conditions":[{"bucket":"uploads"},["starts-with","$key","incoming/"],"#,
+                r#"["content-length-range",1,1048576],{"x-amz-algorithm":"AWS4-HMAC-SHA256"},"#,
+                r#"{"x-amz-credential":"AKIDEXAMPLE/20260301/eu-west-1/s3/aws4_request"},"#,
+                r#"{"x-amz-date":"20260301T120000Z"}],"expiration":"2026-03-01T13:00:00Z"}"#
+            )
+        );
+        // HMAC-SHA256 of the base64 policy under the SigV4 key for
+        // secret/20260301/eu-west-1/s3, computed independently
+        assert_eq!(
+            field("x-amz-signature"),
+            "1913ab94ae30e341aa4cb869495c84853e6209787f622ff2922117286df5527f"
+        );
+    }
+}
@@ -28,6 +28,15 @@ impl Default for S3ClientConfig {
     }
 }
 
+impl S3ClientConfig {
+    /// Custom endpoint URL (CLI option > environment)
+    pub fn endpoint_url(&self) -> Option<String> {
+        self.endpoint_url
+            .clone()
+            .or_else(|| env::var("AWS_ENDPOINT_URL").ok())
+    }
+}
+
 /// Initialize and return an S3 client with AWS configuration
 ///
 /// Respects the following environment variables:
@@ -63,40 +72,12 @@ pub async fn create_s3_client(
         }
     }
 
-    // Set up AWS config loader with proper behavior version
-    let mut loader = aws_config::defaults(BehaviorVersion::latest());
-
-    // Set profile if specified (from CLI option or environment)
-    let profile = config
-        .profile
-        .or_else(|| env::var("AWS_PROFILE").ok())
-        .unwrap_or_else(|| "default".to_string());
-
-    if config.debug {
-        eprintln!("Debug: Using AWS profile: {}", profile);
-    }
-
-    loader = loader.profile_name(&profile);
-
-    // Set region (CLI option > environment > config file)
-    if let Some(region) = config.region.or_else(|| env::var("AWS_REGION").ok()) {
-        if config.debug {
-            eprintln!("Debug: Using region: {}", region);
-        }
-        loader = loader.region(aws_sdk_s3::config::Region::new(region));
-    }
-
-    // Load the AWS config (respects AWS_CONFIG_FILE and AWS_SHARED_CREDENTIALS_FILE)
-    let aws_config = loader.load().await;
+    let aws_config = load_aws_config(&config).await;
 
     // Build S3-specific config
     let mut s3_config_builder = aws_sdk_s3::config::Builder::from(&aws_config);
 
-    // Set endpoint URL (CLI option > environment)
-    let endpoint = config
-        .endpoint_url
-        .or_else(|| env::var("AWS_ENDPOINT_URL").ok());
-    if let Some(endpoint) = endpoint {
+    if let Some(endpoint) = config.endpoint_url() {
         if config.debug {
             eprintln!("Debug: Using custom endpoint: {}", endpoint);
         }
@@ -127,6 +108,53 @@ pub async fn create_s3_client(
     Ok(client)
 }
 
+/// Load the shared AWS configuration for the profile and region of `config`
+async fn load_aws_config(config: &S3ClientConfig) -> aws_config::SdkConfig {
+    // Set up AWS config loader with proper behavior version
+    let mut loader = aws_config::defaults(BehaviorVersion::latest());
+
+    // Set profile if specified (from CLI option or environment)
+    let profile = config
+        .profile
+        .clone()
+        .or_else(|| env::var("AWS_PROFILE").ok())
+        .unwrap_or_else(|| "default".to_string());
+
+    if config.debug {
+        eprintln!("Debug: Using AWS profile: {}", profile);
+    }
+
+    loader = loader.profile_name(&profile);
+
+    // Set region (CLI option > environment > config file)
+    if let Some(region) = config
+        .region
+        .clone()
+        .or_else(|| env::var("AWS_REGION").ok())
+    {
+        if config.debug {
+            eprintln!("Debug: Using region: {}", region);
+        }
+        loader = loader.region(aws_sdk_s3::config::Region::new(region));
+    }
+
+    // Load the AWS config (respects AWS_CONFIG_FILE and AWS_SHARED_CREDENTIALS_FILE)
+    loader.load().await
+}
+
+/// Credentials of the configured profile, for signing requests without the client
+pub async fn load_credentials(
+    config: &S3ClientConfig,
+) -> Result<aws_sdk_s3::config::Credentials, Box<dyn std::error::Error>> {
+    use aws_sdk_s3::config::ProvideCredentials;
+
+    let aws_config = load_aws_config(config).await;
+    let provider = aws_config
+        .credentials_provider()
+        .ok_or("No AWS credentials configured")?;
+    Ok(provider.provide_credentials().await?)
+}
+
 /// Load multipart settings from AWS config file
 /// Returns (threshold, chunksize) from [s3] section
 fn load_multipart_settings(profile: &str) -> Result<(u64, u64), Box<dyn std::error::Error>> {
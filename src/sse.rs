@@ -0,0 +1,201 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use md5::{Digest, Md5};
+use std::path::Path;
+
+/// Server-side encryption settings for writes, plus SSE-C keys for reads
+#[derive(Debug, Clone, Default)]
+pub struct SseOptions {
+    /// SSE-S3 (`AES256`) or SSE-KMS (`aws:kms`, `aws:kms:dsse`) for new objects
+    pub sse: Option<aws_sdk_s3::types::ServerSideEncryption>,
+    /// KMS key for SSE-KMS; the bucket's default key when unset
+    pub kms_key_id: Option<String>,
+    /// Customer key for SSE-C: encrypts new objects and reads existing ones
+    pub customer_key: Option<CustomerKey>,
+    /// Customer key of the source object of S3-to-S3 copies
+    pub copy_source_key: Option<CustomerKey>,
+}
+
+/// A 256-bit SSE-C key, held in the encodings S3 expects
+#[derive(Clone)]
+pub struct CustomerKey {
+    key: String,
+    key_md5: String,
+}
+
+impl CustomerKey {
+    pub fn new(key: &[u8]) -> Result<CustomerKey, String> {
+        if key.len() != 32 {
+            return Err(format!(
+                "SSE-C key must be 32 bytes (256 bits), got {}",
+                key.len()
+            ));
+        }
+        Ok(CustomerKey {
+            key: BASE64.encode(key),
+            key_md5: BASE64.encode(Md5::digest(key)),
+        })
+    }
+
+    /// The only algorithm SSE-C supports
+    pub fn algorithm(&self) -> String {
+        "AES256".to_string()
+    }
+
+    /// Base64-encoded key
+    pub fn key(&self) -> String {
+        self.key.clone()
+    }
+
+    /// Base64-encoded MD5 of the key, which S3 uses to check it arrived intact
+    pub fn key_md5(&self) -> String {
+        self.key_md5.clone()
+    }
+}
+
+/// Requests that carry the three SSE-C headers
+///
+/// `None` leaves the headers unset, so the key can be passed along as is.
+pub trait SseCustomerHeaders: Sized {
+    fn sse_customer(self, key: Option<&CustomerKey>) -> Self;
+}
+
+macro_rules! impl_sse_customer_headers {
+    ($($builder:ty),* $(,)?) => {$(
+        impl SseCustomerHeaders for $builder {
+            fn sse_customer(self, key: Option<&CustomerKey>) -> Self {
+                self.set_sse_customer_algorithm(key.map(CustomerKey::algorithm))
+                    .set_sse_customer_key(key.map(CustomerKey::key))
+                    .set_sse_customer_key_md5(key.map(CustomerKey::key_md5))
+            }
+        }
+    )*};
+}
+
+impl_sse_customer_headers!(
+    aws_sdk_s3::operation::complete_multipart_upload::builders::CompleteMultipartUploadFluentBuilder,
+    aws_sdk_s3::operation::copy_object::builders::CopyObjectFluentBuilder,
+    aws_sdk_s3::operation::create_multipart_upload::builders::CreateMultipartUploadFluentBuilder,
+    aws_sdk_s3::operation::get_object::builders::GetObjectFluentBuilder,
+    aws_sdk_s3::operation::head_object::builders::HeadObjectFluentBuilder,
+    aws_sdk_s3::operation::put_object::builders::PutObjectFluentBuilder,
+    aws_sdk_s3::operation::upload_part::builders::UploadPartFluentBuilder,
+);
+
+/// The SSE-C headers for the source object of a copy
+pub trait CopySourceSseCustomerHeaders: Sized {
+    fn copy_source_sse_customer(self, key: Option<&CustomerKey>) -> Self;
+}
+
+impl CopySourceSseCustomerHeaders
+    for aws_sdk_s3::operation::copy_object::builders::CopyObjectFluentBuilder
+{
+    fn copy_source_sse_customer(self, key: Option<&CustomerKey>) -> Self {
+        self.set_copy_source_sse_customer_algorithm(key.map(CustomerKey::algorithm))
+            .set_copy_source_sse_customer_key(key.map(CustomerKey::key))
+            .set_copy_source_sse_customer_key_md5(key.map(CustomerKey::key_md5))
+    }
+}
+
+// Never print key material, e.g. in --debug output
+impl std::fmt::Debug for CustomerKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CustomerKey(md5 {})", self.key_md5)
+    }
+}
+
+/// Parse an SSE-C key argument
+///
+/// Accepts `fileb://PATH` (a file holding the 32 raw key bytes), a base64
+/// encoded key, or the 32 key bytes given literally.
+pub fn parse_customer_key(value: &str) -> Result<CustomerKey, String> {
+    if let Some(path) = value.strip_prefix("fileb://") {
+        let key = std::fs::read(Path::new(path))
+            .map_err(|e| format!("Cannot read SSE-C key file {}: {}", path, e))?;
+        return CustomerKey::new(&key);
+    }
+
+    if value.len() == 32 {
+        return CustomerKey::new(value.as_bytes());
+    }
+
+    let key = BASE64
+        .decode(value.trim())
+        .map_err(|_| "SSE-C key must be 32 bytes, base64 encoded or fileb://PATH".to_string())?;
+    CustomerKey::new(&key)
+}
+
+/// Parse `--sse AES256|aws:kms|aws:kms:dsse`
+pub fn parse_sse(value: &str) -> Result<aws_sdk_s3::types::ServerSideEncryption, String> {
+    use aws_sdk_s3::types::ServerSideEncryption;
+
+    match value {
+        "AES256" => Ok(ServerSideEncryption::Aes256),
+        "aws:kms" => Ok(ServerSideEncryption::AwsKms),
+        "aws:kms:dsse" => Ok(ServerSideEncryption::AwsKmsDsse),
+        _ => Err(format!(
+            "Invalid server-side encryption: {}. Use AES256, aws:kms or aws:kms:dsse",
+            value
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sse_customer_headers() {
+        let config = aws_sdk_s3::Config::builder()
+            .behavior_version_latest()
+            .build();
+        let client = aws_sdk_s3::Client::from_conf(config);
+        let key = CustomerKey::new(&[7u8; 32]).unwrap();
+
+        let request = client.head_object().sse_customer(Some(&key));
+        assert_eq!(
+            request.get_sse_customer_algorithm().as_deref(),
+            Some("AES256")
+        );
+        assert_eq!(request.get_sse_customer_key(), &Some(key.key()));
+        assert_eq!(request.get_sse_customer_key_md5(), &Some(key.key_md5()));
+        assert!(client
+            .get_object()
+            .sse_customer(None)
+            .get_sse_customer_key()
+            .is_none());
+
+        let copy = client.copy_object().copy_source_sse_customer(Some(&key));
+        assert_eq!(copy.get_copy_source_sse_customer_key(), &Some(key.key()));
+        assert!(copy.get_sse_customer_key().is_none());
+    }
+
+    #[test]
+    fn test_parse_customer_key() {
+        let literal = parse_customer_key("0123456789abcdef0123456789abcdef").unwrap();
+        let encoded =
+            parse_customer_key(&BASE64.encode("0123456789abcdef0123456789abcdef")).unwrap();
+        assert_eq!(literal.key(), encoded.key());
+        assert_eq!(
+            literal.key_md5(),
+            BASE64.encode(Md5::digest(b"0123456789abcdef0123456789abcdef"))
+        );
+        assert!(!format!("{:?}", literal).contains(&literal.key()));
+
+        assert!(parse_customer_key("too short").is_err());
+        assert!(parse_customer_key(&BASE64.encode([0u8; 16])).is_err());
+
+        let path = std::env::temp_dir().join(format!("hsc-sse-{}", std::process::id()));
+        std::fs::write(&path, [7u8; 32]).unwrap();
+        let from_file = parse_customer_key(&format!("fileb://{}", path.display())).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(from_file.key(), BASE64.encode([7u8; 32]));
+    }
+
+    #[test]
+    fn test_parse_sse() {
+        assert!(parse_sse("AES256").is_ok());
+        assert!(parse_sse("aws:kms").is_ok());
+        assert!(parse_sse("aes256").is_err());
+    }
+}
@@ -0,0 +1,143 @@
+use aws_sdk_s3::error::{ProvideErrorMetadata, SdkError};
+use aws_sdk_s3::types::{StorageClass, Tier};
+
+/// Parse `--storage-class`, accepting the classes S3 knows (case-insensitive)
+pub fn parse_storage_class(value: &str) -> Result<StorageClass, String> {
+    let upper = value.to_ascii_uppercase();
+    if StorageClass::values().contains(&upper.as_str()) {
+        Ok(StorageClass::from(upper.as_str()))
+    } else {
+        Err(format!(
+            "Invalid storage class: {}. Use one of {}",
+            value,
+            StorageClass::values().join(", ")
+        ))
+    }
+}
+
+/// Parse `--tier Bulk|Standard|Expedited` (case-insensitive)
+pub fn parse_tier(value: &str) -> Result<Tier, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "bulk" => Ok(Tier::Bulk),
+        "standard" => Ok(Tier::Standard),
+        "expedited" => Ok(Tier::Expedited),
+        _ => Err(format!(
+            "Invalid restore tier: {}. Use Bulk, Standard or Expedited",
+            value
+        )),
+    }
+}
+
+/// Whether objects in this class must be restored before they can be read
+pub fn is_archived(storage_class: &str) -> bool {
+    matches!(storage_class, "GLACIER" | "DEEP_ARCHIVE")
+}
+
+/// Replace the error S3 returns for reads of unrestored archived objects
+///
+/// GetObject fails with InvalidObjectState and CopyObject with
+/// ObjectNotInActiveTierError; both become a hint to run `hsc restore`.
+pub fn explain_archived<E, R>(
+    bucket: &str,
+    key: &str,
+    err: SdkError<E, R>,
+) -> Box<dyn std::error::Error>
+where
+    E: ProvideErrorMetadata + std::error::Error + Send + Sync + 'static,
+    R: std::fmt::Debug + Send + Sync + 'static,
+{
+    match err.code() {
+        Some("InvalidObjectState") | Some("ObjectNotInActiveTierError") => format!(
+            "s3://{}/{} is archived and must be restored before it can be read (see `hsc restore`)",
+            bucket, key
+        )
+        .into(),
+        _ => err.into(),
+    }
+}
+
+/// Restore state of an archived object, from the `x-amz-restore` header
+#[derive(Debug, PartialEq)]
+pub enum RestoreStatus {
+    InProgress,
+    /// Restored; the temporary copy is readable until `expiry`
+    Restored {
+        expiry: Option<String>,
+    },
+}
+
+impl RestoreStatus {
+    /// Parse `ongoing-request="false", expiry-date="Fri, 21 Dec 2012 00:00:00 GMT"`
+    pub fn parse(header: &str) -> Option<RestoreStatus> {
+        let ongoing = header_value(header, "ongoing-request")?;
+        if ongoing == "true" {
+            return Some(RestoreStatus::InProgress);
+        }
+        Some(RestoreStatus::Restored {
+            expiry: header_value(header, "expiry-date").map(String::from),
+        })
+    }
+}
+
+impl std::fmt::Display for RestoreStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RestoreStatus::InProgress => write!(f, "in progress"),
+            RestoreStatus::Restored {
+                expiry: Some(expiry),
+            } => write!(f, "restored until {}", expiry),
+            RestoreStatus::Restored { expiry: None } => write!(f, "restored"),
+        }
+    }
+}
+
+/// Value of `name="value"` in a comma-separated header
+fn header_value<'a>(header: &'a str, name: &str) -> Option<&'a str> {
+    let start = header.find(&format!("{}=\"", name))? + name.len() + 2;
+    let len = header[start..].find('"')?;
+    Some(&header[start..start + len])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_storage_class_and_tier() {
+        assert_eq!(
+            parse_storage_class("glacier_ir").unwrap(),
+            StorageClass::GlacierIr
+        );
+        assert_eq!(
+            parse_storage_class("DEEP_ARCHIVE").unwrap(),
+            StorageClass::DeepArchive
+        );
+        assert!(parse_storage_class("COLD").is_err());
+
+        assert_eq!(parse_tier("Bulk").unwrap(), Tier::Bulk);
+        assert_eq!(parse_tier("EXPEDITED").unwrap(), Tier::Expedited);
+        assert!(parse_tier("fast").is_err());
+
+        assert!(is_archived("GLACIER"));
+        assert!(!is_archived("GLACIER_IR"));
+    }
+
+    #[test]
+    fn test_restore_status() {
+        assert_eq!(
+            RestoreStatus::parse("ongoing-request=\"true\""),
+            Some(RestoreStatus::InProgress)
+        );
+
+        let restored = RestoreStatus::parse(
+            "ongoing-request=\"false\", expiry-date=\"Fri, 21 Dec 2012 00:00:00 GMT\"",
+        )
+        .unwrap();
+        assert_eq!(
+            restored.to_string(),
+            "restored until Fri, 21 Dec 2012 00:00:00 GMT"
+        );
+
+        assert_eq!(RestoreStatus::parse("garbage"), None);
+    }
+}
@@ -0,0 +1,234 @@
+use aws_sdk_s3::types::{Tag, Tagging};
+use aws_sdk_s3::Client;
+
+/// Most tags S3 allows on one object
+const MAX_TAGS: usize = 10;
+/// Longest tag key S3 accepts, in characters
+const MAX_KEY_LEN: usize = 128;
+/// Longest tag value S3 accepts, in characters
+const MAX_VALUE_LEN: usize = 256;
+
+/// Tags given on the command line, in order
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagSet(pub Vec<(String, String)>);
+
+impl TagSet {
+    /// Tag set as the URL-encoded `x-amz-tagging` header of uploads and copies
+    pub fn to_header(&self) -> String {
+        self.0
+            .iter()
+            .map(|(key, value)| format!("{}={}", encode_component(key), encode_component(value)))
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+
+    /// Tag set as the body of a PutObjectTagging request
+    pub fn to_tagging(&self) -> Result<Tagging, Box<dyn std::error::Error>> {
+        let tag_set = self
+            .0
+            .iter()
+            .map(|(key, value)| Tag::builder().key(key).value(value).build())
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Tagging::builder().set_tag_set(Some(tag_set)).build()?)
+    }
+
+    /// These tags over an object's existing ones, e.g. for `tag set --merge`
+    pub fn merged_over(&self, existing: &[Tag]) -> TagSet {
+        let mut tags: Vec<(String, String)> = existing
+            .iter()
+            .filter(|tag| !self.0.iter().any(|(key, _)| key == tag.key()))
+            .map(|tag| (tag.key().to_string(), tag.value().to_string()))
+            .collect();
+        tags.extend(self.0.iter().cloned());
+        TagSet(tags)
+    }
+}
+
+/// Parse a tag set given as `key=value&key2=value2`
+pub fn parse_tags(value: &str) -> Result<TagSet, String> {
+    let mut tags: Vec<(String, String)> = Vec::new();
+
+    for pair in value.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid tag '{}': expected KEY=VALUE", pair))?;
+
+        if key.is_empty() || key.chars().count() > MAX_KEY_LEN {
+            return Err(format!(
+                "Invalid tag key '{}': must be 1 to {} characters",
+                key, MAX_KEY_LEN
+            ));
+        }
+        if value.chars().count() > MAX_VALUE_LEN {
+            return Err(format!(
+                "Invalid tag value for '{}': longer than {} characters",
+                key, MAX_VALUE_LEN
+            ));
+        }
+        if tags.iter().any(|(existing, _)| existing == key) {
+            return Err(format!("Tag '{}' is given more than once", key));
+        }
+        tags.push((key.to_string(), value.to_string()));
+    }
+
+    if tags.is_empty() {
+        return Err("No tags given: expected KEY=VALUE[&KEY2=VALUE2...]".to_string());
+    }
+    if tags.len() > MAX_TAGS {
+        return Err(format!(
+            "Too many tags: {} given, S3 allows {}",
+            tags.len(),
+            MAX_TAGS
+        ));
+    }
+    Ok(TagSet(tags))
+}
+
+/// Tags of an object in the `key=value&key2=value2` form `--tags` accepts
+pub fn format_tags(tags: &[Tag]) -> String {
+    tags.iter()
+        .map(|tag| format!("{}={}", tag.key(), tag.value()))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Percent-encode everything but unreserved characters (RFC 3986)
+fn encode_component(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// A `--tag KEY` or `--tag KEY=VALUE` condition
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagPredicate {
+    key: String,
+    /// Required value; any value matches when unset
+    value: Option<String>,
+}
+
+/// Parse `--tag KEY[=VALUE]`
+pub fn parse_tag_predicate(value: &str) -> Result<TagPredicate, String> {
+    let (key, value) = match value.split_once('=') {
+        Some((key, value)) => (key, Some(value.to_string())),
+        None => (value, None),
+    };
+    if key.is_empty() {
+        return Err("Invalid --tag: expected KEY or KEY=VALUE".to_string());
+    }
+    Ok(TagPredicate {
+        key: key.to_string(),
+        value,
+    })
+}
+
+/// Objects must carry every tag given with `--tag`
+///
+/// Listings do not include tags, so each object that passes the other
+/// filters costs a GetObjectTagging request.
+#[derive(Debug, Default)]
+pub struct TagFilter {
+    predicates: Vec<TagPredicate>,
+}
+
+impl TagFilter {
+    pub fn new(predicates: Vec<TagPredicate>) -> Self {
+        TagFilter { predicates }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.predicates.is_empty()
+    }
+
+    /// Check a tag set against every predicate
+    pub fn matches(&self, tags: &[Tag]) -> bool {
+        self.predicates.iter().all(|predicate| {
+            tags.iter().any(|tag| {
+                tag.key() == predicate.key
+                    && predicate.value.as_deref().is_none_or(|v| tag.value() == v)
+            })
+        })
+    }
+
+    /// Fetch the tags of an object and check them; no request without predicates
+    pub async fn matches_object(
+        &self,
+        client: &Client,
+        bucket: &str,
+        key: &str,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        if self.is_empty() {
+            return Ok(true);
+        }
+        let response = client
+            .get_object_tagging()
+            .bucket(bucket)
+            .key(key)
+            .send()
+            .await?;
+        Ok(self.matches(response.tag_set()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tags_and_header() {
+        let tags = parse_tags("env=prod&owner=data team&empty=").unwrap();
+        assert_eq!(tags.0.len(), 3);
+        assert_eq!(tags.0[2], ("empty".to_string(), String::new()));
+        assert_eq!(tags.to_header(), "env=prod&owner=data%20team&empty=");
+
+        let existing = vec![
+            Tag::builder().key("env").value("dev").build().unwrap(),
+            Tag::builder().key("keep").value("1").build().unwrap(),
+        ];
+        let merged = parse_tags("env=prod").unwrap().merged_over(&existing);
+        assert_eq!(
+            merged.0,
+            vec![
+                ("keep".to_string(), "1".to_string()),
+                ("env".to_string(), "prod".to_string())
+            ]
+        );
+
+        assert!(parse_tags("env").is_err());
+        assert!(parse_tags("=prod").is_err());
+        assert!(parse_tags("a=1&a=2").is_err());
+        assert!(parse_tags("").is_err());
+        let eleven: Vec<String> = (0..11).map(|i| format!("k{}=v", i)).collect();
+        assert!(parse_tags(&eleven.join("&")).is_err());
+    }
+
+    #[test]
+    fn test_tag_filter() {
+        let tags = vec![
+            Tag::builder().key("env").value("prod").build().unwrap(),
+            Tag::builder().key("team").value("data").build().unwrap(),
+        ];
+        assert_eq!(format_tags(&tags), "env=prod&team=data");
+
+        let filter = |values: &[&str]| {
+            TagFilter::new(
+                values
+                    .iter()
+                    .map(|v| parse_tag_predicate(v).unwrap())
+                    .collect(),
+            )
+        };
+        assert!(filter(&[]).matches(&tags));
+        assert!(filter(&["env=prod", "team"]).matches(&tags));
+        assert!(!filter(&["env=dev"]).matches(&tags));
+        assert!(!filter(&["retain"]).matches(&tags));
+        assert!(parse_tag_predicate("=x").is_err());
+    }
+}
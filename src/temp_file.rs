@@ -0,0 +1,137 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Temp files that still need to be removed if the process is interrupted
+static PENDING: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// A temporary file next to its final destination
+///
+/// The file is removed when dropped unless it was renamed into place with
+/// `persist`, so a failed download never leaves a partial file behind.
+pub struct TempFile {
+    path: PathBuf,
+    persisted: bool,
+}
+
+impl TempFile {
+    /// Reserve a temp file path in the same directory as `target`
+    pub fn new(target: &Path) -> Self {
+        let file_name = target
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let temp_name = format!(
+            ".{}.hsc-{}-{}.tmp",
+            file_name,
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = target.with_file_name(temp_name);
+
+        PENDING.lock().unwrap().push(path.clone());
+
+        TempFile {
+            path,
+            persisted: false,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Atomically move the temp file over `target`
+    pub async fn persist(mut self, target: &Path) -> std::io::Result<()> {
+        tokio::fs::rename(&self.path, target).await?;
+        self.persisted = true;
+        Ok(())
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !self.persisted {
+            let _ = std::fs::remove_file(&self.path);
+        }
+        PENDING.lock().unwrap().retain(|p| p != &self.path);
+    }
+}
+
+/// Remove every pending temp file
+pub fn remove_all() {
+    if let Ok(pending) = PENDING.lock() {
+        for path in pending.iter() {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Remove pending temp files and exit when the process receives SIGINT or SIGTERM
+pub fn cleanup_on_signal() {
+    tokio::spawn(async {
+        wait_for_signal().await;
+        remove_all();
+        std::process::exit(130);
+    });
+}
+
+/// Wait for SIGINT (or SIGTERM on Unix); never resolves if handlers cannot be installed
+async fn wait_for_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+            tokio::select! {
+                Ok(()) = tokio::signal::ctrl_c() => return,
+                Some(()) = terminate.recv() => return,
+                else => {}
+            }
+        }
+    }
+
+    if tokio::signal::ctrl_c().await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_temp_file_removed_on_drop() {
+        let dir = std::env::temp_dir().join(format!("hsc-temp-drop-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let target = dir.join("file.txt");
+
+        let temp = TempFile::new(&target);
+        std::fs::write(temp.path(), b"partial").unwrap();
+        let temp_path = temp.path().to_path_buf();
+        assert_eq!(temp_path.parent(), target.parent());
+        drop(temp);
+
+        assert!(!temp_path.exists());
+        assert!(!target.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_temp_file_persist() {
+        let dir = std::env::temp_dir().join(format!("hsc-temp-persist-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let target = dir.join("file.txt");
+        std::fs::write(&target, b"old").unwrap();
+
+        let temp = TempFile::new(&target);
+        std::fs::write(temp.path(), b"new").unwrap();
+        let temp_path = temp.path().to_path_buf();
+        temp.persist(&target).await.unwrap();
+
+        assert!(!temp_path.exists());
+        assert_eq!(std::fs::read(&target).unwrap(), b"new");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
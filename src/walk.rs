@@ -0,0 +1,276 @@
+use crate::filters::FileFilter;
+use std::path::{Path, PathBuf};
+use walkdir::{DirEntry, WalkDir};
+
+/// What a local directory entry should be transferred as
+#[derive(Debug, Clone, PartialEq)]
+pub enum EntryKind {
+    /// Regular file (or a symlink to one when following symlinks)
+    File,
+    /// Symbolic link stored as a marker object holding its target
+    Symlink(String),
+}
+
+/// A transferable entry found while walking a local directory
+#[derive(Debug, Clone)]
+pub struct LocalEntry {
+    pub path: PathBuf,
+    /// Path relative to the walk root, always using '/' separators
+    pub relative: String,
+    pub kind: EntryKind,
+}
+
+impl LocalEntry {
+    /// Entry for a single file given on the command line, named by its file
+    /// name; with `follow_symlinks` a symlink stands for the file it points to
+    pub fn from_path(path: &Path, follow_symlinks: bool) -> Result<LocalEntry, String> {
+        let metadata = std::fs::symlink_metadata(path)
+            .map_err(|e| format!("Cannot access {}: {}", path.display(), e))?;
+        let kind = if metadata.is_symlink() && !follow_symlinks {
+            let target = std::fs::read_link(path)
+                .map_err(|e| format!("Cannot read symlink {}: {}", path.display(), e))?;
+            let target = target
+                .to_str()
+                .ok_or_else(|| format!("Symlink {} has a non-UTF-8 target", path.display()))?;
+            EntryKind::Symlink(target.to_string())
+        } else if path.is_dir() {
+            return Err(format!(
+                "{} is a directory (use --recursive)",
+                path.display()
+            ));
+        } else {
+            EntryKind::File
+        };
+
+        Ok(LocalEntry {
+            path: path.to_path_buf(),
+            relative: path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            kind,
+        })
+    }
+
+    /// Full path as UTF-8 (guaranteed, non-UTF-8 entries are skipped by `walk`)
+    pub fn path_str(&self) -> &str {
+        self.path.to_str().unwrap_or_default()
+    }
+
+    /// Metadata of the entry itself (`lstat` for symlinks stored as markers)
+    pub fn metadata(&self) -> std::io::Result<std::fs::Metadata> {
+        match &self.kind {
+            EntryKind::File => std::fs::metadata(&self.path),
+            EntryKind::Symlink(_) => std::fs::symlink_metadata(&self.path),
+        }
+    }
+
+    /// Size of the data that is transferred for this entry
+    pub fn size(&self) -> std::io::Result<u64> {
+        match &self.kind {
+            EntryKind::File => Ok(std::fs::metadata(&self.path)?.len()),
+            EntryKind::Symlink(target) => Ok(target.len() as u64),
+        }
+    }
+}
+
+/// Walk a local directory and yield files and symlinks in a stable order
+///
+/// With `follow_symlinks`, symlinks to files are transferred as the files they
+/// point to and symlinked directories are descended into (loops are reported
+/// and skipped). Otherwise symlinks are yielded as `EntryKind::Symlink`.
+///
+/// Entries that cannot be transferred are reported on stderr instead of being
+/// silently dropped: unreadable entries, broken links, sockets, FIFOs and
+/// devices, and paths that are not valid UTF-8 (they cannot form an S3 key).
+///
+/// Directories excluded by `filter` are not descended into. Yielded entries
+/// still need to be checked with `FileFilter::matches`.
+pub fn walk<'a>(
+    root: &'a Path,
+    follow_symlinks: bool,
+    filter: &'a FileFilter,
+) -> impl Iterator<Item = LocalEntry> + 'a {
+    WalkDir::new(root)
+        .follow_links(follow_symlinks)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(move |entry| !is_pruned(root, entry, filter))
+        .filter_map(move |entry| {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    eprintln!("Warning: skipping {}", e);
+                    return None;
+                }
+            };
+
+            let file_type = entry.file_type();
+            if file_type.is_dir() {
+                return None;
+            }
+
+            let path = entry.path();
+            if path.to_str().is_none() {
+                eprintln!(
+                    "Warning: skipping non-UTF-8 path: {}",
+                    path.to_string_lossy()
+                );
+                return None;
+            }
+
+            let kind = if file_type.is_file() {
+                EntryKind::File
+            } else if file_type.is_symlink() {
+                match std::fs::read_link(path) {
+                    Ok(target) => match target.to_str() {
+                        Some(target) => EntryKind::Symlink(target.to_string()),
+                        None => {
+                            eprintln!(
+                                "Warning: skipping symlink with non-UTF-8 target: {}",
+                                path.display()
+                            );
+                            return None;
+                        }
+                    },
+                    Err(e) => {
+                        eprintln!("Warning: skipping symlink {}: {}", path.display(), e);
+                        return None;
+                    }
+                }
+            } else {
+                eprintln!("Warning: skipping special file: {}", path.display());
+                return None;
+            };
+
+            Some(LocalEntry {
+                path: path.to_path_buf(),
+                relative: relative_path(root, path),
+                kind,
+            })
+        })
+}
+
+/// Find empty directories below `root` (not `root` itself)
+///
+/// Returns paths relative to `root` with a trailing '/', the form used for
+/// S3 directory marker keys. Directories excluded by `filter` are skipped
+/// along with everything below them.
+pub fn empty_dirs(root: &Path, follow_symlinks: bool, filter: &FileFilter) -> Vec<String> {
+    WalkDir::new(root)
+        .min_depth(1)
+        .follow_links(follow_symlinks)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| !is_pruned(root, entry, filter))
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_dir())
+        .filter(|entry| {
+            std::fs::read_dir(entry.path())
+                .map(|mut entries| entries.next().is_none())
+                .unwrap_or(false)
+        })
+        .filter(|entry| entry.path().to_str().is_some())
+        .map(|entry| format!("{}/", relative_path(root, entry.path())))
+        .collect()
+}
+
+/// Whether a directory below the walk root is excluded by the filter
+fn is_pruned(root: &Path, entry: &DirEntry, filter: &FileFilter) -> bool {
+    entry.depth() > 0
+        && entry.file_type().is_dir()
+        && !filter.includes_dir(&relative_path(root, entry.path()))
+}
+
+/// Path of `path` relative to `root` with '/' separators
+fn relative_path(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let relative = if relative.as_os_str().is_empty() {
+        // Walking a single file yields the root itself
+        path.file_name().map(Path::new).unwrap_or(relative)
+    } else {
+        relative
+    };
+
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative_path() {
+        let root = Path::new("/data");
+        assert_eq!(relative_path(root, Path::new("/data/a/b.txt")), "a/b.txt");
+        assert_eq!(relative_path(root, Path::new("/data")), "data");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_walk_symlink_policies() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let root = std::env::temp_dir().join(format!("hsc-walk-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("dir")).unwrap();
+        std::fs::write(root.join("dir/file.txt"), b"data").unwrap();
+        std::os::unix::fs::symlink("dir/file.txt", root.join("link.txt")).unwrap();
+        std::os::unix::fs::symlink("dir", root.join("linkdir")).unwrap();
+        // Skipped by the walk; file systems that require UTF-8 names (APFS)
+        // refuse to create it, which leaves the results unchanged
+        let _ = std::fs::write(root.join(std::ffi::OsStr::from_bytes(b"bad\xffname")), b"x");
+
+        let followed: Vec<_> = walk(&root, true, &FileFilter::default())
+            .map(|e| (e.relative, e.kind))
+            .collect();
+        assert_eq!(
+            followed,
+            vec![
+                ("dir/file.txt".to_string(), EntryKind::File),
+                ("link.txt".to_string(), EntryKind::File),
+                ("linkdir/file.txt".to_string(), EntryKind::File),
+            ]
+        );
+
+        let not_followed: Vec<_> = walk(&root, false, &FileFilter::default())
+            .map(|e| (e.relative, e.kind))
+            .collect();
+        assert_eq!(
+            not_followed,
+            vec![
+                ("dir/file.txt".to_string(), EntryKind::File),
+                (
+                    "link.txt".to_string(),
+                    EntryKind::Symlink("dir/file.txt".to_string())
+                ),
+                ("linkdir".to_string(), EntryKind::Symlink("dir".to_string())),
+            ]
+        );
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_empty_dirs() {
+        let root = std::env::temp_dir().join(format!("hsc-walk-empty-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("full")).unwrap();
+        std::fs::create_dir_all(root.join("empty/nested")).unwrap();
+        std::fs::create_dir_all(root.join("other")).unwrap();
+        std::fs::write(root.join("full/file.txt"), b"data").unwrap();
+
+        assert_eq!(
+            empty_dirs(&root, true, &FileFilter::default()),
+            vec!["empty/nested/", "other/"]
+        );
+
+        let filter = FileFilter::new(vec![], vec!["empty".to_string()]).unwrap();
+        assert_eq!(empty_dirs(&root, true, &filter), vec!["other/"]);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,215 @@
+use crate::filters::FileFilter;
+use crate::path_utils::relative_key;
+use aws_sdk_s3::types::Object;
+use aws_sdk_s3::Client;
+use regex::Regex;
+
+/// A shell-style wildcard pattern for S3 keys
+///
+/// `*` and `?` match within one path component and `**` matches across '/'
+/// (`a/**/b` also matches `a/b`). Other characters, including `[` and `\`,
+/// are literal, so existing keys never change meaning, and a key containing
+/// `*` or `?` still matches itself.
+pub struct WildcardPattern {
+    regex: Regex,
+    /// Literal text before the first wildcard
+    prefix: String,
+}
+
+impl WildcardPattern {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let mut regex = String::from("^");
+        let mut rest = pattern;
+
+        while let Some(c) = rest.chars().next() {
+            let at_component_start = rest.len() == pattern.len() || regex.ends_with('/');
+            if rest.starts_with("**/") && at_component_start {
+                regex.push_str("(?:.*/)?");
+                rest = &rest[3..];
+            } else if rest.starts_with("**") {
+                regex.push_str(".*");
+                rest = &rest[2..];
+            } else {
+                match c {
+                    '*' => regex.push_str("[^/]*"),
+                    '?' => regex.push_str("[^/]"),
+                    _ => regex.push_str(&regex::escape(&c.to_string())),
+                }
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+        regex.push('$');
+
+        let regex =
+            Regex::new(&regex).map_err(|e| format!("Invalid wildcard '{}': {}", pattern, e))?;
+        let prefix = pattern[..pattern.find(['*', '?']).unwrap_or(pattern.len())].to_string();
+
+        Ok(WildcardPattern { regex, prefix })
+    }
+
+    /// Longest literal prefix, used as the `list_objects_v2` prefix
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    pub fn matches(&self, key: &str) -> bool {
+        self.regex.is_match(key)
+    }
+
+    /// Path of a matching key below the directory holding the first wildcard
+    ///
+    /// `logs/2024-*/app.gz` matching `logs/2024-01/app.gz` gives
+    /// `2024-01/app.gz`, the path used at the destination.
+    pub fn relative<'a>(&self, key: &'a str) -> &'a str {
+        let base = match self.prefix.rfind('/') {
+            Some(i) => &self.prefix[..=i],
+            None => "",
+        };
+        key.strip_prefix(base).unwrap_or(key)
+    }
+}
+
+/// Check whether an S3 key is a wildcard pattern rather than a literal key
+///
+/// With `literal_keys` (`--no-wildcards`) `*` and `?` are ordinary characters.
+pub fn has_wildcard(key: &str, literal_keys: bool) -> bool {
+    !literal_keys && key.contains(['*', '?'])
+}
+
+/// List the objects of a bucket whose keys match a wildcard pattern
+pub async fn list_matching(
+    client: &Client,
+    bucket: &str,
+    pattern: &WildcardPattern,
+) -> Result<Vec<Object>, Box<dyn std::error::Error>> {
+    let mut objects = Vec::new();
+    let mut continuation_token: Option<String> = None;
+
+    loop {
+        let mut request = client.list_objects_v2().bucket(bucket);
+
+        if !pattern.prefix().is_empty() {
+            request = request.prefix(pattern.prefix());
+        }
+
+        if let Some(token) = continuation_token {
+            request = request.continuation_token(token);
+        }
+
+        let response = request.send().await?;
+
+        objects.extend(
+            response
+                .contents()
+                .iter()
+                .filter(|obj| obj.key().is_some_and(|key| pattern.matches(key)))
+                .cloned(),
+        );
+
+        if response.is_truncated() == Some(true) {
+            continuation_token = response.next_continuation_token().map(|s| s.to_string());
+        } else {
+            break;
+        }
+    }
+
+    Ok(objects)
+}
+
+/// List the objects below a prefix or matching a wildcard, after filters
+pub async fn list_objects(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+    filter: &FileFilter,
+    literal_keys: bool,
+) -> Result<Vec<Object>, Box<dyn std::error::Error>> {
+    if has_wildcard(key, literal_keys) {
+        let pattern = WildcardPattern::new(key)?;
+        let objects = list_matching(client, bucket, &pattern).await?;
+        return Ok(objects
+            .into_iter()
+            .filter(|obj| {
+                obj.key()
+                    .is_some_and(|k| filter.matches_object(pattern.relative(k), obj))
+            })
+            .collect());
+    }
+
+    let mut objects = Vec::new();
+    let mut continuation_token: Option<String> = None;
+
+    loop {
+        let mut request = client.list_objects_v2().bucket(bucket);
+
+        if !key.is_empty() {
+            request = request.prefix(key);
+        }
+
+        if let Some(token) = continuation_token {
+            request = request.continuation_token(token);
+        }
+
+        let response = request.send().await?;
+
+        for obj in response.contents() {
+            if let Some(obj_key) = obj.key() {
+                // Apply filters
+                if filter.matches_object(relative_key(obj_key, key), obj) {
+                    objects.push(obj.clone());
+                }
+            }
+        }
+
+        if response.is_truncated() == Some(true) {
+            continuation_token = response.next_continuation_token().map(|s| s.to_string());
+        } else {
+            break;
+        }
+    }
+
+    Ok(objects)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_star_and_question_mark() {
+        let pattern = WildcardPattern::new("logs/2024-*/app?.gz").unwrap();
+        assert_eq!(pattern.prefix(), "logs/2024-");
+        assert!(pattern.matches("logs/2024-01/app1.gz"));
+        assert!(!pattern.matches("logs/2024-01/deep/app1.gz"));
+        assert!(!pattern.matches("logs/2024-01/app10.gz"));
+        assert_eq!(pattern.relative("logs/2024-01/app1.gz"), "2024-01/app1.gz");
+    }
+
+    #[test]
+    fn test_double_star() {
+        let pattern = WildcardPattern::new("data/**/*.csv").unwrap();
+        assert!(pattern.matches("data/a.csv"));
+        assert!(pattern.matches("data/x/y/a.csv"));
+        assert!(!pattern.matches("other/a.csv"));
+
+        let pattern = WildcardPattern::new("data/part**.csv").unwrap();
+        assert!(pattern.matches("data/part-1/x.csv"));
+        assert_eq!(pattern.relative("data/part-1/x.csv"), "part-1/x.csv");
+    }
+
+    #[test]
+    fn test_literal_characters() {
+        assert!(!has_wildcard("file[1].txt", false));
+        assert!(has_wildcard("logs/*.gz", false));
+        assert!(!has_wildcard("logs/*.gz", true));
+        let pattern = WildcardPattern::new("a+b/[x]*.txt").unwrap();
+        assert_eq!(pattern.prefix(), "a+b/[x]");
+        assert!(pattern.matches("a+b/[x]1.txt"));
+        assert!(!pattern.matches("a+b/x1.txt"));
+
+        // A literal key containing a wildcard character still matches itself
+        assert!(WildcardPattern::new("what?.txt")
+            .unwrap()
+            .matches("what?.txt"));
+    }
+}